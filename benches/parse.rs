@@ -0,0 +1,58 @@
+//! Compares parsing an index entry from a slice versus streaming it from a reader
+
+fn main() {
+    let td = tempfile::tempdir().unwrap();
+    let lock = tame_index::index::FileLock::unlocked();
+
+    // windows-sys is one of the largest index entries on crates.io, both in
+    // terms of number of versions and size of each version
+    let index_path = {
+        let loc = tame_index::IndexLocation {
+            url: tame_index::IndexUrl::CratesIoSparse,
+            root: tame_index::IndexPath::Exact(td.path().join("index").try_into().unwrap()),
+        };
+
+        let rsi = tame_index::index::RemoteSparseIndex::new(
+            tame_index::SparseIndex::new(loc).unwrap(),
+            tame_index::external::reqwest::blocking::ClientBuilder::new()
+                .build()
+                .unwrap(),
+        );
+
+        let krate = rsi
+            .krate("windows-sys".try_into().unwrap(), false, &lock)
+            .expect("failed to retrieve windows-sys")
+            .expect("windows-sys doesn't exist?");
+
+        let path = td.path().join("windows-sys");
+        let mut file = std::fs::File::create(&path).unwrap();
+        krate.write_json_lines(&mut file).unwrap();
+        path
+    };
+
+    let label = "parse_windows_sys";
+    let cfg = tiny_bench::BenchmarkConfig {
+        num_samples: 100,
+        ..Default::default()
+    };
+
+    tiny_bench::bench_with_configuration_labeled(label, &cfg, || {
+        let contents = std::fs::read(&index_path).unwrap();
+        tame_index::IndexKrate::from_slice(&contents).unwrap()
+    });
+    tiny_bench::bench_with_configuration_labeled(label, &cfg, || {
+        let file = std::fs::File::open(&index_path).unwrap();
+        tame_index::IndexKrate::from_reader(std::io::BufReader::new(file)).unwrap()
+    });
+    tiny_bench::bench_with_configuration_labeled(label, &cfg, || {
+        let file = std::fs::File::open(&index_path).unwrap();
+        let mut highest = None;
+        tame_index::IndexKrate::for_each_version(std::io::BufReader::new(file), |iv| {
+            if !iv.is_yanked() {
+                highest = Some(iv);
+            }
+        })
+        .unwrap();
+        highest
+    });
+}
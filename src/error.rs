@@ -15,16 +15,27 @@ pub enum Error {
     /// was not valid utf-8
     #[error("unable to use non-utf8 path {:?}", .0)]
     NonUtf8Path(std::path::PathBuf),
+    /// The cargo home directory, either from `CARGO_HOME` or the default
+    /// location, was not valid utf-8
+    #[error("the cargo home directory {:?} is not valid utf-8", .0)]
+    NonUtf8CargoHome(std::path::PathBuf),
     /// An environment variable was located, but had a non-utf8 value
     #[error("environment variable {} has a non-utf8 value", .0)]
     NonUtf8EnvVar(std::borrow::Cow<'static, str>),
     /// A user-provided string was not a valid crate name
     #[error(transparent)]
     InvalidKrateName(#[from] InvalidKrateName),
-    /// The user specified a registry name that did not exist in any searched
-    /// .cargo/config.toml
-    #[error("registry '{}' was not located in any .cargo/config.toml", .0)]
-    UnknownRegistry(String),
+    /// The user specified a registry name that was not set via its environment
+    /// variable or in any searched cargo config
+    #[error("registry '{name}' was not set via {env} or in any of the searched cargo configs {searched:?}")]
+    UnknownRegistry {
+        /// The name of the registry
+        name: String,
+        /// The environment variable that was checked for the registry's index
+        env: String,
+        /// The cargo config files that were searched, in order
+        searched: Vec<crate::PathBuf>,
+    },
     /// An I/O error
     #[error(transparent)]
     Io(#[from] std::io::Error),
@@ -43,6 +54,10 @@ pub enum Error {
     /// An index entry did not contain any versions
     #[error("index entry contained no versions for the crate")]
     NoCrateVersions,
+    /// An index entry violated one of the invariants checked by
+    /// [`IndexKrate::validate`](crate::IndexKrate::validate)
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
     /// Failed to handle an HTTP response or request
     #[error(transparent)]
     Http(#[from] HttpError),
@@ -53,6 +68,10 @@ pub enum Error {
     /// Failed to parse a semver version or requirement
     #[error(transparent)]
     Semver(#[from] semver::Error),
+    /// A `rust-version` was not a valid, possibly partial, version without a
+    /// pre-release or build metadata
+    #[error("'{0}' is not a valid rust-version")]
+    InvalidRustVersion(String),
     /// A local registry is invalid
     #[cfg(feature = "local")]
     #[error(transparent)]
@@ -60,6 +79,13 @@ pub enum Error {
     /// Failed to lock a file
     #[error(transparent)]
     Lock(#[from] crate::utils::flock::FileLockError),
+    /// A config override was not a TOML dotted key expression, eg.
+    /// `registry.default = "my-registry"`
+    #[error("config override `{0}` is not a `KEY=VALUE` TOML dotted key expression")]
+    InvalidConfigOverride(String),
+    /// A chain of source replacements could not be resolved
+    #[error(transparent)]
+    SourceReplacement(#[from] SourceReplacementError),
 }
 
 impl From<std::path::PathBuf> for Error {
@@ -69,7 +95,8 @@ impl From<std::path::PathBuf> for Error {
 }
 
 /// Various kinds of reserved names disallowed by cargo
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ReservedNameKind {
     /// The name is a Rust keyword
     Keyword,
@@ -79,6 +106,10 @@ pub enum ReservedNameKind {
     Windows,
     /// The name conflicts with a Rust std library name
     Standard,
+    /// The name is reserved by crates.io, this kind is only reported by
+    /// [`KrateName::reserved_reason`](crate::KrateName::reserved_reason) and
+    /// never causes validation to fail
+    CratesIo,
 }
 
 impl std::fmt::Display for ReservedNameKind {
@@ -88,10 +119,31 @@ impl std::fmt::Display for ReservedNameKind {
             Self::Artifact => f.write_str("cargo artifact"),
             Self::Windows => f.write_str("windows reserved"),
             Self::Standard => f.write_str("rustlang std library"),
+            Self::CratesIo => f.write_str("crates.io reserved"),
         }
     }
 }
 
+/// The reason a crate name is reserved or otherwise problematic, see
+/// [`KrateName::reserved_reason`](crate::KrateName::reserved_reason)
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct ReservedReason {
+    /// The reserved name the crate name matched
+    pub reserved: &'static str,
+    /// The kind of the reserved name
+    pub kind: ReservedNameKind,
+}
+
+impl std::fmt::Display for ReservedReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the name '{}' is reserved as '{}'",
+            self.reserved, self.kind
+        )
+    }
+}
+
 /// Errors that can occur when validating a crate name
 #[derive(Debug, thiserror::Error)]
 pub enum InvalidKrateName {
@@ -116,11 +168,59 @@ pub enum InvalidKrateName {
     },
 }
 
+/// A rule violated by an [`IndexKrate`](crate::IndexKrate), see
+/// [`IndexKrate::validate`](crate::IndexKrate::validate)
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ValidationError {
+    /// The crate had no versions
+    #[error("index entry contained no versions for the crate")]
+    NoVersions,
+    /// A version's name differed from the name of the first version
+    #[error(
+        "version '{version}' @ {index} has the name '{name}', but the crate is named '{expected}'"
+    )]
+    NameMismatch {
+        /// The index of the version
+        index: usize,
+        /// The version string
+        version: String,
+        /// The name of the version
+        name: String,
+        /// The name of the first version of the crate
+        expected: String,
+    },
+    /// A version was not a valid semver version
+    #[error("version '{version}' @ {index} is not a valid semver version")]
+    InvalidVersion {
+        /// The index of the version
+        index: usize,
+        /// The version string
+        version: String,
+        /// The parse failure
+        #[source]
+        source: semver::Error,
+    },
+    /// A version appeared more than once
+    #[error("version '{version}' @ {index} is a duplicate of the version @ {original}")]
+    DuplicateVersion {
+        /// The index of the duplicate
+        index: usize,
+        /// The index of the version it duplicates
+        original: usize,
+        /// The version string
+        version: String,
+    },
+}
+
 /// An error pertaining to a bad URL provided to the API
 #[derive(Debug, thiserror::Error)]
-#[error("the url '{url}' is invalid")]
+#[error("the url '{}' is invalid", crate::utils::redact_url(.url))]
 pub struct InvalidUrl {
     /// The invalid url
+    ///
+    /// Note that this may contain credentials, the [`std::fmt::Display`]
+    /// implementation redacts them via [`crate::utils::redact_url`]
     pub url: String,
     /// The reason it is invalid
     pub source: InvalidUrlError,
@@ -138,9 +238,69 @@ pub enum InvalidUrlError {
     /// Unable to find the `<scheme>://`
     #[error("the scheme is missing")]
     MissingScheme,
+    /// A local path was used instead of a `file://` url
+    #[error(
+        "local paths must be specified as a `file://` url, eg. `registry+file:///path/to/index`"
+    )]
+    LocalPath,
     /// Attempted to construct a git index with a sparse URL
     #[error("attempted to create a git index for a sparse URL")]
     SparseForGit,
+    /// The `sparse+` scheme modifier was used with a scheme other than `http`
+    /// or `https`
+    #[error("sparse indices can only be accessed via `http` or `https`")]
+    SparseNonHttp,
+}
+
+/// A chain of [source replacements](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
+/// could not be resolved
+#[derive(Debug, thiserror::Error)]
+#[error("failed to resolve source replacement '{}'", .chain.join("' -> '"))]
+pub struct SourceReplacementError {
+    /// The names of the sources in the chain, starting with the replaced source
+    pub chain: Vec<String>,
+    /// The reason the chain could not be resolved
+    pub source: SourceReplacementIssue,
+}
+
+/// The specific reason a chain of source replacements could not be resolved
+#[derive(Debug, thiserror::Error)]
+pub enum SourceReplacementIssue {
+    /// A source in the chain replaces a source earlier in the chain
+    #[error("the replacements form a cycle")]
+    Cycle,
+    /// The chain is longer than the maximum number of replacements
+    #[error("the chain exceeds the maximum of {0} replacements")]
+    TooLong(usize),
+    /// The last source in the chain is not defined as either a source or a
+    /// registry
+    #[error("the last source is not defined in either `[source]` or `[registries]`")]
+    Undefined,
+    /// The last source in the chain is not a kind of source this crate supports
+    #[error("the last source does not have a `registry`, `local-registry`, or `directory` key")]
+    UnknownKind,
+}
+
+/// Errors that can occur decoding a hexadecimal string, see
+/// [`decode_hex`](crate::utils::decode_hex)
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum HexError {
+    /// The string was not twice the length of the output
+    #[error("expected hex string with length {expected} but got length {len}")]
+    InvalidLength {
+        /// The expected length of the string
+        expected: usize,
+        /// The actual length of the string
+        len: usize,
+    },
+    /// The string contained a non-hex character
+    #[error("encountered non-hex character '{invalid}' @ {index}")]
+    InvalidCharacter {
+        /// The invalid character
+        invalid: char,
+        /// The byte index of the character in the string
+        index: usize,
+    },
 }
 
 /// Errors related to a local index cache
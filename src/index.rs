@@ -4,6 +4,7 @@
 pub mod cache;
 #[cfg(all(feature = "__git", feature = "sparse"))]
 mod combo;
+pub mod directory;
 #[allow(missing_docs)]
 pub mod git;
 #[cfg(feature = "__git")]
@@ -18,13 +19,17 @@ mod sparse_remote;
 
 pub use cache::IndexCache;
 #[cfg(all(feature = "__git", feature = "sparse"))]
-pub use combo::ComboIndex;
+pub use combo::{AsyncComboIndex, ClientKind, ComboIndex, UpdateOutcome};
+pub use directory::DirectorySource;
 pub use git::GitIndex;
 #[cfg(feature = "__git")]
 pub use git_remote::RemoteGitIndex;
 #[cfg(feature = "local")]
 pub use local::LocalRegistry;
-pub use location::{IndexLocation, IndexPath, IndexUrl};
+pub use location::{
+    crates_io_protocol, ConfigOverrides, ConfigSearchStop, IndexLocation, IndexPath, IndexUrl,
+    Protocol, ProtocolDecision, ProtocolProvenance, RegistrySelector,
+};
 pub use sparse::SparseIndex;
 #[cfg(feature = "sparse")]
 pub use sparse_remote::{AsyncRemoteSparseIndex, RemoteSparseIndex};
@@ -45,7 +50,44 @@ impl IndexConfig {
     ///
     /// See <https://doc.rust-lang.org/cargo/reference/registries.html#index-format>
     /// for more info
+    #[inline]
     pub fn download_url(&self, name: crate::KrateName<'_>, version: &str) -> String {
+        self.expand_download_url(name, &version, None)
+    }
+
+    /// The same as [`Self::download_url`], but also substitutes the
+    /// `{sha256-checksum}` marker with the hex encoded checksum of the crate file
+    ///
+    /// [`crate::IndexVersion::download_url`] uses this with the version's checksum
+    #[inline]
+    pub fn download_url_with_checksum(
+        &self,
+        name: crate::KrateName<'_>,
+        version: &str,
+        checksum: &crate::krate::Chksum,
+    ) -> String {
+        self.expand_download_url(name, &version, Some(checksum))
+    }
+
+    /// The same as [`Self::download_url`], but takes an already parsed version
+    /// so that the caller doesn't need to allocate a string for it
+    #[inline]
+    pub fn download_url_for_version(
+        &self,
+        name: crate::KrateName<'_>,
+        version: &semver::Version,
+    ) -> String {
+        self.expand_download_url(name, version, None)
+    }
+
+    fn expand_download_url(
+        &self,
+        name: crate::KrateName<'_>,
+        version: &dyn std::fmt::Display,
+        checksum: Option<&crate::krate::Chksum>,
+    ) -> String {
+        use std::fmt::Write;
+
         // Special case crates.io which will easily be the most common case in
         // almost all scenarios, we just use the _actual_ url directly, which
         // avoids a 301 redirect, though obviously this will be bad if crates.io
@@ -56,44 +98,49 @@ impl IndexConfig {
             return format!("https://static.crates.io/crates/{name}/{name}-{version}.crate");
         }
 
-        let mut dl = self.dl.clone();
-
-        if dl.contains('{') {
-            while let Some(start) = dl.find("{crate}") {
-                dl.replace_range(start..start + 7, name.0);
-            }
-
-            while let Some(start) = dl.find("{version}") {
-                dl.replace_range(start..start + 9, version);
-            }
+        let mut dl = String::with_capacity(self.dl.len() + name.0.len() + 16);
 
-            if dl.contains("{prefix}") || dl.contains("{lowerprefix}") {
-                let mut prefix = String::with_capacity(6);
-                name.prefix(&mut prefix, '/');
+        if self.dl.contains('{') {
+            let mut rest = self.dl.as_str();
 
-                while let Some(start) = dl.find("{prefix}") {
-                    dl.replace_range(start..start + 8, &prefix);
-                }
+            while let Some(start) = rest.find('{') {
+                dl.push_str(&rest[..start]);
+                rest = &rest[start..];
 
-                if dl.contains("{lowerprefix}") {
-                    prefix.make_ascii_lowercase();
+                let marker_len = if rest.starts_with("{crate}") {
+                    dl.push_str(name.0);
+                    7
+                } else if rest.starts_with("{version}") {
+                    let _ = write!(dl, "{version}");
+                    9
+                } else if rest.starts_with("{prefix}") {
+                    name.push_prefix(&mut dl, '/');
+                    8
+                } else if rest.starts_with("{lowerprefix}") {
+                    name.push_lower_prefix(&mut dl, '/');
+                    13
+                } else if let Some(checksum) =
+                    checksum.filter(|_| rest.starts_with("{sha256-checksum}"))
+                {
+                    let _ = write!(dl, "{checksum}");
+                    17
+                } else {
+                    dl.push('{');
+                    1
+                };
 
-                    while let Some(start) = dl.find("{lowerprefix}") {
-                        dl.replace_range(start..start + 13, &prefix);
-                    }
-                }
+                rest = &rest[marker_len..];
             }
+
+            dl.push_str(rest);
         } else {
             // If none of the markers are present, then the value /{crate}/{version}/download is appended to the end
+            dl.push_str(&self.dl);
             if !dl.ends_with('/') {
                 dl.push('/');
             }
 
-            dl.push_str(name.0);
-            dl.push('/');
-            dl.push_str(version);
-            dl.push('/');
-            dl.push_str("download");
+            let _ = write!(dl, "{name}/{version}/download");
         }
 
         dl
@@ -112,6 +159,8 @@ pub enum ComboIndexCache {
     /// A local registry
     #[cfg(feature = "local")]
     Local(LocalRegistry),
+    /// A directory source, eg. vendored crates
+    Directory(DirectorySource),
 }
 
 impl ComboIndexCache {
@@ -127,6 +176,41 @@ impl ComboIndexCache {
             Self::Sparse(index) => index.cached_krate(name, lock),
             #[cfg(feature = "local")]
             Self::Local(lr) => lr.cached_krate(name, lock),
+            Self::Directory(ds) => ds.cached_krate(name, lock),
+        }
+    }
+
+    /// Gets the cache of the index, local registries and directory sources
+    /// don't have one as they don't use .cache files
+    ///
+    /// This allows cache maintenance to be done regardless of the kind of
+    /// registry, eg.
+    ///
+    /// ```no_run
+    /// use tame_index::index::{ComboIndexCache, IndexLocation, IndexUrl};
+    ///
+    /// let url = IndexUrl::crates_io(None, None, None)?;
+    /// let index = ComboIndexCache::new(IndexLocation::new(url))?;
+    ///
+    /// // Remove the cache entries so they are retrieved again on the next use
+    /// if let Some(cache) = index.cache() {
+    ///     for name in ["serde", "serde_json"] {
+    ///         let path = cache.cache_path(name.try_into()?);
+    ///         if path.exists() {
+    ///             std::fs::remove_file(path)?;
+    ///         }
+    ///     }
+    /// }
+    /// # Ok::<_, tame_index::Error>(())
+    /// ```
+    #[inline]
+    pub fn cache(&self) -> Option<&IndexCache> {
+        match self {
+            Self::Git(index) => Some(index.cache()),
+            Self::Sparse(index) => Some(index.cache()),
+            #[cfg(feature = "local")]
+            Self::Local(_) => None,
+            Self::Directory(_) => None,
         }
     }
 
@@ -137,6 +221,40 @@ impl ComboIndexCache {
             Self::Sparse(index) => index.cache().cache_path(name),
             #[cfg(feature = "local")]
             Self::Local(lr) => lr.krate_path(name),
+            Self::Directory(ds) => ds.krate_path(name),
+        }
+    }
+
+    /// Get the configuration of the index, without any network I/O
+    ///
+    /// For sparse indices the `config.json` must already be on disk, and for
+    /// git indices it is read from the local clone, which requires the `git`
+    /// feature. Local registries and directory sources don't have a
+    /// configuration, so an error is returned for them.
+    ///
+    /// See the [cargo docs](https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration)
+    pub fn index_config(&self) -> Result<IndexConfig, Error> {
+        match self {
+            #[cfg(feature = "__git")]
+            Self::Git(index) => {
+                // The git index only wraps the cache, so open the local clone
+                // to read the config from it
+                let local = GitIndex {
+                    cache: IndexCache::at_path(index.cache.path.clone()),
+                    url: index.url.clone(),
+                    head: None,
+                };
+                RemoteGitIndex::open(local, &FileLock::unlocked())?.index_config()
+            }
+            #[cfg(not(feature = "__git"))]
+            Self::Git(_) => Err(Error::Io(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "reading config.json from a git index requires the `git` feature",
+            ))),
+            Self::Sparse(index) => index.index_config(),
+            #[cfg(feature = "local")]
+            Self::Local(_) => Err(no_index_config()),
+            Self::Directory(_) => Err(no_index_config()),
         }
     }
 
@@ -145,6 +263,8 @@ impl ComboIndexCache {
     /// See [`Self::crates_io`] if you want to create a crates.io index based
     /// upon other information in the user's environment
     pub fn new(il: IndexLocation<'_>) -> Result<Self, Error> {
+        il.url.validate()?;
+
         #[cfg(feature = "local")]
         {
             if let IndexUrl::Local(path) = il.url {
@@ -152,6 +272,10 @@ impl ComboIndexCache {
             }
         }
 
+        if let IndexUrl::Directory(path) = il.url {
+            return Ok(Self::Directory(DirectorySource::open(path.into())?));
+        }
+
         let index = if il.url.is_sparse() {
             let sparse = SparseIndex::new(il)?;
             Self::Sparse(sparse)
@@ -162,6 +286,29 @@ impl ComboIndexCache {
 
         Ok(index)
     }
+
+    /// Constructs a [`Self`] for the selected registry, rooted in the default
+    /// cargo home
+    ///
+    /// The selector is resolved using the cargo config files found from
+    /// `config_root`, eg. to open the registry returned by
+    /// [`IndexUrl::default_registry`]
+    pub fn for_registry(
+        registry: RegistrySelector<'_>,
+        config_root: Option<crate::PathBuf>,
+    ) -> Result<Self, Error> {
+        let url = registry.into_url(config_root, None)?;
+        Self::new(IndexLocation::new(url))
+    }
+}
+
+/// The error for indices that don't have a `config.json`
+#[inline]
+fn no_index_config() -> Error {
+    Error::Io(std::io::Error::new(
+        std::io::ErrorKind::NotFound,
+        "local registries and directory sources do not have a config.json",
+    ))
 }
 
 impl From<SparseIndex> for ComboIndexCache {
@@ -171,6 +318,13 @@ impl From<SparseIndex> for ComboIndexCache {
     }
 }
 
+impl From<DirectorySource> for ComboIndexCache {
+    #[inline]
+    fn from(ds: DirectorySource) -> Self {
+        Self::Directory(ds)
+    }
+}
+
 impl From<GitIndex> for ComboIndexCache {
     #[inline]
     fn from(gi: GitIndex) -> Self {
@@ -178,11 +332,175 @@ impl From<GitIndex> for ComboIndexCache {
     }
 }
 
+/// Common interface for looking up crates, regardless of the kind of index
+///
+/// This trait is object safe, so eg. a `Box<dyn RegistryIndex>` can be used
+/// when the kind of index is only known at runtime
+pub trait RegistryIndex {
+    /// Retrieves the cached crate metadata if it exists, without any network I/O
+    fn cached_krate(
+        &self,
+        name: crate::KrateName<'_>,
+        lock: &FileLock,
+    ) -> Result<Option<crate::IndexKrate>, Error>;
+
+    /// Retrieves the crate metadata, contacting the remote index if the index
+    /// has one, optionally writing a cache entry for it
+    ///
+    /// Indices that only have local data, eg. [`GitIndex`] or [`SparseIndex`],
+    /// just read the cache, the same as [`Self::cached_krate`]
+    fn krate(
+        &self,
+        name: crate::KrateName<'_>,
+        write_cache_entry: bool,
+        lock: &FileLock,
+    ) -> Result<Option<crate::IndexKrate>, Error>;
+}
+
+/// Implements [`RegistryIndex`] for types that can only read local data
+macro_rules! local_registry_index {
+    ($($(#[$meta:meta])* $ty:ty),+ $(,)?) => {
+        $(
+            $(#[$meta])*
+            impl RegistryIndex for $ty {
+                #[inline]
+                fn cached_krate(
+                    &self,
+                    name: crate::KrateName<'_>,
+                    lock: &FileLock,
+                ) -> Result<Option<crate::IndexKrate>, Error> {
+                    <$ty>::cached_krate(self, name, lock)
+                }
+
+                #[inline]
+                fn krate(
+                    &self,
+                    name: crate::KrateName<'_>,
+                    _write_cache_entry: bool,
+                    lock: &FileLock,
+                ) -> Result<Option<crate::IndexKrate>, Error> {
+                    <$ty>::cached_krate(self, name, lock)
+                }
+            }
+        )+
+    };
+}
+
+/// Implements [`RegistryIndex`] for types that can contact a remote index
+macro_rules! remote_registry_index {
+    ($($(#[$meta:meta])* $ty:ty),+ $(,)?) => {
+        $(
+            $(#[$meta])*
+            impl RegistryIndex for $ty {
+                #[inline]
+                fn cached_krate(
+                    &self,
+                    name: crate::KrateName<'_>,
+                    lock: &FileLock,
+                ) -> Result<Option<crate::IndexKrate>, Error> {
+                    <$ty>::cached_krate(self, name, lock)
+                }
+
+                #[inline]
+                fn krate(
+                    &self,
+                    name: crate::KrateName<'_>,
+                    write_cache_entry: bool,
+                    lock: &FileLock,
+                ) -> Result<Option<crate::IndexKrate>, Error> {
+                    <$ty>::krate(self, name, write_cache_entry, lock)
+                }
+            }
+        )+
+    };
+}
+
+local_registry_index!(
+    GitIndex,
+    SparseIndex,
+    DirectorySource,
+    ComboIndexCache,
+    #[cfg(feature = "local")]
+    LocalRegistry,
+);
+
+remote_registry_index!(
+    #[cfg(feature = "__git")]
+    RemoteGitIndex,
+    #[cfg(feature = "sparse")]
+    RemoteSparseIndex,
+    #[cfg(all(feature = "__git", feature = "sparse"))]
+    ComboIndex,
+);
+
+impl<T: RegistryIndex + ?Sized> RegistryIndex for Box<T> {
+    #[inline]
+    fn cached_krate(
+        &self,
+        name: crate::KrateName<'_>,
+        lock: &FileLock,
+    ) -> Result<Option<crate::IndexKrate>, Error> {
+        (**self).cached_krate(name, lock)
+    }
+
+    #[inline]
+    fn krate(
+        &self,
+        name: crate::KrateName<'_>,
+        write_cache_entry: bool,
+        lock: &FileLock,
+    ) -> Result<Option<crate::IndexKrate>, Error> {
+        (**self).krate(name, write_cache_entry, lock)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::IndexConfig;
     use crate::kn;
 
+    /// Validates different kinds of indices can be used as trait objects
+    #[test]
+    fn boxed_registry_index() {
+        use super::{
+            ComboIndexCache, DirectorySource, IndexLocation, IndexPath, IndexUrl, RegistryIndex,
+            SparseIndex,
+        };
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let lock = crate::index::FileLock::unlocked();
+
+        let sparse = SparseIndex::new(IndexLocation {
+            url: IndexUrl::CratesIoSparse,
+            root: IndexPath::Exact(root.join("sparse")),
+        })
+        .unwrap();
+        let krate = crate::IndexKrate {
+            versions: vec![crate::IndexVersion::fake("boxed", "1.0.0")],
+        };
+        sparse
+            .cache()
+            .write_to_cache(&krate, "etag: \"1\"", &lock)
+            .unwrap();
+
+        let indices: Vec<Box<dyn RegistryIndex>> = vec![
+            Box::new(sparse),
+            Box::new(ComboIndexCache::Directory(
+                DirectorySource::open(root.clone()).unwrap(),
+            )),
+        ];
+
+        let lookup = |index: &dyn RegistryIndex| index.krate(kn!("boxed"), true, &lock).unwrap();
+
+        assert_eq!(lookup(&indices[0]), Some(krate.clone()));
+        assert_eq!(
+            indices[0].cached_krate(kn!("boxed"), &lock).unwrap(),
+            Some(krate)
+        );
+        assert!(lookup(&indices[1]).is_none());
+    }
+
     /// Validates we get the non-redirect url for crates.io downloads
     #[test]
     fn download_url_crates_io() {
@@ -261,4 +579,30 @@ mod test {
             "https://complex.io/ohhi/embark/rust/cargo/ab/c-/aBc-123/aBc-123/aB/c--0.1.0"
         );
     }
+
+    /// Validates a parsed version produces the same url as a version string
+    #[test]
+    fn download_url_for_version() {
+        let ic = IndexConfig {
+            dl: "https://complex.io/{lowerprefix}/{crate}/{version}/{crate}-{version}.crate".into(),
+            api: None,
+        };
+
+        for vers in ["1.0.0", "0.1.0-alpha.1+build.5"] {
+            let parsed: semver::Version = vers.parse().unwrap();
+            assert_eq!(
+                ic.download_url_for_version(kn!("aBc-123"), &parsed),
+                ic.download_url(kn!("aBc-123"), vers),
+            );
+        }
+
+        let ic = IndexConfig {
+            dl: "https://simple.io/api/v1/crates".into(),
+            api: None,
+        };
+        assert_eq!(
+            ic.download_url_for_version(kn!("simple"), &semver::Version::new(1, 2, 3)),
+            "https://simple.io/api/v1/crates/simple/1.2.3/download"
+        );
+    }
 }
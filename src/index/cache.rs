@@ -37,7 +37,10 @@ pub const INDEX_V_MAX: u32 = 2;
 const INDEX_V_MAX_BYTES: [u8; 4] = INDEX_V_MAX.to_le_bytes();
 
 use super::FileLock;
-use crate::{CacheError, Error, IndexKrate, KrateName, PathBuf};
+use crate::{
+    krate::{SplitFeatures, WriteOptions},
+    CacheError, Error, IndexKrate, KrateName, PathBuf,
+};
 
 /// A wrapper around a byte buffer that has been (partially) validated to be a
 /// valid cache entry
@@ -104,6 +107,12 @@ impl<'buffer> ValidCacheEntry<'buffer> {
         })
     }
 
+    /// Parses the [`Self::revision`] into its structured form
+    #[inline]
+    pub fn parsed_revision(&self) -> Revision<'buffer> {
+        Revision::parse(self.revision)
+    }
+
     /// Deserializes this cache entry into a [`IndexKrate`]
     ///
     /// If specified, the `revision` will be used to ignore cache entries
@@ -122,6 +131,79 @@ impl<'buffer> ValidCacheEntry<'buffer> {
     }
 }
 
+/// The revision stored in a cache entry, used to determine if the cache entry
+/// is up to date with the remote index
+///
+/// The [`std::fmt::Display`] implementation produces the exact string that cargo
+/// writes to, and expects in, cache entries
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Revision<'rev> {
+    /// The `etag` header of a sparse index response
+    ETag(&'rev str),
+    /// The `last-modified` header of a sparse index response
+    LastModified(&'rev str),
+    /// The hex encoded sha-1 of either the HEAD commit or the crate's blob in
+    /// a git index
+    Commit(&'rev str),
+    /// A revision that didn't match any of the known formats, eg. the `Unknown`
+    /// revision written for sparse responses that had neither an `etag` nor
+    /// `last-modified` header
+    Unknown(&'rev str),
+}
+
+impl<'rev> Revision<'rev> {
+    /// Creates a revision for the `etag` header value of a sparse index response
+    #[inline]
+    pub fn etag(value: &'rev str) -> Self {
+        Self::ETag(value.trim())
+    }
+
+    /// Creates a revision for the `last-modified` header value of a sparse
+    /// index response
+    #[inline]
+    pub fn last_modified(value: &'rev str) -> Self {
+        Self::LastModified(value.trim())
+    }
+
+    /// Creates a revision for a hex encoded sha-1 in a git index
+    #[inline]
+    pub fn commit(id: &'rev str) -> Self {
+        Self::Commit(id)
+    }
+
+    /// Parses a revision as stored in a cache entry
+    ///
+    /// This never fails, revisions that aren't recognized are returned as
+    /// [`Self::Unknown`]
+    pub fn parse(revision: &'rev str) -> Self {
+        if let Some((key, value)) = revision.split_once(':') {
+            if key.eq_ignore_ascii_case(ETAG) {
+                return Self::etag(value);
+            } else if key.eq_ignore_ascii_case(LAST_MODIFIED) {
+                return Self::last_modified(value);
+            }
+        } else if revision.len() == 40 && revision.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Self::Commit(revision);
+        }
+
+        Self::Unknown(revision)
+    }
+}
+
+const ETAG: &str = "etag";
+const LAST_MODIFIED: &str = "last-modified";
+
+impl std::fmt::Display for Revision<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ETag(etag) => write!(f, "{ETAG}: {etag}"),
+            Self::LastModified(lm) => write!(f, "{LAST_MODIFIED}: {lm}"),
+            Self::Commit(id) => f.write_str(id),
+            Self::Unknown(rev) => f.write_str(rev),
+        }
+    }
+}
+
 impl IndexKrate {
     /// Reads entries from the versions portion of a cache file
     pub(crate) fn from_cache<'cache>(
@@ -149,6 +231,66 @@ impl IndexKrate {
         &self,
         writer: &mut W,
         revision: &str,
+    ) -> Result<(), std::io::Error> {
+        self.write_cache_versions(writer, revision, |_iv| true, WriteOptions::default())
+    }
+
+    /// Async version of [`Self::write_cache_entry`]
+    ///
+    /// The output is identical to the sync version, it is formatted into an
+    /// internal buffer that is then written in one go, there is no need to
+    /// wrap the writer in your own buffering
+    #[cfg(feature = "tokio")]
+    pub async fn write_cache_entry_async<W: tokio::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+        revision: &str,
+    ) -> Result<(), std::io::Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buffer = Vec::new();
+        self.write_cache_entry(&mut buffer, revision)?;
+
+        writer.write_all(&buffer).await?;
+        writer.flush().await
+    }
+
+    /// Writes a cache entry with the specified revision and options to an
+    /// [`std::io::Write`]
+    pub fn write_cache_entry_with_options<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        revision: &str,
+        opts: WriteOptions,
+    ) -> Result<(), std::io::Error> {
+        self.write_cache_versions(writer, revision, |_iv| true, opts)
+    }
+
+    /// Writes a cache entry with the specified revision to an [`std::io::Write`],
+    /// including only the versions that pass the specified filter
+    ///
+    /// Since a cache entry with no versions is invalid, this fails with
+    /// [`Error::NoCrateVersions`] without writing anything if the filter
+    /// excludes every version
+    pub fn write_cache_entry_filtered<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        revision: &str,
+        filter: impl Fn(&crate::IndexVersion) -> bool,
+    ) -> Result<(), Error> {
+        if !self.versions.iter().any(&filter) {
+            return Err(Error::NoCrateVersions);
+        }
+
+        Ok(self.write_cache_versions(writer, revision, filter, WriteOptions::default())?)
+    }
+
+    fn write_cache_versions<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        revision: &str,
+        filter: impl Fn(&crate::IndexVersion) -> bool,
+        opts: WriteOptions,
     ) -> Result<(), std::io::Error> {
         use std::io::Write;
 
@@ -165,14 +307,18 @@ impl IndexKrate {
         // limit
         let mut semver = String::with_capacity(64);
 
-        for iv in &self.versions {
+        for iv in self.versions.iter().filter(|iv| filter(iv)) {
             semver.clear();
             // SAFETY: the only way this would fail would be OOM
             std::fmt::write(&mut semver, format_args!("{}", iv.version)).unwrap();
             w.write_all(semver.as_bytes())?;
             w.write_all(SPLIT)?;
 
-            serde_json::to_writer(&mut w, &iv)?;
+            if opts.split_features2 {
+                serde_json::to_writer(&mut w, &SplitFeatures(iv))?;
+            } else {
+                serde_json::to_writer(&mut w, &iv)?;
+            }
             w.write_all(SPLIT)?;
         }
 
@@ -214,13 +360,66 @@ pub fn split(haystack: &[u8], needle: u8) -> impl Iterator<Item = &[u8]> + '_ {
 pub struct IndexCache {
     /// The root disk location of the local index
     pub(super) path: PathBuf,
+    /// The layout used when writing cache entries
+    layout: CacheLayout,
+}
+
+/// The layout of the cache entries on disk
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum CacheLayout {
+    /// The same layout as cargo, `.cache/<prefix>/<name>`
+    #[default]
+    Cargo,
+    /// Cache entries are stored in `.cache/hashed/<hash[..2]>/<hash>`, where
+    /// the hash is a fixed length hash of the lowercased crate name.
+    ///
+    /// This keeps the length of cache entry paths constant regardless of the
+    /// length of the crate name, which can be necessary on Windows systems
+    /// that don't have long path support enabled. Cargo will not read or write
+    /// cache entries in this layout.
+    Hashed,
+}
+
+impl CacheLayout {
+    #[inline]
+    fn other(self) -> Self {
+        match self {
+            Self::Cargo => Self::Hashed,
+            Self::Hashed => Self::Cargo,
+        }
+    }
 }
 
 impl IndexCache {
     /// Creates a local index exactly at the specified path
     #[inline]
     pub fn at_path(path: PathBuf) -> Self {
-        Self { path }
+        Self {
+            path,
+            layout: CacheLayout::Cargo,
+        }
+    }
+
+    /// Sets the layout used for cache entries
+    ///
+    /// Regardless of the layout, reads will fall back to the other layout if
+    /// a usable cache entry doesn't exist in the configured one
+    #[inline]
+    pub fn with_layout(mut self, layout: CacheLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets the layout used for cache entries, see [`Self::with_layout`]
+    #[inline]
+    pub fn set_layout(&mut self, layout: CacheLayout) {
+        self.layout = layout;
+    }
+
+    /// The layout used for cache entries
+    #[inline]
+    pub fn layout(&self) -> CacheLayout {
+        self.layout
     }
 
     /// Reads a crate from the local cache of the index.
@@ -232,14 +431,49 @@ impl IndexCache {
         &self,
         name: KrateName<'_>,
         revision: Option<&str>,
-        lock: &FileLock,
+        _lock: &FileLock,
     ) -> Result<Option<IndexKrate>, Error> {
-        let Some(contents) = self.read_cache_file(name, lock)? else {
-            return Ok(None);
-        };
+        for layout in self.read_order() {
+            let Some(contents) = self.read_layout_file(name, layout)? else {
+                continue;
+            };
+
+            // An unreadable entry is treated the same as a missing one
+            let Ok(valid) = ValidCacheEntry::read(&contents) else {
+                continue;
+            };
+            if let Some(krate) = verify_name(valid.to_krate(revision).ok().flatten(), name) {
+                return Ok(Some(krate));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Async version of [`Self::cached_krate`]
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub async fn cached_krate_async(
+        &self,
+        name: KrateName<'_>,
+        revision: Option<&str>,
+        _lock: &FileLock,
+    ) -> Result<Option<IndexKrate>, Error> {
+        for layout in self.read_order() {
+            let Some(contents) = self.read_layout_file_async(name, layout).await? else {
+                continue;
+            };
+
+            // An unreadable entry is treated the same as a missing one
+            let Ok(valid) = ValidCacheEntry::read(&contents) else {
+                continue;
+            };
+            if let Some(krate) = verify_name(valid.to_krate(revision).ok().flatten(), name) {
+                return Ok(Some(krate));
+            }
+        }
 
-        let valid = ValidCacheEntry::read(&contents)?;
-        valid.to_krate(revision)
+        Ok(None)
     }
 
     /// Writes the specified crate and revision to the cache
@@ -273,18 +507,76 @@ impl IndexCache {
         }
     }
 
+    /// Async version of [`Self::write_to_cache`]
+    ///
+    /// The cache entry is serialized in memory and then written with [`tokio::fs`],
+    /// so the file contents are identical to those written by [`Self::write_to_cache`]
+    #[cfg(feature = "tokio")]
+    pub async fn write_to_cache_async(
+        &self,
+        krate: &IndexKrate,
+        revision: &str,
+        _lock: &FileLock,
+    ) -> Result<PathBuf, Error> {
+        let name = krate.name().try_into()?;
+        let cache_path = self.cache_path(name);
+
+        let mut entry = Vec::new();
+        krate.write_cache_entry_async(&mut entry, revision).await?;
+
+        if let Err(err) = tokio::fs::create_dir_all(cache_path.parent().unwrap()).await {
+            return Err(Error::IoPath(err, cache_path));
+        }
+
+        match tokio::fs::write(&cache_path, entry).await {
+            Ok(()) => Ok(cache_path),
+            Err(err) => {
+                // _attempt_ to delete the file, to clean up after ourselves
+                let _ = tokio::fs::remove_file(&cache_path).await;
+                Err(Error::IoPath(err, cache_path))
+            }
+        }
+    }
+
     /// Gets the path the crate's cache file would be located at if it exists
     #[inline]
     pub fn cache_path(&self, name: KrateName<'_>) -> PathBuf {
-        let rel_path = name.relative_path(None);
+        self.layout_path(name, self.layout)
+    }
 
-        // avoid realloc on each push
-        let mut cache_path = PathBuf::with_capacity(self.path.as_str().len() + 8 + rel_path.len());
-        cache_path.push(&self.path);
-        cache_path.push(".cache");
-        cache_path.push(rel_path);
+    /// Gets the path the crate's cache file would be located at for the
+    /// specified layout
+    pub fn layout_path(&self, name: KrateName<'_>, layout: CacheLayout) -> PathBuf {
+        let root = self.path.as_str();
+
+        // Write the full path into a single buffer rather than allocating
+        // the relative path separately, the hashed layout is at most 24 bytes
+        let mut cache_path = String::with_capacity(root.len() + 8 + (name.0.len() + 6).max(24));
+        cache_path.push_str(root);
+        if !root.is_empty() && !root.ends_with(std::path::is_separator) {
+            cache_path.push(std::path::MAIN_SEPARATOR);
+        }
+        cache_path.push_str(".cache");
+        cache_path.push(std::path::MAIN_SEPARATOR);
+
+        match layout {
+            CacheLayout::Cargo => name.push_relative_path(&mut cache_path, None),
+            CacheLayout::Hashed => {
+                // Crate names are case insensitive, same as the cargo layout
+                let hash = twox_hash::XxHash64::oneshot(0, name.0.to_ascii_lowercase().as_bytes());
+
+                let mut raw = [0u8; 16];
+                let hex = crate::utils::encode_hex(&hash.to_le_bytes(), &mut raw);
+
+                cache_path.push_str("hashed");
+                cache_path.push(std::path::MAIN_SEPARATOR);
+                cache_path.push_str(&hex[..2]);
+                cache_path.push(std::path::MAIN_SEPARATOR);
+                cache_path.push_str(hex);
+            }
+        }
 
-        cache_path
+        cache_path.into()
     }
 
     /// Attempts to read the cache entry for the specified crate
@@ -296,7 +588,49 @@ impl IndexCache {
         name: KrateName<'_>,
         _lock: &FileLock,
     ) -> Result<Option<Vec<u8>>, Error> {
-        let cache_path = self.cache_path(name);
+        for layout in self.read_order() {
+            if let Some(cb) = self.read_layout_file(name, layout)? {
+                if layout == CacheLayout::Cargo || is_entry_for(&cb, name) {
+                    return Ok(Some(cb));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Async version of [`Self::read_cache_file`]
+    #[cfg(feature = "tokio")]
+    #[inline]
+    pub async fn read_cache_file_async(
+        &self,
+        name: KrateName<'_>,
+        _lock: &FileLock,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        for layout in self.read_order() {
+            if let Some(cb) = self.read_layout_file_async(name, layout).await? {
+                if layout == CacheLayout::Cargo || is_entry_for(&cb, name) {
+                    return Ok(Some(cb));
+                }
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// The order layouts are read in, the configured layout is always preferred
+    #[inline]
+    fn read_order(&self) -> [CacheLayout; 2] {
+        [self.layout, self.layout.other()]
+    }
+
+    /// Reads the cache entry for the crate in the specified layout
+    fn read_layout_file(
+        &self,
+        name: KrateName<'_>,
+        layout: CacheLayout,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let cache_path = self.layout_path(name, layout);
 
         match std::fs::read(&cache_path) {
             Ok(cb) => Ok(Some(cb)),
@@ -304,4 +638,50 @@ impl IndexCache {
             Err(err) => Err(Error::IoPath(err, cache_path)),
         }
     }
+
+    /// Async version of [`Self::read_layout_file`]
+    #[cfg(feature = "tokio")]
+    async fn read_layout_file_async(
+        &self,
+        name: KrateName<'_>,
+        layout: CacheLayout,
+    ) -> Result<Option<Vec<u8>>, Error> {
+        let cache_path = self.layout_path(name, layout);
+
+        match tokio::fs::read(&cache_path).await {
+            Ok(cb) => Ok(Some(cb)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::IoPath(err, cache_path)),
+        }
+    }
+}
+
+/// Hashed cache entries can't be identified by their path alone, so we ensure
+/// that the crate in the cache entry is actually the one that was requested
+#[inline]
+fn verify_name(krate: Option<IndexKrate>, name: KrateName<'_>) -> Option<IndexKrate> {
+    krate.filter(|krate| krate.name().eq_ignore_ascii_case(name.0))
+}
+
+/// Checks if the raw cache entry is for the specified crate, entries that
+/// can't be parsed are treated the same as an entry for a different crate
+///
+/// Only the name of the first version is parsed, as every version of an entry
+/// is for the same crate
+fn is_entry_for(contents: &[u8], name: KrateName<'_>) -> bool {
+    #[derive(serde::Deserialize)]
+    struct Named<'s> {
+        #[serde(borrow)]
+        name: std::borrow::Cow<'s, str>,
+    }
+
+    let Ok(valid) = ValidCacheEntry::read(contents) else {
+        return false;
+    };
+
+    // Skip the semver version of the first entry to get its JSON blob
+    split(valid.version_entries, 0)
+        .nth(1)
+        .and_then(|blob| serde_json::from_slice::<Named<'_>>(blob).ok())
+        .map_or(false, |named| named.name.eq_ignore_ascii_case(name.0))
 }
@@ -1,8 +1,16 @@
 #[cfg(feature = "local")]
 use crate::index::LocalRegistry;
 use crate::{
-    index::{FileLock, RemoteGitIndex, RemoteSparseIndex},
-    Error, IndexKrate, KrateName,
+    index::{
+        sparse_remote::AsyncClient, AsyncRemoteSparseIndex, DirectorySource, FileLock, GitIndex,
+        IndexCache, IndexConfig, IndexLocation, IndexUrl, RemoteGitIndex, RemoteSparseIndex,
+        SparseIndex,
+    },
+    Error, IndexKrate, KrateName, PathBuf,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::atomic::AtomicBool,
 };
 
 /// A wrapper around either a [`RemoteGitIndex`] or [`RemoteSparseIndex`]
@@ -16,14 +24,86 @@ pub enum ComboIndex {
     /// A local registry
     #[cfg(feature = "local")]
     Local(LocalRegistry),
+    /// A directory source, eg. vendored crates
+    Directory(DirectorySource),
+}
+
+/// The HTTP client used by [`ComboIndex`] if the index is sparse
+///
+/// The client is not constructed by this crate as the TLS configuration,
+/// eg. the root certificates, is up to the application
+#[non_exhaustive]
+pub enum ClientKind {
+    /// A preconfigured blocking client
+    Blocking(reqwest::blocking::Client),
+}
+
+impl From<reqwest::blocking::Client> for ClientKind {
+    #[inline]
+    fn from(client: reqwest::blocking::Client) -> Self {
+        Self::Blocking(client)
+    }
+}
+
+/// The outcome of [`ComboIndex::update`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum UpdateOutcome {
+    /// The index was already up to date, or doesn't need to be updated
+    UpToDate,
+    /// The index was updated to a newer state of the remote
+    Updated,
 }
 
 impl ComboIndex {
+    /// Constructs a [`Self`] for the specified index
+    ///
+    /// Note that if the index is a git index that does not exist at the local
+    /// disk location, a full clone will be performed, see [`RemoteGitIndex::new`]
+    pub fn new(il: IndexLocation<'_>, client: ClientKind, lock: &FileLock) -> Result<Self, Error> {
+        il.url.validate()?;
+
+        #[cfg(feature = "local")]
+        {
+            if let IndexUrl::Local(path) = il.url {
+                return Ok(Self::Local(LocalRegistry::open(path.into(), true)?));
+            }
+        }
+
+        if let IndexUrl::Directory(path) = il.url {
+            return Ok(Self::Directory(DirectorySource::open(path.into())?));
+        }
+
+        let index = if il.url.is_sparse() {
+            let ClientKind::Blocking(client) = client;
+            Self::Sparse(RemoteSparseIndex::new(SparseIndex::new(il)?, client))
+        } else {
+            Self::Git(RemoteGitIndex::new(GitIndex::new(il)?, lock)?)
+        };
+
+        Ok(index)
+    }
+
+    /// Constructs a [`Self`] for crates.io, depending on the local environment
+    ///
+    /// The index url, including any source replacement, is determined the same
+    /// as [`IndexUrl::crates_io`]. The index is placed in the specified cargo
+    /// home, or the default cargo home if not specified.
+    pub fn crates_io(
+        config_root: Option<PathBuf>,
+        cargo_home: Option<PathBuf>,
+        cargo_version: Option<&str>,
+        client: ClientKind,
+        lock: &FileLock,
+    ) -> Result<Self, Error> {
+        let url = IndexUrl::crates_io(config_root, cargo_home.as_deref(), cargo_version)?;
+        Self::new(IndexLocation::new(url).with_root(cargo_home), client, lock)
+    }
+
     /// Retrieves the index metadata for the specified crate name, optionally
     /// writing a cache entry for it if there was not already an up to date one
     ///
-    /// Note no cache entry is written if this is a `Local` registry as they do
-    /// not use .cache files
+    /// Note no cache entry is written if this is a `Local` registry or a
+    /// `Directory` source as they do not use .cache files
     #[inline]
     pub fn krate(
         &self,
@@ -36,6 +116,97 @@ impl ComboIndex {
             Self::Sparse(index) => index.krate(name, write_cache_entry, lock),
             #[cfg(feature = "local")]
             Self::Local(lr) => lr.cached_krate(name, lock),
+            Self::Directory(ds) => ds.cached_krate(name, lock),
+        }
+    }
+
+    /// Get the configuration of the index.
+    ///
+    /// For sparse indices, the `config.json` is requested from the remote if
+    /// it is not on disk, see [`RemoteSparseIndex::index_config`]. Local
+    /// registries and directory sources don't have a configuration, so an
+    /// error is returned for them.
+    ///
+    /// See the [cargo docs](https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration)
+    pub fn index_config(&self) -> Result<IndexConfig, Error> {
+        match self {
+            Self::Git(index) => index.index_config(),
+            Self::Sparse(index) => index.index_config(),
+            #[cfg(feature = "local")]
+            Self::Local(_) => Err(super::no_index_config()),
+            Self::Directory(_) => Err(super::no_index_config()),
+        }
+    }
+
+    /// Retrieves the index metadata for multiple crates, see [`Self::krate`]
+    ///
+    /// Sparse indices request the crates in parallel, see [`RemoteSparseIndex::krates`],
+    /// other indices are read sequentially as they do no network I/O
+    pub fn krates(
+        &self,
+        krates: BTreeSet<String>,
+        write_cache_entries: bool,
+        lock: &FileLock,
+    ) -> BTreeMap<String, Result<Option<IndexKrate>, Error>> {
+        if let Self::Sparse(index) = self {
+            return index.krates(krates, write_cache_entries, lock);
+        }
+
+        krates
+            .into_iter()
+            .map(|kname| {
+                let res = kname
+                    .as_str()
+                    .try_into()
+                    .and_then(|name| self.krate(name, write_cache_entries, lock));
+                (kname, res)
+            })
+            .collect()
+    }
+
+    /// Gets the cache of the index, see [`ComboIndexCache::cache`](crate::index::ComboIndexCache::cache)
+    #[inline]
+    pub fn cache(&self) -> Option<&IndexCache> {
+        match self {
+            Self::Git(index) => Some(index.local().cache()),
+            Self::Sparse(index) => Some(index.index.cache()),
+            #[cfg(feature = "local")]
+            Self::Local(_) => None,
+            Self::Directory(_) => None,
+        }
+    }
+
+    /// Ensures the index is up to date with the remote
+    ///
+    /// For git indices this performs a fetch, see [`RemoteGitIndex::fetch`].
+    /// Sparse indices are instead kept fresh per crate, each call to
+    /// [`Self::krate`] checks with the remote if the crate's cache entry is up
+    /// to date, so this is a no-op for them, as well as for local registries
+    /// and directory sources
+    #[inline]
+    pub fn update(&mut self, lock: &FileLock) -> Result<UpdateOutcome, Error> {
+        self.update_with_options(
+            gix::progress::Discard,
+            &gix::interrupt::IS_INTERRUPTED,
+            lock,
+        )
+    }
+
+    /// Same as [`Self::update`] but allows specifying a progress implementation
+    /// and allows interruption of the network operations of a git fetch
+    pub fn update_with_options<P>(
+        &mut self,
+        progress: P,
+        should_interrupt: &AtomicBool,
+        lock: &FileLock,
+    ) -> Result<UpdateOutcome, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        match self {
+            Self::Git(index) => fetch(index, progress, should_interrupt, lock),
+            _ => Ok(UpdateOutcome::UpToDate),
         }
     }
 
@@ -51,10 +222,32 @@ impl ComboIndex {
             Self::Sparse(index) => index.cached_krate(name, lock),
             #[cfg(feature = "local")]
             Self::Local(lr) => lr.cached_krate(name, lock),
+            Self::Directory(ds) => ds.cached_krate(name, lock),
         }
     }
 }
 
+/// Fetches the git index, reporting if the head commit changed
+fn fetch<P>(
+    index: &mut RemoteGitIndex,
+    progress: P,
+    should_interrupt: &AtomicBool,
+    lock: &FileLock,
+) -> Result<UpdateOutcome, Error>
+where
+    P: gix::NestedProgress,
+    P::SubProgress: 'static,
+{
+    let before = index.local().head_commit().map(String::from);
+    index.fetch_with_options(progress, should_interrupt, lock)?;
+
+    Ok(if index.local().head_commit() == before.as_deref() {
+        UpdateOutcome::UpToDate
+    } else {
+        UpdateOutcome::Updated
+    })
+}
+
 impl From<RemoteGitIndex> for ComboIndex {
     #[inline]
     fn from(index: RemoteGitIndex) -> Self {
@@ -76,3 +269,178 @@ impl From<LocalRegistry> for ComboIndex {
         Self::Local(local)
     }
 }
+
+impl From<DirectorySource> for ComboIndex {
+    #[inline]
+    fn from(ds: DirectorySource) -> Self {
+        Self::Directory(ds)
+    }
+}
+
+/// The async equivalent of [`ComboIndex`], wrapping either a [`RemoteGitIndex`]
+/// or an [`AsyncRemoteSparseIndex`]
+///
+/// Note that gix does not provide an async API, and its repositories can't be
+/// moved to another thread, so blocking operations, eg. cloning or fetching a
+/// git index, or reading index entries from disk, are instead run via
+/// [`tokio::task::block_in_place`] so that other tasks are moved off of the
+/// current worker thread. This is only possible on the multi-threaded runtime,
+/// on the current thread runtime they are run inline.
+#[non_exhaustive]
+pub enum AsyncComboIndex {
+    /// A standard git based registry index. No longer the default for crates.io
+    /// as of 1.70.0
+    Git(Box<RemoteGitIndex>),
+    /// An HTTP sparse index
+    Sparse(AsyncRemoteSparseIndex),
+    /// A local registry
+    #[cfg(feature = "local")]
+    Local(LocalRegistry),
+    /// A directory source, eg. vendored crates
+    Directory(DirectorySource),
+}
+
+impl AsyncComboIndex {
+    /// Constructs a [`Self`] for the specified index
+    ///
+    /// Note that if the index is a git index that does not exist at the local
+    /// disk location, a full clone will be performed, see [`RemoteGitIndex::new`]
+    pub fn new(il: IndexLocation<'_>, client: AsyncClient, lock: &FileLock) -> Result<Self, Error> {
+        il.url.validate()?;
+
+        #[cfg(feature = "local")]
+        {
+            if let IndexUrl::Local(path) = il.url {
+                return Ok(Self::Local(LocalRegistry::open(path.into(), true)?));
+            }
+        }
+
+        if let IndexUrl::Directory(path) = il.url {
+            return Ok(Self::Directory(DirectorySource::open(path.into())?));
+        }
+
+        let index = if il.url.is_sparse() {
+            Self::Sparse(AsyncRemoteSparseIndex::new(SparseIndex::new(il)?, client))
+        } else {
+            Self::Git(Box::new(RemoteGitIndex::new(GitIndex::new(il)?, lock)?))
+        };
+
+        Ok(index)
+    }
+
+    /// Constructs a [`Self`] for crates.io, see [`ComboIndex::crates_io`]
+    ///
+    /// Determining the index, which may involve running `cargo -V`, and
+    /// cloning the index if it is a git index that doesn't exist on disk, are
+    /// done via [`tokio::task::block_in_place`]
+    pub async fn crates_io(
+        config_root: Option<PathBuf>,
+        cargo_home: Option<PathBuf>,
+        cargo_version: Option<&str>,
+        client: AsyncClient,
+        lock: &FileLock,
+    ) -> Result<Self, Error> {
+        blocking(|| {
+            let url = IndexUrl::crates_io(config_root, cargo_home.as_deref(), cargo_version)?;
+            Self::new(IndexLocation::new(url).with_root(cargo_home), client, lock)
+        })
+    }
+
+    /// Gets the cache of the index, see [`ComboIndexCache::cache`](crate::index::ComboIndexCache::cache)
+    #[inline]
+    pub fn cache(&self) -> Option<&IndexCache> {
+        match self {
+            Self::Git(index) => Some(index.local().cache()),
+            Self::Sparse(index) => Some(index.index.cache()),
+            #[cfg(feature = "local")]
+            Self::Local(_) => None,
+            Self::Directory(_) => None,
+        }
+    }
+
+    /// Async version of [`ComboIndex::krate`]
+    pub async fn krate_async(
+        &self,
+        name: KrateName<'_>,
+        write_cache_entry: bool,
+        lock: &FileLock,
+    ) -> Result<Option<IndexKrate>, Error> {
+        match self {
+            Self::Git(index) => blocking(|| index.krate(name, write_cache_entry, lock)),
+            Self::Sparse(index) => index.krate_async(name, write_cache_entry, lock).await,
+            #[cfg(feature = "local")]
+            Self::Local(lr) => blocking(|| lr.cached_krate(name, lock)),
+            Self::Directory(ds) => blocking(|| ds.cached_krate(name, lock)),
+        }
+    }
+
+    /// Async version of [`ComboIndex::cached_krate`]
+    pub async fn cached_krate_async(
+        &self,
+        name: KrateName<'_>,
+        lock: &FileLock,
+    ) -> Result<Option<IndexKrate>, Error> {
+        blocking(|| match self {
+            Self::Git(index) => index.cached_krate(name, lock),
+            Self::Sparse(index) => index.cached_krate(name, lock),
+            #[cfg(feature = "local")]
+            Self::Local(lr) => lr.cached_krate(name, lock),
+            Self::Directory(ds) => ds.cached_krate(name, lock),
+        })
+    }
+
+    /// Async version of [`ComboIndex::update`]
+    pub async fn update_async(&mut self, lock: &FileLock) -> Result<UpdateOutcome, Error> {
+        match self {
+            Self::Git(index) => blocking(|| {
+                fetch(
+                    index,
+                    gix::progress::Discard,
+                    &gix::interrupt::IS_INTERRUPTED,
+                    lock,
+                )
+            }),
+            _ => Ok(UpdateOutcome::UpToDate),
+        }
+    }
+}
+
+/// Runs blocking work without stalling the other tasks on the current worker
+/// thread, if the runtime supports it
+fn blocking<R>(f: impl FnOnce() -> R) -> R {
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) if handle.runtime_flavor() == tokio::runtime::RuntimeFlavor::MultiThread => {
+            tokio::task::block_in_place(f)
+        }
+        _ => f(),
+    }
+}
+
+impl From<RemoteGitIndex> for AsyncComboIndex {
+    #[inline]
+    fn from(index: RemoteGitIndex) -> Self {
+        Self::Git(Box::new(index))
+    }
+}
+
+impl From<AsyncRemoteSparseIndex> for AsyncComboIndex {
+    #[inline]
+    fn from(index: AsyncRemoteSparseIndex) -> Self {
+        Self::Sparse(index)
+    }
+}
+
+#[cfg(feature = "local")]
+impl From<LocalRegistry> for AsyncComboIndex {
+    #[inline]
+    fn from(local: LocalRegistry) -> Self {
+        Self::Local(local)
+    }
+}
+
+impl From<DirectorySource> for AsyncComboIndex {
+    #[inline]
+    fn from(ds: DirectorySource) -> Self {
+        Self::Directory(ds)
+    }
+}
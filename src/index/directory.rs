@@ -0,0 +1,258 @@
+//! Contains code for reading [directory sources](https://doc.rust-lang.org/cargo/reference/source-replacement.html#directory-sources),
+//! eg. as created by `cargo vendor`
+
+use super::FileLock;
+use crate::{
+    krate::{DependencyKind, IndexDependency, IndexVersionBuilder},
+    Error, IndexKrate, IndexVersion, KrateName, Path, PathBuf,
+};
+use toml_span::value::Value;
+
+/// A [directory source](https://doc.rust-lang.org/cargo/reference/source-replacement.html#directory-sources)
+/// implementation
+///
+/// Directory sources don't have an index, each crate version is instead
+/// unpacked into its own directory, `<name>` or `<name>-<version>`, so the
+/// index entries are synthesized from the `Cargo.toml` and `.cargo-checksum.json`
+/// of each version
+pub struct DirectorySource {
+    path: PathBuf,
+}
+
+impl DirectorySource {
+    /// Opens an existing directory source
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        if !path.is_dir() {
+            return Err(Error::IoPath(
+                std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "directory source does not exist",
+                ),
+                path,
+            ));
+        }
+
+        Ok(Self { path })
+    }
+
+    /// The root path of the directory source
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Gets the index information for the crate, synthesized from every
+    /// vendored version of it
+    ///
+    /// Note this naming is just to be consistent with [`crate::SparseIndex`] and
+    /// [`crate::GitIndex`], directory sources do not have a .cache
+    pub fn cached_krate(
+        &self,
+        name: KrateName<'_>,
+        _lock: &FileLock,
+    ) -> Result<Option<IndexKrate>, Error> {
+        let name = name.as_str();
+        let rd =
+            std::fs::read_dir(&self.path).map_err(|err| Error::IoPath(err, self.path.clone()))?;
+
+        let mut versions = Vec::new();
+        for entry in rd {
+            let Ok(entry) = entry else {
+                continue;
+            };
+            if entry.file_type().map_or(true, |ft| !ft.is_dir()) {
+                continue;
+            }
+            let Ok(path) = crate::utils::to_utf8_path_buf(entry.path()) else {
+                continue;
+            };
+            let Some(dir_name) = path.file_name() else {
+                continue;
+            };
+
+            // `cargo vendor` places the first version of a crate in `<name>`,
+            // and any other versions in `<name>-<version>`
+            let is_candidate = dir_name == name
+                || dir_name
+                    .strip_prefix(name)
+                    .and_then(|rest| rest.strip_prefix('-'))
+                    .map_or(false, |version| version.parse::<semver::Version>().is_ok());
+            if !is_candidate {
+                continue;
+            }
+
+            if let Some(iv) = read_version(&path)? {
+                if iv.name == name {
+                    versions.push(iv);
+                }
+            }
+        }
+
+        if versions.is_empty() {
+            return Ok(None);
+        }
+
+        IndexKrate::from_versions(versions).map(Some)
+    }
+
+    /// Gets the path to the unversioned directory for the crate
+    ///
+    /// Note that other versions of the crate may be located in `<name>-<version>`
+    /// directories next to it
+    #[inline]
+    pub fn krate_path(&self, name: KrateName<'_>) -> PathBuf {
+        self.path.join(name.as_str())
+    }
+}
+
+/// Synthesizes the index entry for a single vendored crate version
+///
+/// Returns `None` if the directory does not contain a `Cargo.toml`
+fn read_version(dir: &Path) -> Result<Option<IndexVersion>, Error> {
+    let manifest_path = dir.join("Cargo.toml");
+    let manifest = match std::fs::read_to_string(&manifest_path) {
+        Ok(m) => m,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::IoPath(err, manifest_path)),
+    };
+    let manifest = toml_span::parse(&manifest).map_err(Box::new)?;
+
+    let (Some(name), Some(version)) = (
+        manifest.pointer("/package/name").and_then(|n| n.as_str()),
+        manifest
+            .pointer("/package/version")
+            .and_then(|v| v.as_str()),
+    ) else {
+        return Ok(None);
+    };
+
+    let mut builder = IndexVersionBuilder::new(name, version);
+
+    if let Some(links) = manifest.pointer("/package/links").and_then(|l| l.as_str()) {
+        builder = builder.links(links);
+    }
+
+    if let Some(rv) = manifest
+        .pointer("/package/rust-version")
+        .and_then(|rv| rv.as_str())
+    {
+        builder = builder.rust_version(rv);
+    }
+
+    if let Some(features) = manifest.pointer("/features").and_then(|f| f.as_table()) {
+        for (feature, enables) in features {
+            let enables = enables
+                .as_array()
+                .map(|e| e.iter().filter_map(|e| e.as_str()).collect::<Vec<_>>())
+                .unwrap_or_default();
+            builder = builder.feature(feature.name.as_ref(), enables);
+        }
+    }
+
+    let mut deps = Vec::new();
+    read_deps(&manifest, None, &mut deps);
+    if let Some(targets) = manifest.pointer("/target").and_then(|t| t.as_table()) {
+        for (target, table) in targets {
+            read_deps(table, Some(target.name.as_ref()), &mut deps);
+        }
+    }
+
+    for dep in deps {
+        builder = builder.dependency(dep);
+    }
+
+    // Crates vendored from a registry have the checksum of their .crate file,
+    // but it will be `null` for crates vendored from other sources
+    let checksum_path = dir.join(".cargo-checksum.json");
+    match std::fs::read(&checksum_path) {
+        Ok(contents) => {
+            #[derive(serde::Deserialize)]
+            struct Checksums {
+                package: Option<String>,
+            }
+
+            let checksums: Checksums = serde_json::from_slice(&contents)?;
+            if let Some(package) = checksums.package {
+                let checksum = crate::utils::decode_hex_array(&package).map_err(|err| {
+                    Error::IoPath(
+                        std::io::Error::new(std::io::ErrorKind::InvalidData, err),
+                        checksum_path.clone(),
+                    )
+                })?;
+                builder = builder.checksum(checksum);
+            }
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(Error::IoPath(err, checksum_path)),
+    }
+
+    builder.build().map(Some)
+}
+
+/// Reads the dependencies of every kind in the specified table
+fn read_deps(table: &Value<'_>, target: Option<&str>, deps: &mut Vec<IndexDependency>) {
+    for (key, kind) in [
+        ("dependencies", DependencyKind::Normal),
+        ("dev-dependencies", DependencyKind::Dev),
+        ("dev_dependencies", DependencyKind::Dev),
+        ("build-dependencies", DependencyKind::Build),
+        ("build_dependencies", DependencyKind::Build),
+    ] {
+        let Some(dependencies) = table
+            .as_table()
+            .and_then(|t| t.get(key))
+            .and_then(|d| d.as_table())
+        else {
+            continue;
+        };
+
+        for (name, dep) in dependencies {
+            deps.push(read_dep(name.name.as_ref(), dep, target, kind.clone()));
+        }
+    }
+}
+
+/// Converts a manifest dependency into an index dependency
+fn read_dep(
+    name: &str,
+    dep: &Value<'_>,
+    target: Option<&str>,
+    kind: DependencyKind,
+) -> IndexDependency {
+    let table = dep.as_table();
+    let get = |key: &str| table.and_then(|t| t.get(key));
+    let get_bool = |key: &str| get(key).and_then(|v| v.as_bool());
+
+    let req = dep
+        .as_str()
+        .or_else(|| get("version")?.as_str())
+        .unwrap_or("*");
+    let features: Vec<String> = get("features")
+        .and_then(|f| f.as_array())
+        .map(|f| {
+            f.iter()
+                .filter_map(|f| f.as_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+
+    IndexDependency {
+        name: name.into(),
+        req: req.into(),
+        features: Box::new(features.into_boxed_slice()),
+        optional: get_bool("optional").unwrap_or(false),
+        default_features: get_bool("default-features")
+            .or_else(|| get_bool("default_features"))
+            .unwrap_or(true),
+        target: target.map(|t| Box::new(t.into())),
+        kind: Some(kind),
+        package: get("package")
+            .and_then(|p| p.as_str())
+            .map(|p| Box::new(p.into())),
+        artifact: None,
+        bindep_target: None,
+        lib: false,
+        public: get_bool("public"),
+        extra: None,
+    }
+}
@@ -35,6 +35,19 @@ impl GitIndex {
         })
     }
 
+    /// Gets the accessor to the local index cache
+    #[inline]
+    pub fn cache(&self) -> &IndexCache {
+        &self.cache
+    }
+
+    /// Gets the mutable accessor to the local index cache, eg. to change its
+    /// [`CacheLayout`](super::cache::CacheLayout)
+    #[inline]
+    pub fn cache_mut(&mut self) -> &mut IndexCache {
+        &mut self.cache
+    }
+
     /// Sets the sha-1 id for the head commit.
     ///
     /// If set, this will be used to disregard cache entries that do not match
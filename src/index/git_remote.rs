@@ -30,6 +30,22 @@ impl RemoteGitIndex {
         )
     }
 
+    /// Creates a new [`Self`] from an existing local clone of the index, without
+    /// contacting the remote
+    ///
+    /// Unlike [`Self::new`], this fails with [`GitError::NotCloned`] instead of
+    /// performing a clone if the repository does not exist, so it should be
+    /// used when cargo's offline mode is enabled, see [`crate::utils::is_offline`]
+    #[inline]
+    pub fn open(index: GitIndex, _lock: &FileLock) -> Result<Self, Error> {
+        Self::open_or_clone(
+            index,
+            gix::progress::Discard,
+            &gix::interrupt::IS_INTERRUPTED,
+            false,
+        )
+    }
+
     /// Breaks [`Self`] into its component parts
     ///
     /// This method is useful if you need thread safe access to the repository
@@ -41,12 +57,26 @@ impl RemoteGitIndex {
     /// Creates a new [`Self`] that allows showing of progress of the the potential
     /// fetch if the disk location is empty, as well as allowing interruption
     /// of the fetch operation.
+    #[inline]
     pub fn with_options<P>(
-        mut index: GitIndex,
+        index: GitIndex,
         progress: P,
         should_interrupt: &AtomicBool,
         _lock: &FileLock,
     ) -> Result<Self, Error>
+    where
+        P: gix::NestedProgress,
+        P::SubProgress: 'static,
+    {
+        Self::open_or_clone(index, progress, should_interrupt, true)
+    }
+
+    fn open_or_clone<P>(
+        mut index: GitIndex,
+        progress: P,
+        should_interrupt: &AtomicBool,
+        allow_clone: bool,
+    ) -> Result<Self, Error>
     where
         P: gix::NestedProgress,
         P::SubProgress: 'static,
@@ -88,6 +118,8 @@ impl RemoteGitIndex {
 
             let res = if let Some(repo) = repo {
                 (repo, None)
+            } else if !allow_clone {
+                return Err(GitError::NotCloned(index.cache.path.clone()));
             } else {
                 // We need to create the directory chain ourselves, gix will fail
                 // if any parent directory is missing
@@ -426,6 +458,8 @@ pub enum GitError {
     UnableToFindRemoteHead,
     #[error("unable to update HEAD to remote HEAD")]
     UnableToUpdateHead,
+    #[error("the index has not been cloned to '{0}'")]
+    NotCloned(crate::PathBuf),
 }
 
 impl GitError {
@@ -26,6 +26,46 @@ pub enum LocalRegistryError {
         /// The specific crate version
         version: SmolStr,
     },
+    /// A crate file was provided for a version that is not in the index entry
+    #[error("crate file {name}-{version}.crate does not have a version in the index entry")]
+    VersionMismatch {
+        /// The name of the crate
+        name: String,
+        /// The specific crate version
+        version: SmolStr,
+    },
+    /// More than one crate file was provided for the same version
+    #[error("multiple crate files were provided for {name}-{version}")]
+    DuplicateVersion {
+        /// The name of the crate
+        name: String,
+        /// The specific crate version
+        version: SmolStr,
+    },
+    /// A version is already in the registry with a different checksum
+    #[error("{name}-{version} is already in the registry with a different checksum")]
+    ConflictingVersion {
+        /// The name of the crate
+        name: String,
+        /// The specific crate version
+        version: SmolStr,
+    },
+    /// One or more packages could not be added to the local registry
+    #[error("failed to add {} package(s) to the local registry", .0.len())]
+    Packages(Vec<PackageError>),
+}
+
+/// The reason a single package could not be added to a local registry, see
+/// [`LocalRegistryError::Packages`]
+#[derive(Debug, thiserror::Error)]
+#[error("{name}-{version}: {source}")]
+pub struct PackageError {
+    /// The name of the crate
+    pub name: String,
+    /// The specific crate version
+    pub version: SmolStr,
+    /// The underlying error
+    pub source: Box<Error>,
 }
 
 /// A [local registry](https://doc.rust-lang.org/cargo/reference/source-replacement.html#local-registry-sources)
@@ -36,14 +76,35 @@ pub struct LocalRegistry {
 
 impl LocalRegistry {
     /// Opens an existing local registry, optionally validating it
+    ///
+    /// The registry is always required to have an `index` directory, but the
+    /// crate files are only checked against the index if `validate` is true,
+    /// see [`Self::validate`]
     pub fn open(path: PathBuf, validate: bool) -> Result<Self, Error> {
         if validate {
             Self::validate(&path)?;
+        } else {
+            let index_root = path.join("index");
+            if !index_root.is_dir() {
+                return Err(Error::IoPath(
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        "unable to find index directory",
+                    ),
+                    index_root,
+                ));
+            }
         }
 
         Ok(Self { path })
     }
 
+    /// The root path of the local registry
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
     /// Validates the specified path contains a local registry
     ///
     /// Validation ensures every crate file matches the expected according to
@@ -66,6 +127,7 @@ impl LocalRegistry {
         // Don't bother deserializing multiple times if there are multiple versions
         // of the same crate
         let mut indexed = std::collections::BTreeMap::new();
+        let mut rel_path = String::new();
 
         for entry in rd {
             let Ok(entry) = entry else {
@@ -74,7 +136,7 @@ impl LocalRegistry {
             if entry.file_type().map_or(true, |ft| !ft.is_file()) {
                 continue;
             }
-            let Ok(path) = PathBuf::from_path_buf(entry.path()) else {
+            let Ok(path) = crate::utils::to_utf8_path_buf(entry.path()) else {
                 continue;
             };
 
@@ -89,7 +151,8 @@ impl LocalRegistry {
                 ie
             } else {
                 let krate_name: crate::KrateName<'_> = crate_name.try_into()?;
-                let path = index_root.join(krate_name.relative_path(None));
+                krate_name.relative_path_into(&mut rel_path, None);
+                let path = index_root.join(&rel_path);
 
                 let index_contents =
                     std::fs::read(&path).map_err(|err| Error::IoPath(err, path.clone()))?;
@@ -136,6 +199,14 @@ impl LocalRegistry {
         name: KrateName<'_>,
         _lock: &FileLock,
     ) -> Result<Option<IndexKrate>, Error> {
+        self.krate(name)
+    }
+
+    /// Reads the index entry for the crate
+    ///
+    /// Local registries do not have cache entries that are written by cargo,
+    /// so unlike other indices this does not need a [`FileLock`]
+    pub fn krate(&self, name: KrateName<'_>) -> Result<Option<IndexKrate>, Error> {
         let index_path = self.krate_path(name);
 
         let buf = match std::fs::read(&index_path) {
@@ -155,6 +226,380 @@ impl LocalRegistry {
     pub fn krate_path(&self, name: KrateName<'_>) -> PathBuf {
         make_path(&self.path, name)
     }
+
+    /// Gets the path of the .crate file for the version, if it exists in the
+    /// registry
+    pub fn crate_file_path(&self, version: &crate::IndexVersion) -> Option<PathBuf> {
+        let path = self.path.join(crate::utils::crate_file_name_str(
+            &version.name,
+            &version.version,
+        ));
+        path.is_file().then_some(path)
+    }
+
+    /// Verifies every crate file in the registry against the index, see
+    /// [`Self::verify_with`]
+    #[inline]
+    pub fn verify(&self) -> Result<VerifyReport, Error> {
+        self.verify_with(VerifyOptions::default())
+    }
+
+    /// Verifies every crate file in the registry against the index
+    ///
+    /// Unlike [`Self::validate`], this does not stop at the first problem
+    /// (unless [`VerifyOptions::fail_fast`] is set), and also checks that every
+    /// non-yanked version in the index has a crate file, and that every crate
+    /// file has an index entry.
+    ///
+    /// An `Err` is only returned if the registry itself can't be read, problems
+    /// with individual crates are instead collected in the [`VerifyReport`]
+    pub fn verify_with(&self, options: VerifyOptions) -> Result<VerifyReport, Error> {
+        let mut report = VerifyReport::default();
+
+        let mut crate_files = std::collections::BTreeMap::new();
+        let rd =
+            std::fs::read_dir(&self.path).map_err(|err| Error::IoPath(err, self.path.clone()))?;
+        for entry in rd {
+            let entry = entry.map_err(|err| Error::IoPath(err, self.path.clone()))?;
+            if entry.file_type().map_or(true, |ft| !ft.is_file()) {
+                continue;
+            }
+            let Ok(path) = crate::utils::to_utf8_path_buf(entry.path()) else {
+                continue;
+            };
+            let Some(fname) = path.file_name() else {
+                continue;
+            };
+            if !fname.ends_with(".crate") {
+                continue;
+            }
+
+            crate_files.insert(fname.to_owned(), path);
+        }
+
+        let mut krates = Vec::new();
+        for path in index_entries(&self.path.join("index"))? {
+            let name = path.file_name().unwrap_or_default().to_owned();
+            match std::fs::read(&path) {
+                Ok(contents) => match IndexKrate::from_slice(&contents) {
+                    Ok(ik) => krates.push(ik),
+                    Err(error) => {
+                        report.push(name, VerifyProblem::InvalidIndexEntry { path, error });
+                    }
+                },
+                Err(error) => report.push(name, VerifyProblem::Io { path, error }),
+            }
+        }
+
+        let mut to_hash = Vec::new();
+        for ik in &krates {
+            for iv in &ik.versions {
+                let fname = crate::utils::crate_file_name_str(&iv.name, &iv.version);
+                if let Some(path) = crate_files.remove(&fname) {
+                    to_hash.push((iv, path));
+                } else if !iv.is_yanked() {
+                    report.push(
+                        ik.name().to_owned(),
+                        VerifyProblem::MissingCrateFile {
+                            version: iv.version.clone(),
+                        },
+                    );
+                }
+            }
+        }
+
+        for (fname, path) in crate_files {
+            let name = crate_file_components(&fname).map_or(fname.as_str(), |(name, _)| name);
+            report.push(name.to_owned(), VerifyProblem::OrphanedCrateFile { path });
+        }
+
+        if options.fail_fast && report.has_errors() {
+            report.stopped_early = !to_hash.is_empty();
+            return Ok(report);
+        }
+
+        let failed = std::sync::atomic::AtomicBool::new(false);
+        let hash = |(iv, path): (&crate::IndexVersion, PathBuf)| {
+            use std::sync::atomic::Ordering;
+
+            if options.fail_fast && failed.load(Ordering::Relaxed) {
+                return None;
+            }
+
+            let res = std::fs::File::open(&path)
+                .and_then(|file| validate_checksum::<{ 64 * 1024 }>(file, &iv.checksum));
+
+            let problem = match res {
+                Ok(true) => None,
+                Ok(false) => Some(VerifyProblem::ChecksumMismatch {
+                    version: iv.version.clone(),
+                }),
+                Err(error) => Some(VerifyProblem::Io { path, error }),
+            };
+
+            if problem.is_some() {
+                failed.store(true, Ordering::Relaxed);
+            }
+
+            Some((iv.name.to_string(), problem))
+        };
+
+        let to_check = to_hash.len();
+
+        #[cfg(feature = "sparse")]
+        let hashed: Vec<_> = {
+            use rayon::prelude::*;
+            to_hash.into_par_iter().map(hash).collect()
+        };
+        #[cfg(not(feature = "sparse"))]
+        let hashed: Vec<_> = to_hash.into_iter().map(hash).collect();
+
+        for (name, problem) in hashed.into_iter().flatten() {
+            report.checked += 1;
+            if let Some(problem) = problem {
+                report.push(name, problem);
+            }
+        }
+
+        report.stopped_early = report.checked != to_check;
+        Ok(report)
+    }
+
+    /// Exports the registry as a [sparse index](https://doc.rust-lang.org/cargo/reference/registry-index.html#sparse-protocol),
+    /// see [`Self::export_sparse_with`]
+    #[inline]
+    pub fn export_sparse(&self, out_dir: PathBuf, base_url: &str) -> Result<(), Error> {
+        self.export_sparse_with(out_dir, base_url, &SparseExportOptions::default())
+    }
+
+    /// Exports the registry as a [sparse index](https://doc.rust-lang.org/cargo/reference/registry-index.html#sparse-protocol)
+    ///
+    /// The output directory can be served as static files at `base_url`, ie.
+    /// via `sparse+{base_url}/`, as it contains the index entries in the same
+    /// prefix layout as crates.io, a `config.json` whose `dl` is the
+    /// [`SparseExportOptions::dl_template`] relative to `base_url`, and each
+    /// crate file placed at the path the template expands to
+    pub fn export_sparse_with(
+        &self,
+        out_dir: PathBuf,
+        base_url: &str,
+        options: &SparseExportOptions,
+    ) -> Result<(), Error> {
+        let index_root = self.path.join("index");
+        let dl_path = super::IndexConfig {
+            // The template is relative to the base url, so it must also be
+            // relative to the output directory
+            dl: options.dl_template.trim_start_matches('/').to_owned(),
+            api: None,
+        };
+
+        let place = |src: &Path, dst: PathBuf| -> Result<(), Error> {
+            if let Some(parent) = dst.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| Error::IoPath(err, parent.to_owned()))?;
+            }
+
+            if options.hard_link {
+                // Remove any previous export of the file, as linking fails if
+                // the destination exists
+                let _ = std::fs::remove_file(&dst);
+                if std::fs::hard_link(src, &dst).is_ok() {
+                    return Ok(());
+                }
+            }
+
+            std::fs::copy(src, &dst).map_err(|err| Error::IoPath(err, dst))?;
+            Ok(())
+        };
+
+        for path in index_entries(&index_root)? {
+            let contents = std::fs::read(&path).map_err(|err| Error::IoPath(err, path.clone()))?;
+            let ik = IndexKrate::from_slice(&contents)?;
+
+            let rel_path = path.strip_prefix(&index_root).unwrap_or(&path);
+            let dst = out_dir.join(rel_path);
+            if let Some(parent) = dst.parent() {
+                std::fs::create_dir_all(parent)
+                    .map_err(|err| Error::IoPath(err, parent.to_owned()))?;
+            }
+            std::fs::write(&dst, &contents).map_err(|err| Error::IoPath(err, dst))?;
+
+            for iv in &ik.versions {
+                let Some(src) = self.crate_file_path(iv) else {
+                    continue;
+                };
+                place(&src, out_dir.join(iv.download_url(&dl_path)?))?;
+            }
+        }
+
+        let config = super::IndexConfig {
+            dl: format!(
+                "{}/{}",
+                base_url.trim_end_matches('/'),
+                options.dl_template.trim_start_matches('/')
+            ),
+            api: options.api.clone(),
+        };
+        let config_path = out_dir.join("config.json");
+        std::fs::write(&config_path, serde_json::to_vec_pretty(&config)?)
+            .map_err(|err| Error::IoPath(err, config_path))?;
+
+        Ok(())
+    }
+}
+
+/// Options for [`LocalRegistry::export_sparse_with`]
+#[derive(Clone, Debug)]
+pub struct SparseExportOptions {
+    /// The [`dl`](https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration)
+    /// template, relative to the base url, crate files are placed at the
+    /// path it expands to in the output directory
+    ///
+    /// Defaults to `crates/{crate}/{version}/download`
+    pub dl_template: String,
+    /// The `api` url written to the `config.json`, if any
+    pub api: Option<String>,
+    /// If true, crate files are hard linked rather than copied, falling back
+    /// to a copy if the link fails, eg. if the output is on a different
+    /// filesystem
+    pub hard_link: bool,
+}
+
+impl Default for SparseExportOptions {
+    fn default() -> Self {
+        Self {
+            dl_template: "crates/{crate}/{version}/download".into(),
+            api: None,
+            hard_link: false,
+        }
+    }
+}
+
+/// Options for [`LocalRegistry::verify_with`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VerifyOptions {
+    /// Stops verification once an error has been found, though crate files
+    /// that are already being hashed are still completed and reported
+    pub fail_fast: bool,
+}
+
+/// The severity of a [`VerifyProblem`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The problem does not prevent cargo from using the registry
+    Warning,
+    /// Cargo will fail to use the affected crate
+    Error,
+}
+
+/// A problem found by [`LocalRegistry::verify`]
+#[derive(Debug)]
+pub enum VerifyProblem {
+    /// A non-yanked version in the index does not have a crate file
+    MissingCrateFile {
+        /// The version
+        version: SmolStr,
+    },
+    /// A crate file's checksum does not match the checksum in the index
+    ChecksumMismatch {
+        /// The version
+        version: SmolStr,
+    },
+    /// An index entry could not be deserialized
+    InvalidIndexEntry {
+        /// The path of the index entry
+        path: PathBuf,
+        /// The deserialization error
+        error: Error,
+    },
+    /// An index entry or crate file could not be read
+    Io {
+        /// The path of the file
+        path: PathBuf,
+        /// The I/O error
+        error: std::io::Error,
+    },
+    /// A crate file does not have an index entry for its version
+    OrphanedCrateFile {
+        /// The path of the crate file
+        path: PathBuf,
+    },
+}
+
+impl VerifyProblem {
+    /// The severity of the problem
+    #[inline]
+    pub fn severity(&self) -> Severity {
+        match self {
+            Self::OrphanedCrateFile { .. } => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+/// The problems for a single crate, grouped by severity
+#[derive(Debug, Default)]
+pub struct CrateProblems {
+    /// Problems that will cause cargo to fail
+    pub errors: Vec<VerifyProblem>,
+    /// Problems that don't affect cargo
+    pub warnings: Vec<VerifyProblem>,
+}
+
+/// The result of [`LocalRegistry::verify`]
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// The problems found, keyed by crate name
+    pub crates: std::collections::BTreeMap<String, CrateProblems>,
+    /// The number of crate files whose checksum was computed
+    pub checked: usize,
+    /// True if [`VerifyOptions::fail_fast`] was set and one or more crate
+    /// files were not checked
+    pub stopped_early: bool,
+}
+
+impl VerifyReport {
+    /// True if no problems, including warnings, were found
+    #[inline]
+    pub fn is_clean(&self) -> bool {
+        self.crates.is_empty()
+    }
+
+    /// True if any errors were found
+    #[inline]
+    pub fn has_errors(&self) -> bool {
+        self.crates.values().any(|cp| !cp.errors.is_empty())
+    }
+
+    /// Iterates over every problem of the specified severity
+    pub fn problems(&self, severity: Severity) -> impl Iterator<Item = (&str, &VerifyProblem)> {
+        self.crates.iter().flat_map(move |(name, cp)| {
+            let problems = match severity {
+                Severity::Error => &cp.errors,
+                Severity::Warning => &cp.warnings,
+            };
+            problems.iter().map(move |p| (name.as_str(), p))
+        })
+    }
+
+    fn push(&mut self, name: String, problem: VerifyProblem) {
+        let cp = self.crates.entry(name).or_default();
+        match problem.severity() {
+            Severity::Error => cp.errors.push(problem),
+            Severity::Warning => cp.warnings.push(problem),
+        }
+    }
+}
+
+/// The result of [`LocalRegistryBuilder::insert`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InsertReport {
+    /// The total number of bytes written for the index entry and crate files
+    pub bytes_written: u64,
+    /// The versions whose crate files were written
+    pub written: Vec<SmolStr>,
+    /// The versions whose crate files were already present in the registry
+    pub skipped: Vec<SmolStr>,
 }
 
 /// Allows the building of a local registry from a [`RemoteGitIndex`] or [`RemoteSparseIndex`]
@@ -166,7 +611,8 @@ impl LocalRegistryBuilder {
     /// Creates a builder for the specified directory.
     ///
     /// The directory is required to be empty, but it will
-    /// be created if it doesn't exist
+    /// be created if it doesn't exist, see [`Self::open`] for adding crates
+    /// to an existing local registry
     pub fn create(path: PathBuf) -> Result<Self, Error> {
         if path.exists() {
             let count = std::fs::read_dir(&path)?.count();
@@ -188,49 +634,162 @@ impl LocalRegistryBuilder {
         Ok(Self { path })
     }
 
-    /// Inserts the specified crate index entry and one or more crates files
-    /// into the registry
+    /// Opens an existing local registry so that crates can be added to it,
+    /// see [`Self::insert`]
     ///
-    /// This will fail if the specified crate is already located in the index, it
-    /// is your responsibility to insert the crate and all the versions you want
-    /// only once
-    pub fn insert(&self, krate: &IndexKrate, krates: &[ValidKrate<'_>]) -> Result<u64, Error> {
-        let index_path = make_path(&self.path, krate.name().try_into()?);
-
-        if index_path.exists() {
+    /// Unlike [`Self::create`], the directory must already contain a local
+    /// registry, ie. it must have an `index` directory
+    pub fn open(path: PathBuf) -> Result<Self, Error> {
+        let index_root = path.join("index");
+        if !index_root.is_dir() {
             return Err(Error::IoPath(
                 std::io::Error::new(
-                    std::io::ErrorKind::AlreadyExists,
-                    "crate has already been inserted",
+                    std::io::ErrorKind::NotFound,
+                    "unable to find index directory",
                 ),
-                index_path,
+                index_root,
             ));
         }
 
-        let mut written = {
+        Ok(Self { path })
+    }
+
+    /// Inserts the specified crate index entry and one or more crates files
+    /// into the registry
+    ///
+    /// Every crate file must be for a version in the index entry, with the
+    /// same checksum, and there can only be one crate file per version,
+    /// otherwise an error is returned before anything is written.
+    ///
+    /// If the crate is already located in the index, the versions of the
+    /// specified index entry are merged into the existing one, see
+    /// [`IndexKrate::merge`], and the index entry is only written if it
+    /// changed. Versions that are already in the index with a different
+    /// checksum are rejected, as that would invalidate the crate file that is
+    /// already in the registry. Crate files that already exist with a checksum
+    /// matching their version are not written again, and are instead reported
+    /// in [`InsertReport::skipped`].
+    ///
+    /// The index entry is written with [`WriteOptions::split_features2`](crate::krate::WriteOptions::split_features2)
+    /// enabled so that it can be read by older cargo versions
+    pub fn insert(
+        &self,
+        krate: &IndexKrate,
+        krates: &[ValidKrate<'_>],
+    ) -> Result<InsertReport, Error> {
+        let name = krate.name();
+
+        let mut seen = std::collections::BTreeSet::new();
+        for vk in krates {
+            let mismatch = || LocalRegistryError::VersionMismatch {
+                name: vk.iv.name.to_string(),
+                version: vk.iv.version.clone(),
+            };
+
+            if !vk.iv.name.eq_ignore_ascii_case(name) {
+                return Err(mismatch().into());
+            }
+
+            let iv = krate
+                .versions
+                .iter()
+                .find(|iv| iv.version == vk.iv.version)
+                .ok_or_else(mismatch)?;
+
+            if iv.checksum != vk.iv.checksum {
+                return Err(LocalRegistryError::ChecksumMismatch {
+                    name: name.to_owned(),
+                    version: iv.version.clone(),
+                }
+                .into());
+            }
+
+            if !seen.insert(vk.iv.version.as_str()) {
+                return Err(LocalRegistryError::DuplicateVersion {
+                    name: name.to_owned(),
+                    version: vk.iv.version.clone(),
+                }
+                .into());
+            }
+        }
+
+        let index_path = make_path(&self.path, name.try_into()?);
+
+        let existing = match std::fs::read(&index_path) {
+            Ok(buf) => Some(IndexKrate::from_slice(&buf)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => None,
+            Err(err) => return Err(Error::IoPath(err, index_path)),
+        };
+
+        let merged;
+        let to_write = if let Some(mut existing) = existing {
+            for iv in &krate.versions {
+                if let Some(eiv) = existing
+                    .versions
+                    .iter()
+                    .find(|eiv| eiv.version == iv.version)
+                {
+                    if eiv.checksum != iv.checksum {
+                        return Err(LocalRegistryError::ConflictingVersion {
+                            name: name.to_owned(),
+                            version: iv.version.clone(),
+                        }
+                        .into());
+                    }
+                }
+            }
+
+            let summary = existing.merge(krate.clone());
+            if summary.added == 0 && summary.updated == 0 {
+                None
+            } else {
+                merged = existing;
+                Some(&merged)
+            }
+        } else {
+            Some(krate)
+        };
+
+        let mut report = InsertReport::default();
+
+        if let Some(to_write) = to_write {
             if let Err(err) = std::fs::create_dir_all(index_path.parent().unwrap()) {
                 return Err(Error::IoPath(err, index_path));
             }
 
-            let mut index_entry =
-                std::fs::File::create(&index_path).map_err(|err| Error::IoPath(err, index_path))?;
-            krate.write_json_lines(&mut index_entry)?;
+            let mut index_entry = std::fs::File::create(&index_path)
+                .map_err(|err| Error::IoPath(err, index_path.clone()))?;
+            to_write.write_json_lines_with_options(
+                &mut index_entry,
+                crate::krate::WriteOptions {
+                    split_features2: true,
+                },
+            )?;
             // This _should_ never fail, but even if it does, just ignore it
             use std::io::Seek;
-            index_entry.stream_position().unwrap_or_default()
-        };
+            report.bytes_written += index_entry.stream_position().unwrap_or_default();
+        }
 
         for krate in krates {
-            let krate_fname = format!("{}-{}.crate", krate.iv.name, krate.iv.version);
+            let krate_fname = crate::utils::crate_file_name_str(&krate.iv.name, &krate.iv.version);
             let krate_path = self.path.join(krate_fname);
 
+            // Avoid rewriting crate files that are already in the registry
+            if let Ok(file) = std::fs::File::open(&krate_path) {
+                if validate_checksum::<{ 8 * 1024 }>(file, &krate.iv.checksum).unwrap_or(false) {
+                    report.skipped.push(krate.iv.version.clone());
+                    continue;
+                }
+            }
+
             std::fs::write(&krate_path, &krate.buff)
                 .map_err(|err| Error::IoPath(err, krate_path))?;
 
-            written += krate.buff.len() as u64;
+            report.bytes_written += krate.buff.len() as u64;
+            report.written.push(krate.iv.version.clone());
         }
 
-        Ok(written)
+        Ok(report)
     }
 
     /// Consumes the builder and opens a [`LocalRegistry`]
@@ -255,14 +814,10 @@ impl<'iv> ValidKrate<'iv> {
     ) -> Result<Self, Error> {
         let buff = buff.into();
 
-        let computed = {
-            use sha2::{Digest, Sha256};
-            let mut hasher = Sha256::new();
-            hasher.update(&buff);
-            hasher.finalize()
-        };
+        let mut hasher = ChecksumHasher::new();
+        hasher.update(&buff);
 
-        if computed.as_slice() != expected.checksum.0 {
+        if hasher.finalize() != expected.checksum {
             return Err(LocalRegistryError::ChecksumMismatch {
                 name: expected.name.to_string(),
                 version: expected.version.clone(),
@@ -274,29 +829,109 @@ impl<'iv> ValidKrate<'iv> {
     }
 }
 
+/// Incrementally computes the sha-256 checksum of a crate file
+#[derive(Clone, Default)]
+pub struct ChecksumHasher(sha2::Sha256);
+
+impl ChecksumHasher {
+    /// Creates a new hasher
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the data to the checksum
+    #[inline]
+    pub fn update(&mut self, data: &[u8]) {
+        use sha2::Digest;
+        self.0.update(data);
+    }
+
+    /// Consumes the hasher and retrieves the checksum of all the data it was
+    /// updated with
+    #[inline]
+    pub fn finalize(self) -> crate::krate::Chksum {
+        use sha2::Digest;
+        crate::krate::Chksum(self.0.finalize().into())
+    }
+}
+
 /// Ensures the specified stream's sha-256 matches the specified checksum
 #[inline]
 pub fn validate_checksum<const N: usize>(
-    mut stream: impl std::io::Read,
+    stream: impl std::io::Read,
     chksum: &crate::krate::Chksum,
 ) -> Result<bool, std::io::Error> {
-    use sha2::{Digest, Sha256};
+    let (_, computed) = hash_stream::<N>(stream, None, |_| {})?;
+    Ok(computed == *chksum)
+}
+
+/// The same as [`validate_checksum`], but calls `progress` with the total
+/// number of bytes read so far each time a chunk is hashed
+#[inline]
+pub fn validate_checksum_with_progress(
+    stream: impl std::io::Read,
+    chksum: &crate::krate::Chksum,
+    progress: impl FnMut(u64),
+) -> Result<bool, std::io::Error> {
+    let (_, computed) = hash_stream::<{ 64 * 1024 }>(stream, None, progress)?;
+    Ok(computed == *chksum)
+}
+
+/// Copies the stream to the writer while computing its checksum, so that the
+/// data only needs to be read once, returning the number of bytes copied
+///
+/// If the checksum doesn't match, an [`std::io::ErrorKind::InvalidData`] error
+/// is returned, note that all of the data will have already been written at
+/// that point, so it is up to the caller to discard it
+pub fn copy_and_validate(
+    stream: impl std::io::Read,
+    mut writer: impl std::io::Write,
+    chksum: &crate::krate::Chksum,
+) -> Result<u64, std::io::Error> {
+    let (copied, computed) =
+        hash_stream::<{ 64 * 1024 }>(stream, Some(&mut writer as &mut dyn std::io::Write), |_| {})?;
+    writer.flush()?;
+
+    if computed != *chksum {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("checksum mismatch, expected {chksum}, computed {computed}"),
+        ));
+    }
+
+    Ok(copied)
+}
 
+/// Hashes the stream, optionally writing it to the writer as well
+fn hash_stream<const N: usize>(
+    mut stream: impl std::io::Read,
+    mut writer: Option<&mut dyn std::io::Write>,
+    mut progress: impl FnMut(u64),
+) -> Result<(u64, crate::krate::Chksum), std::io::Error> {
     let mut buffer = [0u8; N];
-    let mut hasher = Sha256::new();
+    let mut hasher = ChecksumHasher::new();
+    let mut total = 0;
 
     loop {
-        let read = stream.read(&mut buffer)?;
-        if read == 0 {
-            break;
+        let read = match stream.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        let chunk = &buffer[..read];
+        hasher.update(chunk);
+        if let Some(writer) = &mut writer {
+            writer.write_all(chunk)?;
         }
 
-        hasher.update(&buffer[..read]);
+        total += read as u64;
+        progress(total);
     }
 
-    let computed = hasher.finalize();
-
-    Ok(computed.as_slice() == chksum.0)
+    Ok((total, hasher.finalize()))
 }
 
 /// Splits a crate package name into its component parts
@@ -317,6 +952,39 @@ pub fn crate_file_components(name: &str) -> Option<(&str, &str)> {
     Some((&name[..dash_sep], &name[dash_sep + 1..]))
 }
 
+/// Gets the paths of every index entry in the index directory of a local registry
+fn index_entries(index_root: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut entries = Vec::new();
+    let mut dirs = vec![index_root.to_owned()];
+
+    while let Some(dir) = dirs.pop() {
+        let rd = std::fs::read_dir(&dir).map_err(|err| Error::IoPath(err, dir.clone()))?;
+        for entry in rd {
+            let entry = entry.map_err(|err| Error::IoPath(err, dir.clone()))?;
+            let Ok(path) = crate::utils::to_utf8_path_buf(entry.path()) else {
+                continue;
+            };
+            let Some(fname) = path.file_name() else {
+                continue;
+            };
+            if fname.starts_with('.') || (dir.as_path() == index_root && fname == "config.json") {
+                continue;
+            }
+
+            let Ok(ft) = entry.file_type() else {
+                continue;
+            };
+            if ft.is_dir() {
+                dirs.push(path);
+            } else {
+                entries.push(path);
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
 #[inline]
 fn make_path(root: &Path, name: KrateName<'_>) -> PathBuf {
     let rel_path = name.relative_path(None);
@@ -331,6 +999,43 @@ fn make_path(root: &Path, name: KrateName<'_>) -> PathBuf {
 
 #[cfg(test)]
 mod test {
+    /// Validates the incremental hashing helpers all agree on the checksum
+    #[test]
+    fn incremental_checksum() {
+        let data: Vec<u8> = (0..200 * 1024).map(|i| (i % 251) as u8).collect();
+
+        let mut hasher = super::ChecksumHasher::new();
+        for chunk in data.chunks(1000) {
+            hasher.update(chunk);
+        }
+        let chksum = hasher.finalize();
+
+        let mut whole = super::ChecksumHasher::new();
+        whole.update(&data);
+        assert_eq!(whole.finalize(), chksum);
+
+        assert!(super::validate_checksum::<1024>(data.as_slice(), &chksum).unwrap());
+
+        let mut reported = Vec::new();
+        assert!(
+            super::validate_checksum_with_progress(data.as_slice(), &chksum, |read| reported
+                .push(read))
+            .unwrap()
+        );
+        assert_eq!(reported.last(), Some(&(data.len() as u64)));
+        assert!(reported.windows(2).all(|w| w[0] < w[1]));
+
+        let mut copy = Vec::new();
+        assert_eq!(
+            super::copy_and_validate(data.as_slice(), &mut copy, &chksum).unwrap(),
+            data.len() as u64
+        );
+        assert_eq!(copy, data);
+
+        let err = super::copy_and_validate(&data[1..], std::io::sink(), &chksum).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    }
+
     #[test]
     fn gets_components() {
         use super::crate_file_components as cfc;
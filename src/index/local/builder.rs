@@ -2,11 +2,15 @@
 
 use crate::Error;
 
+/// The maximum amount of time, in milliseconds, to wait between download retries
+const MAX_BACKOFF_MS: u64 = 10_000;
+
 /// Wrapper around a [`reqwest::blocking::Client`] to condition it correctly
 /// for making requests to a remote registry
 #[derive(Clone)]
 pub struct Client {
     inner: reqwest::blocking::Client,
+    max_retries: u8,
 }
 
 impl Client {
@@ -17,21 +21,442 @@ impl Client {
         // they aren't automatically decompressed by reqwest, screwing up the
         // checksum computation
         let inner = builder.no_gzip().build()?;
-        Ok(Self { inner })
+        Ok(Self {
+            inner,
+            max_retries: 3,
+        })
+    }
+
+    /// Sets the maximum number of times a download is retried after a
+    /// transport error or a 5xx response, defaults to 3
+    ///
+    /// The delay between retries doubles with each attempt, up to a maximum
+    /// of 10 seconds
+    #[inline]
+    pub fn max_retries(mut self, max_retries: u8) -> Self {
+        self.max_retries = max_retries;
+        self
     }
 }
 
 impl<'iv> super::ValidKrate<'iv> {
     /// Downloads and validates a .crate from the specified index
+    ///
+    /// Transport errors, including the connection being dropped while
+    /// receiving the body, and 5xx responses are retried with an exponential
+    /// backoff, up to [`Client::max_retries`] times. Other responses, as well
+    /// as a checksum mismatch, fail immediately
     pub fn download(
         client: &Client,
         config: &crate::index::IndexConfig,
         version: &'iv crate::IndexVersion,
     ) -> Result<Self, Error> {
-        let url = config.download_url(version.name.as_str().try_into()?, version.version.as_ref());
+        let url = version.download_url(config)?;
+
+        let mut attempt = 0;
+        let body = loop {
+            let res = client
+                .inner
+                .get(&url)
+                .send()
+                .and_then(|res| res.error_for_status())
+                .and_then(|res| res.bytes());
+
+            match res {
+                Ok(body) => break body,
+                Err(err) => {
+                    let is_retryable = match err.status() {
+                        Some(sc) => sc.is_server_error(),
+                        None => !err.is_builder(),
+                    };
+                    if !is_retryable || attempt >= client.max_retries {
+                        return Err(err.into());
+                    }
+
+                    // Cap the exponent, as well as the backoff, so that large
+                    // retry counts can't overflow the shift
+                    let backoff = (100u64 << attempt.min(16)).min(MAX_BACKOFF_MS);
+                    std::thread::sleep(std::time::Duration::from_millis(backoff));
+                    attempt += 1;
+                }
+            }
+        };
 
-        let res = client.inner.get(url).send()?.error_for_status()?;
-        let body = res.bytes()?;
         Self::validate(body, version)
     }
 }
+
+/// The progress of [`from_lockfile`], reported after each package is processed
+#[cfg(feature = "sparse")]
+#[derive(Copy, Clone, Debug)]
+pub struct LockfileProgress<'p> {
+    /// The name of the package
+    pub name: &'p str,
+    /// The version of the package
+    pub version: &'p str,
+    /// True if the package was added to the registry, or was already in it
+    pub ok: bool,
+    /// The number of packages that have been processed so far
+    pub completed: usize,
+    /// The total number of packages to process
+    pub total: usize,
+}
+
+/// Options for [`from_lockfile`]
+#[cfg(feature = "sparse")]
+pub struct LockfileOptions<'cb> {
+    /// If true and a local registry already exists in the output directory,
+    /// packages are added to it and crate files that are already present are
+    /// not downloaded again, otherwise the output directory must be empty
+    pub reuse_existing: bool,
+    /// The maximum number of crate files downloaded in parallel
+    pub max_parallel: usize,
+    /// Called after each package is processed
+    pub progress: Option<&'cb (dyn Fn(LockfileProgress<'_>) + Sync)>,
+}
+
+#[cfg(feature = "sparse")]
+impl Default for LockfileOptions<'_> {
+    fn default() -> Self {
+        Self {
+            reuse_existing: false,
+            max_parallel: 8,
+            progress: None,
+        }
+    }
+}
+
+/// Builds a local registry containing every registry package in a `Cargo.lock`
+///
+/// Only packages whose source is the specified index, or crates.io if the
+/// index is crates.io, are added. The index metadata for every package is
+/// retrieved first, then each crate file is downloaded and verified against
+/// both the index and the lockfile checksum, and the registry is finalized.
+///
+/// Failures for individual packages don't stop the others from being added,
+/// instead they are all returned as a [`LocalRegistryError::Packages`](super::LocalRegistryError::Packages)
+/// once every package has been processed
+#[cfg(feature = "sparse")]
+pub fn from_lockfile(
+    lockfile: &crate::Path,
+    index: &crate::index::RemoteSparseIndex,
+    client: &Client,
+    out_dir: crate::PathBuf,
+    options: LockfileOptions<'_>,
+    lock: &crate::index::FileLock,
+) -> Result<super::LocalRegistry, Error> {
+    use super::{LocalRegistryBuilder, LocalRegistryError, PackageError, ValidKrate};
+    use rayon::prelude::*;
+    use std::collections::{BTreeMap, BTreeSet};
+
+    let contents =
+        std::fs::read_to_string(lockfile).map_err(|err| Error::IoPath(err, lockfile.to_owned()))?;
+    let packages = lockfile_packages(&contents, index.index.url())?;
+
+    let lrb = if options.reuse_existing && out_dir.join("index").is_dir() {
+        LocalRegistryBuilder::open(out_dir)?
+    } else {
+        LocalRegistryBuilder::create(out_dir)?
+    };
+
+    let config = index.index_config()?;
+
+    let mut by_crate = BTreeMap::<_, Vec<_>>::new();
+    for pkg in &packages {
+        by_crate.entry(pkg.name.as_str()).or_default().push(pkg);
+    }
+
+    let mut krates = index.krates(
+        by_crate
+            .keys()
+            .map(|name| (*name).to_owned())
+            .collect::<BTreeSet<_>>(),
+        true,
+        lock,
+    );
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(options.max_parallel.max(1))
+        .build()
+        .map_err(|err| Error::Io(std::io::Error::new(std::io::ErrorKind::Other, err)))?;
+
+    let total = packages.len();
+    let completed = std::sync::atomic::AtomicUsize::new(0);
+    let failures = std::sync::Mutex::new(Vec::new());
+
+    let report = |pkg: &LockedPackage, result: Result<(), Error>| {
+        let ok = result.is_ok();
+        if let Err(err) = result {
+            failures.lock().unwrap().push(PackageError {
+                name: pkg.name.clone(),
+                version: pkg.version.as_str().into(),
+                source: Box::new(err),
+            });
+        }
+
+        let completed = completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed) + 1;
+        if let Some(progress) = options.progress {
+            progress(LockfileProgress {
+                name: &pkg.name,
+                version: &pkg.version,
+                ok,
+                completed,
+                total,
+            });
+        }
+    };
+
+    let work: Vec<_> = by_crate
+        .into_iter()
+        .map(|(name, pkgs)| (krates.remove(name), pkgs))
+        .collect();
+
+    pool.install(|| {
+        work.into_par_iter().for_each(|(ik, pkgs)| {
+            let ik = match ik.unwrap_or(Ok(None)) {
+                Ok(Some(ik)) => ik,
+                Ok(None) => {
+                    for pkg in pkgs {
+                        report(
+                            pkg,
+                            Err(LocalRegistryError::MissingVersion {
+                                name: pkg.name.clone(),
+                                version: pkg.version.as_str().into(),
+                            }
+                            .into()),
+                        );
+                    }
+                    return;
+                }
+                Err(err) => {
+                    // The error can't be cloned, so it is only reported for
+                    // the first version of the crate
+                    let mut err = Some(err);
+                    for pkg in pkgs {
+                        let err = err.take().unwrap_or_else(|| {
+                            Error::Io(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                "failed to retrieve the index entry for the crate",
+                            ))
+                        });
+                        report(pkg, Err(err));
+                    }
+                    return;
+                }
+            };
+
+            let versions: Vec<_> = pkgs
+                .par_iter()
+                .map(|pkg| {
+                    let res = (|| {
+                        let iv = ik
+                            .versions
+                            .iter()
+                            .find(|iv| iv.version == pkg.version)
+                            .ok_or_else(|| LocalRegistryError::MissingVersion {
+                                name: pkg.name.clone(),
+                                version: pkg.version.as_str().into(),
+                            })?;
+
+                        if pkg
+                            .checksum
+                            .as_ref()
+                            .map_or(false, |chk| *chk != iv.checksum)
+                        {
+                            return Err(LocalRegistryError::ChecksumMismatch {
+                                name: pkg.name.clone(),
+                                version: pkg.version.as_str().into(),
+                            }
+                            .into());
+                        }
+
+                        let existing = lrb
+                            .path
+                            .join(crate::utils::crate_file_name_str(&iv.name, &iv.version));
+                        let is_present = std::fs::File::open(existing).ok().map_or(false, |file| {
+                            super::validate_checksum::<{ 8 * 1024 }>(file, &iv.checksum)
+                                .unwrap_or(false)
+                        });
+
+                        let vk = if is_present {
+                            None
+                        } else {
+                            Some(ValidKrate::download(client, &config, iv)?)
+                        };
+
+                        Ok((iv, vk))
+                    })();
+
+                    (*pkg, res)
+                })
+                .collect();
+
+            let mut ok = Vec::new();
+            let mut failed = Vec::new();
+            for (pkg, res) in versions {
+                match res {
+                    Ok(v) => ok.push((pkg, v)),
+                    Err(err) => failed.push((pkg, err)),
+                }
+            }
+
+            let entry = crate::IndexKrate {
+                versions: ok.iter().map(|(_, (iv, _))| (*iv).clone()).collect(),
+            };
+            let crate_files: Vec<_> = ok.iter_mut().filter_map(|(_, (_, vk))| vk.take()).collect();
+
+            let inserted = if entry.versions.is_empty() {
+                Ok(())
+            } else {
+                lrb.insert(&entry, &crate_files).map(|_| ())
+            };
+
+            match inserted {
+                Ok(()) => {
+                    for (pkg, _) in ok {
+                        report(pkg, Ok(()));
+                    }
+                }
+                Err(err) => {
+                    let mut err = Some(err);
+                    for (pkg, _) in ok {
+                        let err = err.take().unwrap_or_else(|| {
+                            Error::Io(std::io::Error::new(
+                                std::io::ErrorKind::Other,
+                                "failed to insert the crate into the local registry",
+                            ))
+                        });
+                        report(pkg, Err(err));
+                    }
+                }
+            }
+
+            for (pkg, err) in failed {
+                report(pkg, Err(err));
+            }
+        });
+    });
+
+    let failures = failures.into_inner().unwrap();
+    if !failures.is_empty() {
+        return Err(LocalRegistryError::Packages(failures).into());
+    }
+
+    lrb.finalize(false)
+}
+
+/// A registry package in a `Cargo.lock`
+#[cfg(feature = "sparse")]
+struct LockedPackage {
+    name: String,
+    version: String,
+    checksum: Option<crate::krate::Chksum>,
+}
+
+/// Gets the packages in the lockfile that come from the specified sparse index
+#[cfg(feature = "sparse")]
+fn lockfile_packages(contents: &str, index_url: &str) -> Result<Vec<LockedPackage>, Error> {
+    let lockfile = toml_span::parse(contents).map_err(Box::new)?;
+
+    // The url may or may not have the `sparse+` prefix, eg. SparseIndex::url
+    // strips it
+    let normalize = |url: &str| -> String {
+        url.strip_prefix("sparse+")
+            .unwrap_or(url)
+            .trim_end_matches('/')
+            .to_owned()
+    };
+
+    let index_url = normalize(index_url);
+    let is_crates_io = index_url == normalize(crate::CRATES_IO_HTTP_INDEX);
+    let matches_source = |source: &str| {
+        if let Some(url) = source.strip_prefix("sparse+") {
+            normalize(url) == index_url
+        } else if let Some(url) = source.strip_prefix("registry+") {
+            is_crates_io && url == crate::CRATES_IO_INDEX
+        } else {
+            false
+        }
+    };
+
+    let Some(packages) = lockfile.pointer("/package").and_then(|p| p.as_array()) else {
+        return Ok(Vec::new());
+    };
+
+    Ok(packages
+        .iter()
+        .filter_map(|pkg| {
+            let pkg = pkg.as_table()?;
+            let field = |key: &str| pkg.get(key).and_then(|v| v.as_str());
+
+            if !matches_source(field("source")?) {
+                return None;
+            }
+
+            Some(LockedPackage {
+                name: field("name")?.to_owned(),
+                version: field("version")?.to_owned(),
+                checksum: field("checksum").and_then(|chk| chk.parse().ok()),
+            })
+        })
+        .collect())
+}
+
+#[cfg(all(test, feature = "sparse"))]
+mod test {
+    /// Validates only the packages from the requested registry are used
+    #[test]
+    fn reads_lockfile_packages() {
+        const LOCKFILE: &str = r#"
+version = 3
+
+[[package]]
+name = "local"
+version = "0.1.0"
+dependencies = ["smallvec"]
+
+[[package]]
+name = "smallvec"
+version = "1.11.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+checksum = "62bb4feee49fdd9f707ef802e22365a35de4b7b299de4763d44bfea899442ff9"
+
+[[package]]
+name = "sparse-dep"
+version = "2.0.0"
+source = "sparse+https://index.crates.io/"
+checksum = "0000000000000000000000000000000000000000000000000000000000000000"
+
+[[package]]
+name = "other"
+version = "1.0.0"
+source = "sparse+https://my-registry.example.com/index/"
+
+[[package]]
+name = "git-dep"
+version = "0.2.0"
+source = "git+https://github.com/EmbarkStudios/tame-index?rev=abc#abc"
+"#;
+
+        let names = |url: &str| {
+            super::lockfile_packages(LOCKFILE, url)
+                .unwrap()
+                .into_iter()
+                .map(|pkg| format!("{}-{}-{}", pkg.name, pkg.version, pkg.checksum.is_some()))
+                .collect::<Vec<_>>()
+        };
+
+        // Both with and without the sparse+ prefix, as SparseIndex::url strips it
+        for url in [crate::CRATES_IO_HTTP_INDEX, "https://index.crates.io/"] {
+            assert_eq!(
+                names(url),
+                ["smallvec-1.11.0-true", "sparse-dep-2.0.0-true"]
+            );
+        }
+        assert_eq!(
+            names("https://my-registry.example.com/index"),
+            ["other-1.0.0-false"]
+        );
+        assert!(names("https://nope.example.com/").is_empty());
+    }
+}
@@ -1,10 +1,10 @@
 //! Helpers for initializing the remote and local disk location of an index
 
-use crate::{Error, Path, PathBuf};
+use crate::{error::SourceReplacementIssue, Error, Path, PathBuf};
 use std::borrow::Cow;
 
 /// A remote index url
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub enum IndexUrl<'iu> {
     /// The canonical crates.io HTTP sparse index.
     ///
@@ -23,16 +23,37 @@ pub enum IndexUrl<'iu> {
     NonCratesIo(Cow<'iu, str>),
     /// A [local registry](crate::index::LocalRegistry)
     Local(Cow<'iu, Path>),
+    /// A [directory source](crate::index::DirectorySource), eg. crates
+    /// vendored via `cargo vendor`
+    Directory(Cow<'iu, Path>),
 }
 
 impl<'iu> IndexUrl<'iu> {
+    /// Creates a [`Self::NonCratesIo`] for the specified url
+    #[inline]
+    pub fn non_crates_io(url: impl Into<Cow<'iu, str>>) -> Self {
+        Self::NonCratesIo(url.into())
+    }
+
+    /// Converts this into an [`IndexUrl`] that owns its data, allowing it to be
+    /// stored or sent elsewhere independently of what it was borrowed from
+    pub fn into_owned(self) -> IndexUrl<'static> {
+        match self {
+            Self::CratesIoSparse => IndexUrl::CratesIoSparse,
+            Self::CratesIoGit => IndexUrl::CratesIoGit,
+            Self::NonCratesIo(url) => IndexUrl::NonCratesIo(Cow::Owned(url.into_owned())),
+            Self::Local(path) => IndexUrl::Local(Cow::Owned(path.into_owned())),
+            Self::Directory(path) => IndexUrl::Directory(Cow::Owned(path.into_owned())),
+        }
+    }
+
     /// Gets the url as a string
     pub fn as_str(&'iu self) -> &'iu str {
         match self {
             Self::CratesIoSparse => crate::CRATES_IO_HTTP_INDEX,
             Self::CratesIoGit => crate::CRATES_IO_INDEX,
             Self::NonCratesIo(url) => url,
-            Self::Local(pb) => pb.as_str(),
+            Self::Local(pb) | Self::Directory(pb) => pb.as_str(),
         }
     }
 
@@ -40,124 +61,397 @@ impl<'iu> IndexUrl<'iu> {
     pub fn is_sparse(&self) -> bool {
         match self {
             Self::CratesIoSparse => true,
-            Self::CratesIoGit | Self::Local(..) => false,
-            Self::NonCratesIo(url) => url.starts_with("sparse+http"),
+            Self::CratesIoGit | Self::Local(..) | Self::Directory(..) => false,
+            Self::NonCratesIo(url) => {
+                crate::utils::url_kind(url).ok() == Some(crate::utils::UrlKind::SparseRegistry)
+            }
+        }
+    }
+
+    /// Validates the url is for the kind of index it appears to be
+    ///
+    /// Sparse indices require the `sparse+` scheme modifier, which is easy to
+    /// forget, resulting in a git index being used for a sparse index, which
+    /// then fails with confusing git errors. This fails with
+    /// [`InvalidUrlError::MissingSparse`](crate::InvalidUrlError::MissingSparse)
+    /// for `http(s)` urls without the modifier that are obviously sparse
+    /// indices, ie. the host is a known sparse index, or the url ends with a
+    /// `/` and the host is not a known git host. It also fails with
+    /// [`InvalidUrlError::SparseNonHttp`](crate::InvalidUrlError::SparseNonHttp)
+    /// if the `sparse+` modifier is used with a scheme other than `http(s)`.
+    pub fn validate(&self) -> Result<(), Error> {
+        let Self::NonCratesIo(url) = self else {
+            return Ok(());
+        };
+
+        let invalid = |source| -> Result<(), Error> {
+            Err(crate::InvalidUrl {
+                url: url.as_ref().to_owned(),
+                source,
+            }
+            .into())
+        };
+
+        match crate::utils::url_kind(url)? {
+            crate::utils::UrlKind::SparseRegistry => {
+                if !url.starts_with("sparse+http://") && !url.starts_with("sparse+https://") {
+                    return invalid(crate::InvalidUrlError::SparseNonHttp);
+                }
+            }
+            crate::utils::UrlKind::GitRegistry => {
+                let url = url.strip_prefix("registry+").unwrap_or(url);
+                if looks_sparse(url) {
+                    return invalid(crate::InvalidUrlError::MissingSparse);
+                }
+            }
+            crate::utils::UrlKind::GitRepo => {}
         }
+
+        Ok(())
     }
 
     /// Gets the [`IndexUrl`] for crates.io, depending on the local environment.
     ///
-    /// 1. Determines if the crates.io registry has been [replaced](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
-    /// 2. Determines if the protocol was explicitly [configured](https://doc.rust-lang.org/cargo/reference/config.html#registriescrates-ioprotocol) by the user
-    /// 3. Otherwise, detects the version of cargo (see [`crate::utils::cargo_version`]), and uses that to determine the appropriate default
+    /// See [`crates_io_protocol`] for how this is determined, and the reason for
+    /// the decision
+    ///
+    /// The detected cargo version is cached for the lifetime of the process, so
+    /// repeatedly calling this for the same config root only spawns cargo once
+    #[inline]
     pub fn crates_io(
         config_root: Option<PathBuf>,
         cargo_home: Option<&Path>,
         cargo_version: Option<&str>,
     ) -> Result<Self, Error> {
+        Self::crates_io_with_overrides(
+            config_root,
+            cargo_home,
+            cargo_version,
+            &ConfigOverrides::default(),
+        )
+    }
+
+    /// The same as [`Self::crates_io`], but with config values that take
+    /// precedence over config files and environment variables
+    pub fn crates_io_with_overrides(
+        config_root: Option<PathBuf>,
+        cargo_home: Option<&Path>,
+        cargo_version: Option<&str>,
+        overrides: &ConfigOverrides,
+    ) -> Result<Self, Error> {
+        Ok(crates_io_protocol_with(
+            std::env::var("CARGO_REGISTRIES_CRATES_IO_PROTOCOL").ok(),
+            config_root,
+            cargo_home,
+            cargo_version,
+            overrides,
+        )?
+        .into_url())
+    }
+
+    /// Gets the [`IndexUrl`] for crates.io, using the specified protocol
+    /// unless it is [`Protocol::Auto`], in which case this is the same as
+    /// [`Self::crates_io`]
+    ///
+    /// Note that [source replacement](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
+    /// still takes precedence over an explicit protocol, the same as cargo,
+    /// which ignores the protocol for crates.io if it has been replaced
+    pub fn crates_io_with_protocol(
+        config_root: Option<PathBuf>,
+        cargo_home: Option<&Path>,
+        protocol: Protocol,
+    ) -> Result<Self, Error> {
+        let url = match protocol {
+            Protocol::Auto => return Self::crates_io(config_root, cargo_home, None),
+            Protocol::Sparse => Self::CratesIoSparse,
+            Protocol::Git => Self::CratesIoGit,
+        };
+
         // If the crates.io registry has been replaced it doesn't matter what
         // the protocol for it has been changed to
-        if let Some(replacement) =
-            get_source_replacement(config_root.clone(), cargo_home, "crates-io")?
-        {
+        if let Some(replacement) = get_source_replacement(
+            config_root,
+            cargo_home,
+            &ConfigOverrides::default(),
+            "crates-io",
+        )? {
             return Ok(replacement);
         }
 
-        let sparse_index = match std::env::var("CARGO_REGISTRIES_CRATES_IO_PROTOCOL")
-            .ok()
-            .as_deref()
-        {
-            Some("sparse") => true,
-            Some("git") => false,
-            _ => {
-                let sparse_index =
-                    read_cargo_config(config_root, cargo_home, |config| {
-                        match config
-                            .pointer("/registries/crates-io/protocol")
-                            .and_then(|p| p.as_str())?
-                        {
-                            "sparse" => Some(true),
-                            "git" => Some(false),
-                            _ => None,
-                        }
-                    })?;
-
-                if let Some(si) = sparse_index {
-                    si
-                } else {
-                    let vers = match cargo_version {
-                        Some(v) => v.trim().parse()?,
-                        None => crate::utils::cargo_version(None)?,
-                    };
-
-                    vers >= semver::Version::new(1, 70, 0)
-                }
-            }
-        };
-
-        Ok(if sparse_index {
-            Self::CratesIoSparse
-        } else {
-            Self::CratesIoGit
-        })
+        Ok(url)
     }
 
     /// Creates an [`IndexUrl`] for the specified registry name
     ///
-    /// 1. Checks if [`CARGO_REGISTRIES_<name>_INDEX`](https://doc.rust-lang.org/cargo/reference/config.html#registriesnameindex) is set
-    /// 2. Checks if the source for the registry has been [replaced](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
-    /// 3. Uses the value of [`registries.<name>.index`](https://doc.rust-lang.org/cargo/reference/config.html#registriesnameindex) otherwise
+    /// See [`Self::for_registry`]
+    #[deprecated = "use `IndexUrl::for_registry` instead"]
+    #[inline]
     pub fn for_registry_name(
         config_root: Option<PathBuf>,
         cargo_home: Option<&Path>,
         registry_name: &str,
     ) -> Result<Self, Error> {
-        // Check if the index was explicitly specified
-        let mut env = String::with_capacity(17 + registry_name.len() + 6);
-        env.push_str("CARGO_REGISTRIES_");
-
-        if registry_name.is_ascii() {
-            for c in registry_name.chars() {
-                if c == '-' {
-                    env.push('_');
-                } else {
-                    env.push(c.to_ascii_uppercase());
-                }
-            }
-        } else {
-            let mut upper = registry_name.to_uppercase();
-            if upper.contains('-') {
-                upper = upper.replace('-', "_");
-            }
-
-            env.push_str(&upper);
-        }
+        IndexUrl::for_registry(registry_name, config_root, cargo_home)
+    }
 
-        env.push_str("_INDEX");
+    /// Creates an [`IndexUrl`] for the specified registry name, eg. the value
+    /// passed to cargo's `--registry` flag
+    ///
+    /// 1. If the name is `crates-io`, uses [`Self::crates_io`]
+    /// 2. Checks if the source for the registry has been [replaced](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
+    /// 3. Uses the value of [`registries.<name>.index`](https://doc.rust-lang.org/cargo/reference/config.html#registriesnameindex),
+    ///    which can be overridden by [`CARGO_REGISTRIES_<name>_INDEX`](https://doc.rust-lang.org/cargo/reference/config.html#registriesnameindex)
+    ///
+    /// If the registry is not configured, the returned
+    /// [`Error::UnknownRegistry`] lists the cargo config files that were searched
+    #[inline]
+    pub fn for_registry(
+        registry_name: &str,
+        config_root: Option<PathBuf>,
+        cargo_home: Option<&Path>,
+    ) -> Result<IndexUrl<'static>, Error> {
+        Self::for_registry_with_overrides(
+            registry_name,
+            config_root,
+            cargo_home,
+            &ConfigOverrides::default(),
+        )
+    }
 
-        match std::env::var(&env) {
-            Ok(index) => return Ok(Self::NonCratesIo(index.into())),
-            Err(err) => {
-                if let std::env::VarError::NotUnicode(_nu) = err {
-                    return Err(Error::NonUtf8EnvVar(env.into()));
-                }
-            }
+    /// The same as [`Self::for_registry`], but with config values that take
+    /// precedence over config files and environment variables
+    pub fn for_registry_with_overrides(
+        registry_name: &str,
+        config_root: Option<PathBuf>,
+        cargo_home: Option<&Path>,
+        overrides: &ConfigOverrides,
+    ) -> Result<IndexUrl<'static>, Error> {
+        if registry_name == "crates-io" {
+            return IndexUrl::crates_io_with_overrides(config_root, cargo_home, None, overrides);
         }
 
         if let Some(replacement) =
-            get_source_replacement(config_root.clone(), cargo_home, registry_name)?
+            get_source_replacement(config_root.clone(), cargo_home, overrides, registry_name)?
         {
             return Ok(replacement);
         }
 
-        read_cargo_config(config_root, cargo_home, |config| {
+        let index = read_cargo_config(config_root.clone(), cargo_home, overrides, |config| {
             let path = format!("/registries/{registry_name}/index");
             config
                 .pointer(&path)?
                 .as_str()
-                .map(|si| Self::NonCratesIo(si.to_owned().into()))
-        })?
-        .ok_or_else(|| Error::UnknownRegistry(registry_name.into()))
+                .map(|si| IndexUrl::NonCratesIo(si.to_owned().into()))
+        })?;
+
+        if let Some(index) = index {
+            return Ok(index);
+        }
+
+        // The environment variable is applied to the config above, but it
+        // can't be for registries that are only specified in the environment
+        // and use `_` in their name, as the mangling is ambiguous
+        let env = config_env_var(&["registries", registry_name, "index"]);
+
+        match std::env::var(&env) {
+            Ok(index) => Ok(IndexUrl::NonCratesIo(index.into())),
+            Err(std::env::VarError::NotUnicode(_nu)) => Err(Error::NonUtf8EnvVar(env.into())),
+            Err(std::env::VarError::NotPresent) => Err(Error::UnknownRegistry {
+                name: registry_name.into(),
+                env,
+                searched: cargo_config_paths(config_root, cargo_home, &overrides.search_stop),
+            }),
+        }
+    }
+
+    /// Gets the name and [`IndexUrl`] of the registry cargo uses by default,
+    /// if one has been [configured](https://doc.rust-lang.org/cargo/reference/config.html#registrydefault)
+    ///
+    /// 1. Checks if `CARGO_REGISTRY_DEFAULT` is set
+    /// 2. Uses the value of `registry.default` otherwise
+    ///
+    /// The name is then resolved via [`Self::for_registry`]. If no default is
+    /// configured, cargo uses crates.io, see [`Self::crates_io`]
+    pub fn default_registry(
+        config_root: Option<PathBuf>,
+        cargo_home: Option<&Path>,
+    ) -> Result<Option<(String, IndexUrl<'static>)>, Error> {
+        const ENV: &str = "CARGO_REGISTRY_DEFAULT";
+
+        let name = match std::env::var(ENV) {
+            Ok(name) => Some(name),
+            Err(std::env::VarError::NotUnicode(_nu)) => {
+                return Err(Error::NonUtf8EnvVar(ENV.into()));
+            }
+            Err(std::env::VarError::NotPresent) => read_cargo_config(
+                config_root.clone(),
+                cargo_home,
+                &ConfigOverrides::default(),
+                |config| {
+                    config
+                        .pointer("/registry/default")?
+                        .as_str()
+                        .map(String::from)
+                },
+            )?,
+        };
+
+        let Some(name) = name else {
+            return Ok(None);
+        };
+
+        let url = IndexUrl::for_registry(&name, config_root, cargo_home)?;
+        Ok(Some((name, url)))
+    }
+}
+
+/// Selects the registry whose index should be opened
+#[derive(Debug)]
+pub enum RegistrySelector<'rs> {
+    /// The crates.io registry, see [`IndexUrl::crates_io`]
+    CratesIo,
+    /// A registry configured by name, see [`IndexUrl::for_registry`]
+    Named(&'rs str),
+    /// An explicit index url
+    Url(IndexUrl<'rs>),
+}
+
+impl<'rs> RegistrySelector<'rs> {
+    /// Resolves the selector to the url of the registry's index
+    pub fn into_url(
+        self,
+        config_root: Option<PathBuf>,
+        cargo_home: Option<&Path>,
+    ) -> Result<IndexUrl<'rs>, Error> {
+        match self {
+            Self::CratesIo => IndexUrl::crates_io(config_root, cargo_home, None),
+            Self::Named(name) => IndexUrl::for_registry(name, config_root, cargo_home),
+            Self::Url(url) => Ok(url),
+        }
+    }
+}
+
+impl<'rs> From<IndexUrl<'rs>> for RegistrySelector<'rs> {
+    #[inline]
+    fn from(url: IndexUrl<'rs>) -> Self {
+        Self::Url(url)
+    }
+}
+
+/// Hosts that only serve sparse indices
+const KNOWN_SPARSE_HOSTS: &[&str] = &["index.crates.io"];
+/// Hosts that serve git repositories
+const KNOWN_GIT_HOSTS: &[&str] = &[
+    "github.com",
+    "gitlab.com",
+    "bitbucket.org",
+    "codeberg.org",
+    "git.sr.ht",
+    "dev.azure.com",
+    "ssh.dev.azure.com",
+];
+
+/// Heuristically determines if an `http(s)` url without the `sparse+` modifier
+/// is actually for a sparse index
+fn looks_sparse(url: &str) -> bool {
+    let Some(rest) = url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    else {
+        return false;
+    };
+
+    let authority = rest.split('/').next().unwrap_or_default();
+    let host = authority.rsplit('@').next().unwrap_or_default();
+    let host = host.split(':').next().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host);
+
+    if KNOWN_SPARSE_HOSTS.contains(&host) {
+        return true;
+    }
+
+    url.ends_with('/')
+        && !url.trim_end_matches('/').ends_with(".git")
+        && !KNOWN_GIT_HOSTS.contains(&host)
+}
+
+/// Gets the name of the `CARGO_<key>` environment variable for the config
+/// key, using cargo's name mangling of uppercasing each part of the key and
+/// replacing `-` and `.` with `_`
+pub(crate) fn config_env_var(key: &[&str]) -> String {
+    let mut env = String::from("CARGO");
+
+    for part in key {
+        env.push('_');
+        mangle_env_part(part, &mut env);
+    }
+
+    env
+}
+
+/// Uppercases the key part and replaces `-` with `_`
+fn mangle_env_part(part: &str, env: &mut String) {
+    if part.is_ascii() {
+        for c in part.chars() {
+            if c == '-' {
+                env.push('_');
+            } else {
+                env.push(c.to_ascii_uppercase());
+            }
+        }
+    } else {
+        let mut upper = part.to_uppercase();
+        if upper.contains('-') {
+            upper = upper.replace('-', "_");
+        }
+
+        env.push_str(&upper);
+    }
+}
+
+impl std::str::FromStr for IndexUrl<'static> {
+    type Err = Error;
+
+    /// Parses an index url, eg. from a command line argument
+    ///
+    /// * The canonical crates.io urls are parsed as [`IndexUrl::CratesIoSparse`]
+    ///   and [`IndexUrl::CratesIoGit`]
+    /// * Absolute paths, and relative paths beginning with `./` or `../`, are
+    ///   parsed as [`IndexUrl::Local`]
+    /// * Other urls are parsed as [`IndexUrl::NonCratesIo`] if they have a
+    ///   valid scheme and scheme modifier, and pass [`IndexUrl::validate`]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let crates_io = |canonical: &str| {
+            let canonical = canonical.trim_end_matches('/');
+            let s = s.trim_end_matches('/');
+            s == canonical || s.strip_prefix("registry+") == Some(canonical)
+        };
+
+        if crates_io(crate::CRATES_IO_HTTP_INDEX) {
+            return Ok(Self::CratesIoSparse);
+        } else if crates_io(crate::CRATES_IO_INDEX) {
+            return Ok(Self::CratesIoGit);
+        }
+
+        if !s.contains("://")
+            && (crate::utils::is_local_path(s)
+                || ["./", "../", ".\\", "..\\"]
+                    .iter()
+                    .any(|prefix| s.starts_with(prefix)))
+        {
+            return Ok(Self::Local(PathBuf::from(s).into()));
+        }
+
+        let url = Self::NonCratesIo(s.to_owned().into());
+        url.validate()?;
+        Ok(url)
+    }
+}
+
+impl std::fmt::Display for IndexUrl<'_> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
 }
 
@@ -168,19 +462,294 @@ impl<'iu> From<&'iu str> for IndexUrl<'iu> {
     }
 }
 
+impl From<String> for IndexUrl<'static> {
+    #[inline]
+    fn from(s: String) -> Self {
+        Self::NonCratesIo(s.into())
+    }
+}
+
+/// The protocol used to access the crates.io index, see
+/// [`IndexUrl::crates_io_with_protocol`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Protocol {
+    /// The protocol is determined by the user's environment, see [`IndexUrl::crates_io`]
+    #[default]
+    Auto,
+    /// The HTTP sparse index
+    Sparse,
+    /// The git index
+    Git,
+}
+
+/// Where the decision of which protocol to use for crates.io came from, see
+/// [`crates_io_protocol`]
+#[derive(Clone, Debug)]
+pub enum ProtocolProvenance {
+    /// crates.io has been [replaced](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
+    /// by another registry, so its protocol is irrelevant
+    SourceReplacement(IndexUrl<'static>),
+    /// The protocol was set by a [`ConfigOverrides`]
+    ConfigOverride,
+    /// The protocol was set by `CARGO_REGISTRIES_CRATES_IO_PROTOCOL`
+    EnvVar,
+    /// The protocol was set by [`registries.crates-io.protocol`](https://doc.rust-lang.org/cargo/reference/config.html#registriescrates-ioprotocol)
+    /// in the specified config file
+    ConfigFile(PathBuf),
+    /// The protocol was not configured, so the default for the version of
+    /// cargo was used, sparse being the default as of 1.70.0
+    CargoVersionDefault(semver::Version),
+}
+
+/// The protocol to use for crates.io, and why, see [`crates_io_protocol`]
+#[derive(Clone, Debug)]
+pub struct ProtocolDecision {
+    /// The protocol that was chosen, never [`Protocol::Auto`]
+    ///
+    /// For source replacements, this is [`Protocol::Sparse`] if the replacement
+    /// is a sparse registry, otherwise [`Protocol::Git`]
+    pub protocol: Protocol,
+    /// Where the decision came from
+    pub provenance: ProtocolProvenance,
+}
+
+impl ProtocolDecision {
+    /// Gets the [`IndexUrl`] that was decided upon
+    pub fn into_url(self) -> IndexUrl<'static> {
+        match self.provenance {
+            ProtocolProvenance::SourceReplacement(url) => url,
+            _ if self.protocol == Protocol::Sparse => IndexUrl::CratesIoSparse,
+            _ => IndexUrl::CratesIoGit,
+        }
+    }
+}
+
+/// Determines the protocol to use for crates.io, depending on the local
+/// environment, as well as the reason for the decision
+///
+/// 1. Determines if the crates.io registry has been [replaced](https://doc.rust-lang.org/cargo/reference/source-replacement.html)
+/// 2. Determines if the protocol was explicitly set via `CARGO_REGISTRIES_CRATES_IO_PROTOCOL`,
+///    or a [`ConfigOverrides`] when using [`IndexUrl::crates_io_with_overrides`]
+/// 3. Determines if the protocol was explicitly [configured](https://doc.rust-lang.org/cargo/reference/config.html#registriescrates-ioprotocol) by the user
+/// 4. Otherwise, uses the version of cargo, either the one specified, or the
+///    one detected in the config root (see [`crate::utils::cargo_version`]), to
+///    determine the appropriate default
+///
+/// This is what [`IndexUrl::crates_io`] uses
+pub fn crates_io_protocol(
+    config_root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    cargo_version: Option<&str>,
+) -> Result<ProtocolDecision, Error> {
+    crates_io_protocol_with(
+        std::env::var("CARGO_REGISTRIES_CRATES_IO_PROTOCOL").ok(),
+        config_root,
+        cargo_home,
+        cargo_version,
+        &ConfigOverrides::default(),
+    )
+}
+
+fn crates_io_protocol_with(
+    env: Option<String>,
+    config_root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    cargo_version: Option<&str>,
+    overrides: &ConfigOverrides,
+) -> Result<ProtocolDecision, Error> {
+    let decision = |protocol, provenance| ProtocolDecision {
+        protocol,
+        provenance,
+    };
+
+    // If the crates.io registry has been replaced it doesn't matter what
+    // the protocol for it has been changed to
+    if let Some(replacement) =
+        get_source_replacement(config_root.clone(), cargo_home, overrides, "crates-io")?
+    {
+        let protocol = if replacement.is_sparse() {
+            Protocol::Sparse
+        } else {
+            Protocol::Git
+        };
+        return Ok(decision(
+            protocol,
+            ProtocolProvenance::SourceReplacement(replacement),
+        ));
+    }
+
+    let parse = |protocol: &str| match protocol {
+        "sparse" => Some(Protocol::Sparse),
+        "git" => Some(Protocol::Git),
+        _ => None,
+    };
+
+    const POINTER: &str = "/registries/crates-io/protocol";
+
+    if let Some(protocol) = overrides.get(POINTER, |value| parse(value.as_str()?)) {
+        return Ok(decision(protocol, ProtocolProvenance::ConfigOverride));
+    }
+
+    if let Some(protocol) = env.as_deref().and_then(parse) {
+        return Ok(decision(protocol, ProtocolProvenance::EnvVar));
+    }
+
+    let protocol = read_cargo_config(
+        config_root.clone(),
+        cargo_home,
+        &overrides.without_values(),
+        |config| parse(config.pointer(POINTER)?.as_str()?),
+    )?;
+
+    if let Some(protocol) = protocol {
+        // The merged config doesn't track where each value came from, so find
+        // the config with the highest precedence that sets it
+        if let Some(path) = config_value_origin(
+            config_root.clone(),
+            cargo_home,
+            &overrides.search_stop,
+            POINTER,
+        )? {
+            return Ok(decision(protocol, ProtocolProvenance::ConfigFile(path)));
+        }
+    }
+
+    let vers = match cargo_version {
+        Some(v) => v.trim().parse()?,
+        // The version of cargo can differ per project
+        None => crate::utils::cargo_version(config_root.as_deref())?,
+    };
+
+    let protocol = if vers >= semver::Version::new(1, 70, 0) {
+        Protocol::Sparse
+    } else {
+        Protocol::Git
+    };
+
+    Ok(decision(
+        protocol,
+        ProtocolProvenance::CargoVersionDefault(vers),
+    ))
+}
+
+/// Config values that take precedence over both config files and environment
+/// variables, the same as cargo's [`--config KEY=VALUE`](https://doc.rust-lang.org/cargo/reference/config.html#command-line-overrides)
+#[derive(Clone, Debug, Default)]
+pub struct ConfigOverrides {
+    values: Vec<String>,
+    search_stop: ConfigSearchStop,
+}
+
+impl ConfigOverrides {
+    /// Parses each `KEY=VALUE` override, later overrides taking precedence
+    /// over earlier ones for the same key
+    pub fn parse<I>(overrides: I) -> Result<Self, Error>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut co = Self::default();
+        for kv in overrides {
+            co.push(kv.as_ref())?;
+        }
+        Ok(co)
+    }
+
+    /// Adds a `KEY=VALUE` override
+    ///
+    /// The same as cargo, the override must be a TOML dotted key expression,
+    /// eg. `registries.my-registry.index = "sparse+https://my-registry.com/"`.
+    /// Unlike cargo, paths to additional config files are not supported.
+    pub fn push(&mut self, kv: &str) -> Result<&mut Self, Error> {
+        let toml = toml_span::parse(kv).map_err(Box::new)?;
+
+        // There must be exactly one key at each level, down to a single value
+        let mut value = &toml;
+        while let Some(table) = value.as_table() {
+            let mut entries = table.values();
+            match (entries.next(), entries.next()) {
+                (Some(child), None) => value = child,
+                _ => return Err(Error::InvalidConfigOverride(kv.to_owned())),
+            }
+        }
+
+        self.values.push(kv.to_owned());
+        Ok(self)
+    }
+
+    /// Sets where the search for configs in the ancestors of the config root
+    /// stops, by default every ancestor is searched, the same as cargo
+    ///
+    /// Searching every ancestor can be undesirable if the config root is deep
+    /// in eg. a network mounted home directory, in which case
+    /// [`ConfigSearchStop::Home`] avoids probing directories the user likely
+    /// doesn't control
+    pub fn search_stop(&mut self, stop: ConfigSearchStop) -> &mut Self {
+        self.search_stop = stop;
+        self
+    }
+
+    /// Returns true if there are no overrides
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Gets a copy without any override values, but the same search stop
+    fn without_values(&self) -> Self {
+        Self {
+            values: Vec::new(),
+            search_stop: self.search_stop.clone(),
+        }
+    }
+
+    /// Merges the overrides on top of the config
+    fn apply<'de>(&'de self, config: &mut toml_span::value::Value<'de>) {
+        for kv in &self.values {
+            // The overrides are validated when added
+            if let Ok(toml) = toml_span::parse(kv) {
+                merge_value(config, toml);
+            }
+        }
+    }
+
+    /// Gets the value at the pointer from the overrides alone
+    fn get<T>(
+        &self,
+        pointer: &str,
+        f: impl Fn(&toml_span::value::Value<'_>) -> Option<T>,
+    ) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let mut config =
+            toml_span::value::Value::new(toml_span::value::ValueInner::Table(Default::default()));
+        self.apply(&mut config);
+        f(config.pointer(pointer)?)
+    }
+}
+
 /// The local disk location to place an index
-#[derive(Default)]
+#[derive(Default, Debug, Clone)]
 pub enum IndexPath {
     /// The default cargo home root path
     #[default]
     CargoHome,
-    /// User-specified root path
+    /// User-specified root path, in the same layout as a cargo home, ie. the
+    /// index is placed in `<root>/registry/index/<url hash>`
     UserSpecified(PathBuf),
     /// An exact path on disk where an index is located.
     ///
-    /// Unlike the other two variants, this variant won't take the index's url
+    /// Unlike the other variants, this variant won't take the index's url
     /// into account to calculate the unique url hash as part of the full path
     Exact(PathBuf),
+    /// A root path where the index is placed directly in the unique directory
+    /// for its url, ie. `<root>/<url hash>`
+    ///
+    /// This is useful for application-owned caches of multiple indices that
+    /// don't need a full cargo home layout
+    Hashed(PathBuf),
 }
 
 impl From<Option<PathBuf>> for IndexPath {
@@ -198,7 +767,7 @@ impl From<Option<PathBuf>> for IndexPath {
 
 /// Helper for constructing an index location, consisting of the remote url for
 /// the index and the local location on disk
-#[derive(Default)]
+#[derive(Default, Debug, Clone)]
 pub struct IndexLocation<'il> {
     /// The remote url of the registry index
     pub url: IndexUrl<'il>,
@@ -225,18 +794,31 @@ impl<'il> IndexLocation<'il> {
         self
     }
 
+    /// Converts this into an [`IndexLocation`] that owns its data, see
+    /// [`IndexUrl::into_owned`]
+    #[inline]
+    pub fn into_owned(self) -> IndexLocation<'static> {
+        IndexLocation {
+            url: self.url.into_owned(),
+            root: self.root,
+        }
+    }
+
     /// Obtains the full local disk path and URL of this index location
     pub fn into_parts(self) -> Result<(PathBuf, String), Error> {
         let url = self.url.as_str();
 
-        let root = match self.root {
-            IndexPath::CargoHome => crate::utils::cargo_home()?,
-            IndexPath::UserSpecified(root) => root,
+        let (path, mut url) = match self.root {
+            IndexPath::CargoHome => crate::utils::get_index_details(url, None)?,
+            IndexPath::UserSpecified(root) => crate::utils::get_index_details(url, Some(root))?,
             IndexPath::Exact(path) => return Ok((path, url.to_owned())),
+            IndexPath::Hashed(mut path) => {
+                let url_dir = crate::utils::url_to_local_dir(url)?;
+                path.push(url_dir.dir_name);
+                (path, url_dir.canonical)
+            }
         };
 
-        let (path, mut url) = crate::utils::get_index_details(url, Some(root))?;
-
         if !url.ends_with('/') {
             url.push('/');
         }
@@ -245,88 +827,668 @@ impl<'il> IndexLocation<'il> {
     }
 }
 
-/// Calls the specified function for each cargo config located according to
-/// cargo's standard hierarchical structure
+/// The maximum number of directories that are searched for cargo configs,
+/// starting at the config root, in case of eg. symlink loops
+const MAX_CONFIG_ANCESTORS: usize = 64;
+
+/// Where the search for cargo configs in the ancestors of the config root
+/// stops, see [`ConfigOverrides::search_stop`]
+///
+/// The directory the search stops at is still searched, and if the config
+/// root is not a descendant of it, the search continues up to the filesystem
+/// root.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum ConfigSearchStop {
+    /// Searches every ancestor up to the filesystem root, the same as cargo
+    #[default]
+    FilesystemRoot,
+    /// Stops at the user's home directory, or the directory containing the
+    /// cargo home, whichever is reached first
+    Home,
+    /// Stops at the specified directory
+    Dir(PathBuf),
+}
+
+/// Gets the paths of the cargo configs that exist, in the order of precedence
+/// defined by cargo's standard hierarchical structure
+///
+/// Both the legacy `config` and `config.toml` file names are supported. If
+/// both exist in the same directory, cargo uses the legacy `config`, so we do
+/// the same.
 ///
-/// Note that this only supports the use of `.cargo/config.toml`, which is not
-/// supported below cargo 1.39.0
+/// The config in the cargo home is only added at the end if it was not
+/// already found while searching the ancestors of the root, in which case it
+/// keeps the precedence of that location, same as cargo.
 ///
 /// See <https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure>
-pub(crate) fn read_cargo_config<T>(
+pub(crate) fn cargo_config_paths(
     root: Option<PathBuf>,
     cargo_home: Option<&Path>,
-    callback: impl Fn(&toml_span::value::Value<'_>) -> Option<T>,
-) -> Result<Option<T>, Error> {
+    stop: &ConfigSearchStop,
+) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    let cargo_home = cargo_home
+        .map(Cow::Borrowed)
+        .or_else(|| crate::utils::cargo_home().ok().map(Cow::Owned));
+
+    let stop_dirs: Vec<Cow<'_, Path>> = match stop {
+        ConfigSearchStop::FilesystemRoot => Vec::new(),
+        ConfigSearchStop::Home => home::home_dir()
+            .and_then(|hd| crate::utils::to_utf8_path_buf(hd).ok())
+            .map(Cow::Owned)
+            .into_iter()
+            .chain(
+                cargo_home
+                    .as_deref()
+                    .and_then(|ch| ch.parent())
+                    .map(Cow::Borrowed),
+            )
+            .collect(),
+        ConfigSearchStop::Dir(dir) => vec![Cow::Borrowed(dir.as_path())],
+    };
+
     if let Some(mut path) = root.or_else(|| {
         std::env::current_dir()
             .ok()
-            .and_then(|pb| PathBuf::from_path_buf(pb).ok())
+            .and_then(|pb| crate::utils::to_utf8_path_buf(pb).ok())
     }) {
-        loop {
-            path.push(".cargo/config.toml");
-            if path.exists() {
-                let contents = match std::fs::read_to_string(&path) {
-                    Ok(c) => c,
-                    Err(err) => return Err(Error::IoPath(err, path)),
-                };
-
-                let toml = toml_span::parse(&contents).map_err(Box::new)?;
-                if let Some(value) = callback(&toml) {
-                    return Ok(Some(value));
-                }
-            }
-            path.pop();
+        for _ in 0..MAX_CONFIG_ANCESTORS {
+            path.push(".cargo");
+            paths.extend(config_in_dir(&path));
             path.pop();
 
-            // Walk up to the next potential config root
-            if !path.pop() {
+            // Walk up to the next potential config root, unless this is where
+            // the search is meant to stop
+            if stop_dirs.iter().any(|sd| sd.as_ref() == path.as_path()) || !path.pop() {
                 break;
             }
         }
     }
 
-    if let Some(home) = cargo_home
-        .map(Cow::Borrowed)
-        .or_else(|| crate::utils::cargo_home().ok().map(Cow::Owned))
-    {
-        let path = home.join("config.toml");
-        if path.exists() {
-            let fc = std::fs::read_to_string(&path)?;
-            let toml = toml_span::parse(&fc).map_err(Box::new)?;
-            if let Some(value) = callback(&toml) {
-                return Ok(Some(value));
+    if let Some(home) = cargo_home {
+        if let Some(config) = config_in_dir(&home) {
+            if !paths.contains(&config) {
+                paths.push(config);
             }
         }
     }
 
-    Ok(None)
+    paths
 }
 
-/// Gets the url of a replacement registry for the specified registry if one has been configured
-///
-/// See <https://doc.rust-lang.org/cargo/reference/source-replacement.html>
+/// Gets the path of the cargo config in the specified directory, if it exists
 #[inline]
-pub(crate) fn get_source_replacement<'iu>(
-    root: Option<PathBuf>,
-    cargo_home: Option<&Path>,
-    registry_name: &str,
-) -> Result<Option<IndexUrl<'iu>>, Error> {
-    read_cargo_config(root, cargo_home, |config| {
-        let path = format!("/source/{registry_name}/replace-with");
-        let repw = config.pointer(&path)?.as_str()?;
-        let sources = config.pointer("/source")?.as_table()?;
-        let replace_src = sources.get(repw)?.as_table()?;
-
-        if let Some(rr) = replace_src.get("registry") {
-            rr.as_str()
-                .map(|r| IndexUrl::NonCratesIo(r.to_owned().into()))
-        } else if let Some(rlr) = replace_src.get("local-registry") {
-            rlr.as_str()
-                .map(|l| IndexUrl::Local(PathBuf::from(l).into()))
+fn config_in_dir(dir: &Path) -> Option<PathBuf> {
+    ["config", "config.toml"]
+        .into_iter()
+        .map(|name| dir.join(name))
+        .find(|path| path.is_file())
+}
+
+/// Merges every cargo config located according to cargo's standard
+/// hierarchical structure, see [`cargo_config_paths`], and calls the specified
+/// function with the merged config
+///
+/// Configs closer to the root take precedence over those further up the
+/// hierarchy, with the config in the cargo home having the lowest precedence.
+/// Each config is also merged with the files it [includes](https://doc.rust-lang.org/cargo/reference/unstable.html#config-include).
+/// `CARGO_*` environment variables take precedence over all config files,
+/// this applies to every key set in a config file, as well as the documented
+/// keys that are only set in the environment, see [`apply_env_overrides`].
+/// The overrides take precedence over everything.
+///
+/// See <https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure>
+pub(crate) fn read_cargo_config<T>(
+    root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    overrides: &ConfigOverrides,
+    callback: impl Fn(&toml_span::value::Value<'_>) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    read_cargo_config_with(root, cargo_home, &config_env(), overrides, callback)
+}
+
+/// Gets the `CARGO_` environment variables that can override config values
+pub(crate) fn config_env() -> Vec<(String, String)> {
+    std::env::vars_os()
+        .filter_map(|(key, value)| {
+            let key = key.into_string().ok()?;
+            if !key.starts_with("CARGO_") {
+                return None;
+            }
+
+            Some((key, value.into_string().ok()?))
+        })
+        .collect()
+}
+
+/// Reads the merged cargo config, with the specified environment variables
+/// overriding the values in the config files, see [`read_cargo_config`]
+pub(crate) fn read_cargo_config_with<T>(
+    root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    env: &[(String, String)],
+    overrides: &ConfigOverrides,
+    callback: impl Fn(&toml_span::value::Value<'_>) -> Option<T>,
+) -> Result<Option<T>, Error> {
+    let files = load_configs(root, cargo_home, &overrides.search_stop)?;
+    let mut toml = merge_configs(&files)?;
+    apply_env_overrides(&mut toml, env);
+    overrides.apply(&mut toml);
+    Ok(callback(&toml))
+}
+
+/// Loads every config, including the configs they include, in the order they
+/// are merged
+fn load_configs(
+    root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    stop: &ConfigSearchStop,
+) -> Result<Vec<(PathBuf, String)>, Error> {
+    let mut files = Vec::new();
+    let mut visited = Vec::new();
+
+    // Load the lowest precedence configs first so that closer configs are
+    // merged on top of them
+    for path in cargo_config_paths(root, cargo_home, stop).into_iter().rev() {
+        load_config(path, &mut files, &mut visited)?;
+    }
+
+    Ok(files)
+}
+
+/// Gets the path of the config with the highest precedence that sets the
+/// value at the specified pointer
+fn config_value_origin(
+    root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    stop: &ConfigSearchStop,
+    pointer: &str,
+) -> Result<Option<PathBuf>, Error> {
+    for (path, contents) in load_configs(root, cargo_home, stop)?.into_iter().rev() {
+        let toml = toml_span::parse(&contents).map_err(Box::new)?;
+        if toml.pointer(pointer).is_some() {
+            return Ok(Some(path));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Config tables whose entries have user chosen names, eg. a registry name,
+/// along with the keys cargo documents for those entries
+const NAMED_TABLES: &[(&str, &[&str])] = &[
+    (
+        "registries",
+        &["index", "token", "credential-provider", "protocol"],
+    ),
+    (
+        "source",
+        &[
+            "replace-with",
+            "registry",
+            "local-registry",
+            "directory",
+            "git",
+            "branch",
+            "tag",
+            "rev",
+        ],
+    ),
+];
+
+/// The documented config keys, outside of [`NAMED_TABLES`], that are set from
+/// the environment even if they aren't set in any config file
+const ENV_KEYS: &[&str] = &[
+    "registry.default",
+    "registry.token",
+    "registry.credential-provider",
+    "net.offline",
+    "net.retry",
+    "net.git-fetch-with-cli",
+    "http.debug",
+    "http.proxy",
+    "http.cainfo",
+    "http.check-revoke",
+    "http.timeout",
+    "http.low-speed-limit",
+    "http.ssl-version",
+    "http.multiplexing",
+    "http.user-agent",
+];
+
+/// Overrides the values in the merged config with the values from the
+/// [environment](https://doc.rust-lang.org/cargo/reference/config.html#environment-variables),
+/// which take precedence over config files
+///
+/// The same as cargo, every key set in a config file can be overridden by
+/// its `CARGO_*` environment variable. Keys that aren't set in any config file
+/// can only be discovered if they are documented, ie. one of [`ENV_KEYS`], or
+/// an entry in one of the [`NAMED_TABLES`].
+fn apply_env_overrides(config: &mut toml_span::value::Value<'_>, env: &[(String, String)]) {
+    let get = |var: &str| {
+        env.iter()
+            .find_map(|(k, v)| (k == var).then_some(v.as_str()))
+    };
+
+    let mut keys = Vec::new();
+    collect_keys(config, &mut Vec::new(), &mut keys);
+
+    keys.extend(
+        ENV_KEYS
+            .iter()
+            .map(|key| key.split('.').map(String::from).collect()),
+    );
+
+    for (table, fields) in NAMED_TABLES {
+        for name in named_entries(config, table, fields, env) {
+            keys.extend(
+                fields
+                    .iter()
+                    .map(|field| vec![(*table).to_owned(), name.clone(), (*field).to_owned()]),
+            );
+        }
+    }
+
+    keys.sort();
+    keys.dedup();
+
+    for key in &keys {
+        let key: Vec<_> = key.iter().map(String::as_str).collect();
+        if let Some(value) = get(&config_env_var(&key)) {
+            set_config_value(config, &key, value);
+        }
+    }
+}
+
+/// Collects the keys of every non-table value in the config
+fn collect_keys(
+    value: &toml_span::value::Value<'_>,
+    key: &mut Vec<String>,
+    keys: &mut Vec<Vec<String>>,
+) {
+    let Some(table) = value.as_table() else {
+        if !key.is_empty() {
+            keys.push(key.clone());
+        }
+        return;
+    };
+
+    for (name, child) in table {
+        key.push(name.name.to_string());
+        collect_keys(child, key, keys);
+        key.pop();
+    }
+}
+
+/// Gets the names of the entries in a [`NAMED_TABLES`] table, including the
+/// entries that are only specified in the environment
+fn named_entries(
+    config: &toml_span::value::Value<'_>,
+    table: &str,
+    fields: &[&str],
+    env: &[(String, String)],
+) -> Vec<String> {
+    let mut names: Vec<String> = config
+        .pointer(&format!("/{table}"))
+        .and_then(|t| t.as_table())
+        .map(|t| t.keys().map(|k| k.name.to_string()).collect())
+        .unwrap_or_default();
+
+    let mangled = |name: &str| {
+        let mut mangled = String::new();
+        mangle_env_part(name, &mut mangled);
+        mangled
+    };
+
+    // Entries that are only specified in the environment can only be
+    // discovered from the variable names, but since both `-` and `_` are
+    // mangled to `_`, we assume `-` is used, eg. `crates-io`
+    let prefix = format!("{}_", config_env_var(&[table]));
+    for field in fields {
+        let suffix = format!("_{}", mangled(field));
+
+        for (var, _) in env {
+            let Some(name) = var
+                .strip_prefix(&prefix)
+                .and_then(|rest| rest.strip_suffix(&suffix))
+                .filter(|name| !name.is_empty())
+            else {
+                continue;
+            };
+
+            let name = name.to_lowercase().replace('_', "-");
+            if !names.iter().any(|n| mangled(n) == mangled(&name)) {
+                names.push(name);
+            }
+        }
+    }
+
+    names
+}
+
+/// Sets the value for the key in the config, creating tables as needed
+///
+/// Environment variables are always strings, so they are converted to the
+/// type of the value they are replacing, or the type cargo expects for the key
+fn set_config_value(config: &mut toml_span::value::Value<'_>, key: &[&str], value: &str) {
+    use toml_span::value::ValueInner;
+
+    let existing = config.pointer(&format!("/{}", key.join("/")));
+    let is_bool = existing.map_or_else(
+        || {
+            matches!(
+                key.last(),
+                Some(
+                    &("offline" | "multiplexing" | "check-revoke" | "git-fetch-with-cli" | "debug")
+                )
+            )
+        },
+        |e| e.as_bool().is_some(),
+    );
+    let is_integer = existing.map_or_else(
+        || matches!(key.last(), Some(&("timeout" | "retry" | "low-speed-limit"))),
+        |e| e.as_integer().is_some(),
+    );
+
+    let value = match (is_bool, is_integer) {
+        (true, _) => value.parse().map(ValueInner::Boolean).ok(),
+        (_, true) => value.parse().map(ValueInner::Integer).ok(),
+        _ => None,
+    }
+    .unwrap_or_else(|| ValueInner::String(value.to_owned().into()));
+
+    insert_value(config, key, value);
+}
+
+/// Inserts the value at the key, creating tables as needed
+fn insert_value<'de>(
+    config: &mut toml_span::value::Value<'de>,
+    key: &[&str],
+    value: toml_span::value::ValueInner<'de>,
+) {
+    use toml_span::value::{Key, Value, ValueInner};
+
+    let Some((first, rest)) = key.split_first() else {
+        return;
+    };
+
+    with_table_mut(config, |table| {
+        let key = || Key {
+            name: (*first).to_owned().into(),
+            span: Default::default(),
+        };
+
+        if rest.is_empty() {
+            table.insert(key(), Value::new(value));
+            return;
+        }
+
+        if !table.contains_key(*first) {
+            table.insert(key(), Value::new(ValueInner::Table(Default::default())));
+        }
+
+        if let Some(child) = table.get_mut(*first) {
+            insert_value(child, rest, value);
+        }
+    });
+}
+
+/// Reads the specified config, and recursively the files it includes, in the
+/// order they need to be merged, ie. lowest precedence first
+///
+/// Includes are resolved relative to the directory of the including file, and
+/// have lower precedence than it, with later includes having higher precedence
+/// than earlier ones. Files that have already been visited are skipped so that
+/// include cycles are not followed.
+fn load_config(
+    path: PathBuf,
+    files: &mut Vec<(PathBuf, String)>,
+    visited: &mut Vec<std::path::PathBuf>,
+) -> Result<(), Error> {
+    // Includes can contain relative components, so compare canonical paths
+    let canonical = path
+        .canonicalize()
+        .unwrap_or_else(|_| path.clone().into_std_path_buf());
+    if visited.contains(&canonical) {
+        return Ok(());
+    }
+    visited.push(canonical);
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(c) => c,
+        Err(err) => return Err(Error::IoPath(err, path)),
+    };
+
+    let includes: Vec<PathBuf> = {
+        let toml = toml_span::parse(&contents).map_err(Box::new)?;
+        let dir = path.parent().unwrap_or(Path::new(""));
+
+        let include_path = |inc: &toml_span::value::Value<'_>| {
+            inc.as_str()
+                .or_else(|| inc.as_table()?.get("path")?.as_str())
+                .map(|inc| dir.join(inc))
+        };
+
+        match toml.pointer("/include") {
+            Some(inc) => {
+                if let Some(incs) = inc.as_array() {
+                    incs.iter().filter_map(include_path).collect()
+                } else {
+                    include_path(inc).into_iter().collect()
+                }
+            }
+            None => Vec::new(),
+        }
+    };
+
+    for include in includes {
+        load_config(include, files, visited)?;
+    }
+
+    files.push((path, contents));
+    Ok(())
+}
+
+/// Parses and merges the specified configs, later configs taking precedence
+/// over earlier ones
+fn merge_configs(files: &[(PathBuf, String)]) -> Result<toml_span::value::Value<'_>, Error> {
+    let mut merged: Option<toml_span::value::Value<'_>> = None;
+
+    for (path, contents) in files {
+        let mut toml = toml_span::parse(contents).map_err(Box::new)?;
+
+        // Relative paths are relative to the parent of the directory the config
+        // is located in, eg. the directory containing `.cargo/`
+        if let Some(config_root) = path.parent().and_then(|p| p.parent()) {
+            resolve_relative_paths(&mut toml, config_root);
+        }
+
+        if let Some(merged) = &mut merged {
+            merge_value(merged, toml);
         } else {
-            None
+            merged = Some(toml);
         }
-    })
+    }
+
+    Ok(merged.unwrap_or_else(|| {
+        toml_span::value::Value::new(toml_span::value::ValueInner::Table(Default::default()))
+    }))
+}
+
+/// Makes the relative `directory` and `local-registry` paths of sources, as
+/// well as `http.cainfo`, absolute, as they are relative to the config they
+/// are specified in, which is lost once configs are merged
+fn resolve_relative_paths(config: &mut toml_span::value::Value<'_>, config_root: &Path) {
+    with_table_mut(config, |config| {
+        if let Some(sources) = config.get_mut("source") {
+            with_table_mut(sources, |sources| {
+                for source in sources.values_mut() {
+                    with_table_mut(source, |source| {
+                        for key in ["directory", "local-registry"] {
+                            if let Some(value) = source.get_mut(key) {
+                                make_absolute(value, config_root);
+                            }
+                        }
+                    });
+                }
+            });
+        }
+
+        if let Some(http) = config.get_mut("http") {
+            with_table_mut(http, |http| {
+                if let Some(cainfo) = http.get_mut("cainfo") {
+                    make_absolute(cainfo, config_root);
+                }
+            });
+        }
+    });
+}
+
+/// Joins the path in the value to the root if it is relative
+fn make_absolute(value: &mut toml_span::value::Value<'_>, config_root: &Path) {
+    let Some(path) = value.as_str().filter(|p| Path::new(p).is_relative()) else {
+        return;
+    };
+
+    let path = config_root.join(path);
+    value.set(toml_span::value::ValueInner::String(
+        path.into_string().into(),
+    ));
+}
+
+/// Calls the function with the table in the value, if it is a table
+fn with_table_mut<'de>(
+    value: &mut toml_span::value::Value<'de>,
+    f: impl FnOnce(&mut toml_span::value::Table<'de>),
+) {
+    use toml_span::value::ValueInner;
+
+    match value.take() {
+        ValueInner::Table(mut table) => {
+            f(&mut table);
+            value.set(ValueInner::Table(table));
+        }
+        other => value.set(other),
+    }
+}
+
+/// Merges `src` into `dst`, following cargo's rules of tables being merged
+/// recursively, arrays being joined with the values from `src` placed last,
+/// and all other values in `src` replacing those in `dst`
+///
+/// See <https://doc.rust-lang.org/cargo/reference/config.html#hierarchical-structure>
+fn merge_value<'de>(dst: &mut toml_span::value::Value<'de>, mut src: toml_span::value::Value<'de>) {
+    use toml_span::value::ValueInner;
+
+    match (dst.take(), src.take()) {
+        (ValueInner::Table(mut dst_table), ValueInner::Table(src_table)) => {
+            for (key, value) in src_table {
+                if let Some(existing) = dst_table.get_mut(&key) {
+                    merge_value(existing, value);
+                } else {
+                    dst_table.insert(key, value);
+                }
+            }
+
+            dst.set(ValueInner::Table(dst_table));
+        }
+        (ValueInner::Array(mut dst_array), ValueInner::Array(src_array)) => {
+            dst_array.extend(src_array);
+            dst.set(ValueInner::Array(dst_array));
+        }
+        (_, src) => dst.set(src),
+    }
+}
+
+/// The maximum number of `replace-with` hops followed when resolving a source
+/// replacement
+const MAX_REPLACEMENTS: usize = 16;
+
+/// Gets the url of a replacement registry for the specified registry if one has been configured
+///
+/// Chained replacements, where a replacement source is itself replaced, are
+/// followed until a `registry`, `local-registry`, or `directory` source is
+/// reached. A replacement can also name a registry in the `[registries]`
+/// table, which is only used if there isn't a source of the same name.
+///
+/// See <https://doc.rust-lang.org/cargo/reference/source-replacement.html>
+#[inline]
+pub(crate) fn get_source_replacement<'iu>(
+    root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+    overrides: &ConfigOverrides,
+    registry_name: &str,
+) -> Result<Option<IndexUrl<'iu>>, Error> {
+    read_cargo_config(root, cargo_home, overrides, |config| {
+        let sources = config.pointer("/source")?.as_table()?;
+        let mut repw = sources
+            .get(registry_name)?
+            .as_table()?
+            .get("replace-with")?
+            .as_str()?;
+
+        let mut chain = vec![registry_name.to_owned()];
+        let fail = |mut chain: Vec<String>, name: &str, issue| {
+            chain.push(name.to_owned());
+            Some(Err(crate::error::SourceReplacementError {
+                chain,
+                source: issue,
+            }))
+        };
+
+        loop {
+            if chain.iter().any(|name| name == repw) {
+                return fail(chain, repw, SourceReplacementIssue::Cycle);
+            }
+            if chain.len() > MAX_REPLACEMENTS {
+                return fail(
+                    chain,
+                    repw,
+                    SourceReplacementIssue::TooLong(MAX_REPLACEMENTS),
+                );
+            }
+
+            let Some(replace_src) = sources.get(repw).and_then(|rs| rs.as_table()) else {
+                // Cargo also allows replacing a source with a registry, but
+                // only if there isn't a source with the same name
+                let index = config
+                    .pointer(&format!("/registries/{repw}/index"))
+                    .and_then(|index| index.as_str());
+
+                return match index {
+                    Some(index) => Some(Ok(IndexUrl::NonCratesIo(index.to_owned().into()))),
+                    None => fail(chain, repw, SourceReplacementIssue::Undefined),
+                };
+            };
+
+            if let Some(next) = replace_src.get("replace-with").and_then(|rw| rw.as_str()) {
+                chain.push(repw.to_owned());
+                repw = next;
+                continue;
+            }
+
+            let url = if let Some(rr) = replace_src.get("registry") {
+                rr.as_str()
+                    .map(|r| IndexUrl::NonCratesIo(r.to_owned().into()))
+            } else if let Some(rlr) = replace_src.get("local-registry") {
+                rlr.as_str()
+                    .map(|l| IndexUrl::Local(PathBuf::from(l).into()))
+            } else if let Some(rd) = replace_src.get("directory") {
+                rd.as_str()
+                    .map(|d| IndexUrl::Directory(PathBuf::from(d).into()))
+            } else {
+                None
+            };
+
+            return match url {
+                Some(url) => Some(Ok(url)),
+                None => fail(chain, repw, SourceReplacementIssue::UnknownKind),
+            };
+        }
+    })?
+    .transpose()
+    .map_err(Error::from)
 }
 
 #[cfg(test)]
@@ -351,7 +1513,7 @@ mod test {
         assert!(std::env::var_os("CARGO_REGISTRIES_CRATES_IO_PROTOCOL").is_none());
 
         let td = tempfile::tempdir().unwrap();
-        let root = crate::PathBuf::from_path_buf(td.path().to_owned()).unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
         let cfg_toml = td.path().join(".cargo/config.toml");
 
         std::fs::create_dir_all(cfg_toml.parent().unwrap()).unwrap();
@@ -387,12 +1549,955 @@ protocol = "git"
         }
     }
 
+    /// Verifies legacy `.cargo/config` files without the `.toml` extension are
+    /// read, and take precedence over `.cargo/config.toml`, just as in cargo
+    #[test]
+    fn parses_legacy_config() {
+        assert!(std::env::var_os("CARGO_REGISTRIES_CRATES_IO_PROTOCOL").is_none());
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        let project = root.join("project");
+
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::create_dir_all(project.join(".cargo")).unwrap();
+
+        const GIT: &str = "[registries.crates-io]\nprotocol = 'git'\n";
+        const SPARSE: &str = "[registries.crates-io]\nprotocol = 'sparse'\n";
+
+        let crates_io = || {
+            super::IndexUrl::crates_io(Some(project.clone()), Some(&home), Some("1.70.0")).unwrap()
+        };
+
+        // Only the legacy name in the cargo home
+        std::fs::write(home.join("config"), GIT).unwrap();
+        assert!(!crates_io().is_sparse());
+        assert_eq!(
+            super::cargo_config_paths(Some(project.clone()), Some(&home), &Default::default())
+                .last(),
+            Some(&home.join("config"))
+        );
+
+        // Only the legacy name in the project
+        std::fs::write(home.join("config"), SPARSE).unwrap();
+        std::fs::write(project.join(".cargo/config"), GIT).unwrap();
+        assert!(!crates_io().is_sparse());
+
+        // Both names in the project, the legacy name wins
+        std::fs::write(project.join(".cargo/config.toml"), SPARSE).unwrap();
+        assert!(!crates_io().is_sparse());
+        assert_eq!(
+            super::cargo_config_paths(Some(project.clone()), Some(&home), &Default::default())[0],
+            project.join(".cargo/config")
+        );
+
+        std::fs::remove_file(project.join(".cargo/config")).unwrap();
+        std::fs::write(project.join(".cargo/config.toml"), GIT).unwrap();
+        assert!(!crates_io().is_sparse());
+    }
+
+    /// Verifies files included by a config are merged with it, with the
+    /// including file taking precedence
+    #[test]
+    fn follows_includes() {
+        assert!(std::env::var_os("CARGO_REGISTRIES_CRATES_IO_PROTOCOL").is_none());
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        let cargo_dir = root.join("project/.cargo");
+
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::create_dir_all(cargo_dir.join("shared")).unwrap();
+
+        std::fs::write(
+            cargo_dir.join("config.toml"),
+            "include = ['shared/replace.toml']\n[registries.crates-io]\nprotocol = 'git'\n",
+        )
+        .unwrap();
+        // Includes are relative to the including file, and may form cycles
+        std::fs::write(
+            cargo_dir.join("shared/replace.toml"),
+            "include = '../config.toml'\n[registries.crates-io]\nprotocol = 'sparse'\n[source.crates-io]\nreplace-with = 'mirror'\n[source.mirror]\nregistry = 'sparse+https://included-mirror.com'\n",
+        )
+        .unwrap();
+
+        let project = Some(root.join("project"));
+
+        let iurl = super::IndexUrl::crates_io(project.clone(), Some(&home), None).unwrap();
+        assert_eq!(iurl.as_str(), "sparse+https://included-mirror.com");
+
+        // The including file takes precedence over the included file
+        let protocol =
+            super::read_cargo_config(project, Some(&home), &Default::default(), |config| {
+                config
+                    .pointer("/registries/crates-io/protocol")?
+                    .as_str()
+                    .map(String::from)
+            })
+            .unwrap();
+        assert_eq!(protocol.as_deref(), Some("git"));
+    }
+
+    /// Verifies configs across the hierarchy are merged, rather than the first
+    /// config containing a value being used
+    #[test]
+    fn merges_hierarchy() {
+        assert!(std::env::var_os("CARGO_REGISTRIES_CRATES_IO_PROTOCOL").is_none());
+        assert!(std::env::var_os("CARGO_REGISTRY_DEFAULT").is_none());
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        let project = root.join("workspace/project");
+
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::create_dir_all(project.join(".cargo")).unwrap();
+        std::fs::create_dir_all(root.join("workspace/.cargo")).unwrap();
+
+        // The home config defines the registries and sources...
+        std::fs::write(
+            home.join("config.toml"),
+            r#"[registries.crates-io]
+protocol = "git"
+
+[registries.tame-index-merged]
+index = "sparse+https://home-registry.com"
+
+[source.mirror]
+registry = "sparse+https://home-mirror.com"
+"#,
+        )
+        .unwrap();
+        // ...the workspace overrides one of them...
+        std::fs::write(
+            root.join("workspace/.cargo/config.toml"),
+            r#"[source.mirror]
+registry = "sparse+https://workspace-mirror.com"
+"#,
+        )
+        .unwrap();
+        // ...and the project uses them
+        std::fs::write(
+            project.join(".cargo/config.toml"),
+            r#"[registry]
+default = "tame-index-merged"
+
+[source.crates-io]
+replace-with = "mirror"
+"#,
+        )
+        .unwrap();
+
+        let project = Some(project);
+
+        let iurl = super::IndexUrl::crates_io(project.clone(), Some(&home), None).unwrap();
+        assert_eq!(iurl.as_str(), "sparse+https://workspace-mirror.com");
+
+        let (name, iurl) = super::IndexUrl::default_registry(project.clone(), Some(&home))
+            .unwrap()
+            .unwrap();
+        assert_eq!(name, "tame-index-merged");
+        assert_eq!(iurl.as_str(), "sparse+https://home-registry.com");
+
+        let protocol =
+            super::read_cargo_config(project, Some(&home), &Default::default(), |config| {
+                config
+                    .pointer("/registries/crates-io/protocol")?
+                    .as_str()
+                    .map(String::from)
+            })
+            .unwrap();
+        assert_eq!(protocol.as_deref(), Some("git"));
+    }
+
+    /// Verifies chains of source replacements are followed, including across
+    /// configs, and that invalid chains are reported
+    #[test]
+    fn follows_replacement_chains() {
+        use crate::error::SourceReplacementIssue as Issue;
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        let project = root.join("project");
+        let cfg_toml = project.join(".cargo/config.toml");
+
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::create_dir_all(cfg_toml.parent().unwrap()).unwrap();
+
+        let replacement = |config: &str| {
+            std::fs::write(&cfg_toml, config).unwrap();
+            super::get_source_replacement(
+                Some(project.clone()),
+                Some(&home),
+                &Default::default(),
+                "crates-io",
+            )
+        };
+
+        // Two hops
+        let iurl = replacement(
+            r#"[source.crates-io]
+replace-with = "a"
+[source.a]
+replace-with = "b"
+[source.b]
+registry = "sparse+https://two-hops.com"
+"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(iurl.as_str(), "sparse+https://two-hops.com");
+
+        let failure = |config: &str| {
+            let Err(crate::Error::SourceReplacement(sre)) = replacement(config) else {
+                panic!("expected a source replacement error");
+            };
+            sre
+        };
+
+        // Cycle
+        let sre = failure(
+            r#"[source.crates-io]
+replace-with = "a"
+[source.a]
+replace-with = "b"
+[source.b]
+replace-with = "a"
+"#,
+        );
+        assert!(matches!(sre.source, Issue::Cycle));
+        assert_eq!(sre.chain, ["crates-io", "a", "b", "a"]);
+
+        // Dead end
+        let sre = failure(
+            r#"[source.crates-io]
+replace-with = "a"
+[source.a]
+git = "https://github.com/rust-lang/crates.io-index"
+"#,
+        );
+        assert!(matches!(sre.source, Issue::UnknownKind));
+
+        let sre = failure(
+            r#"[source.crates-io]
+replace-with = "a"
+"#,
+        );
+        assert!(matches!(sre.source, Issue::Undefined));
+        assert_eq!(
+            sre.source.to_string(),
+            "the last source is not defined in either `[source]` or `[registries]`"
+        );
+        assert_eq!(
+            crate::Error::from(sre).to_string(),
+            "failed to resolve source replacement 'crates-io' -> 'a'"
+        );
+
+        // Replacing with a registry instead of a source
+        let iurl = replacement(
+            r#"[source.crates-io]
+replace-with = "a"
+[registries.a]
+index = "sparse+https://registry.com/index/"
+"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(iurl.is_sparse());
+        assert_eq!(iurl.as_str(), "sparse+https://registry.com/index/");
+
+        let iurl = replacement(
+            r#"[source.crates-io]
+replace-with = "mirror"
+[source.mirror]
+replace-with = "a"
+[registries.a]
+index = "https://github.com/EmbarkStudios/cargo-test-index"
+"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(!iurl.is_sparse());
+        assert_eq!(
+            iurl.as_str(),
+            "https://github.com/EmbarkStudios/cargo-test-index"
+        );
+
+        // A source takes precedence over a registry with the same name
+        let iurl = replacement(
+            r#"[source.crates-io]
+replace-with = "a"
+[source.a]
+registry = "sparse+https://source.com/"
+[registries.a]
+index = "sparse+https://registry.com/index/"
+"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(iurl.as_str(), "sparse+https://source.com/");
+
+        // A registry without an index is not a replacement
+        let sre = failure(
+            r#"[source.crates-io]
+replace-with = "a"
+[registries.a]
+token = "not-an-index"
+"#,
+        );
+        assert!(matches!(sre.source, Issue::Undefined));
+
+        // Across configs, the base layer defines the actual sources, and the
+        // project layers another replacement on top
+        std::fs::write(
+            home.join("config.toml"),
+            r#"[source.base-mirror]
+replace-with = "base-vendor"
+[source.base-vendor]
+directory = "vendor"
+"#,
+        )
+        .unwrap();
+
+        let iurl = replacement(
+            r#"[source.crates-io]
+replace-with = "base-mirror"
+"#,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(matches!(iurl, super::IndexUrl::Directory(_)));
+        assert_eq!(iurl.as_str(), root.join("vendor").as_str());
+    }
+
+    /// Verifies locations can be converted to owned versions that outlive
+    /// the data they were created from
+    #[test]
+    fn into_owned() {
+        fn spawn(il: super::IndexLocation<'static>) -> std::thread::JoinHandle<String> {
+            std::thread::spawn(move || il.url.as_str().to_owned())
+        }
+
+        let url = String::from("sparse+https://owned.com/");
+        let location = super::IndexLocation::new(super::IndexUrl::non_crates_io(url.as_str()))
+            .with_root(Some("/index-root".into()));
+        let cloned = location.clone();
+
+        assert!(cloned.url.is_sparse());
+
+        let owned = location.into_owned();
+        drop(cloned);
+        drop(url);
+
+        assert_eq!(spawn(owned).join().unwrap(), "sparse+https://owned.com/");
+
+        let from_string: super::IndexUrl<'static> = String::from("https://owned.com/").into();
+        assert!(!from_string.is_sparse());
+    }
+
+    /// Verifies urls that are obviously for the wrong kind of index are rejected
+    #[test]
+    fn validates_urls() {
+        use crate::InvalidUrlError as Iue;
+
+        let validate = |url: &str| match super::IndexUrl::from(url).validate() {
+            Ok(()) => None,
+            Err(crate::Error::InvalidUrl(iu)) => Some(iu.source),
+            Err(err) => panic!("unexpected error {err}"),
+        };
+
+        assert!(validate("sparse+https://index.crates.io/").is_none());
+        assert!(validate("sparse+http://my-registry.com/index/").is_none());
+        assert!(validate("https://github.com/rust-lang/crates.io-index").is_none());
+        assert!(validate("https://github.com/rust-lang/crates.io-index/").is_none());
+        assert!(validate("https://my-registry.com/index.git/").is_none());
+        assert!(validate("https://my-registry.com/index").is_none());
+        assert!(validate("ssh://git@my-registry.com/index/").is_none());
+
+        assert!(matches!(
+            validate("https://index.crates.io"),
+            Some(Iue::MissingSparse)
+        ));
+        assert!(matches!(
+            validate("registry+https://user@my-registry.com:8080/index/"),
+            Some(Iue::MissingSparse)
+        ));
+        assert!(matches!(
+            validate("sparse+ssh://my-registry.com/index/"),
+            Some(Iue::SparseNonHttp)
+        ));
+
+        assert!(matches!(
+            crate::index::ComboIndexCache::new(super::IndexLocation::new(
+                "https://index.crates.io/".into()
+            )),
+            Err(crate::Error::InvalidUrl(_))
+        ));
+    }
+
+    /// Verifies environment variables override config values, including for
+    /// table entries that are only specified in the environment
+    #[test]
+    fn env_overrides() {
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::create_dir(&home).unwrap();
+
+        std::fs::write(
+            root.join(".cargo/config.toml"),
+            r#"
+[registries.my_registry]
+index = "sparse+https://my-registry.example.com/"
+
+[net]
+offline = true
+
+[alias]
+b = "build"
+"#,
+        )
+        .unwrap();
+
+        let env: Vec<_> = [
+            (
+                "CARGO_REGISTRIES_MY_REGISTRY_INDEX",
+                "sparse+https://env.example.com/",
+            ),
+            ("CARGO_REGISTRIES_CRATES_IO_PROTOCOL", "git"),
+            ("CARGO_SOURCE_CRATES_IO_REPLACE_WITH", "vendored"),
+            ("CARGO_NET_OFFLINE", "false"),
+            ("CARGO_HTTP_TIMEOUT", "10"),
+            ("CARGO_HOME", "/ignored"),
+            ("CARGO_REGISTRIES_MY_REGISTRY_TOKEN", "secret"),
+            ("CARGO_SOURCE_MY_GIT_GIT", "https://example.com/repo.git"),
+            ("CARGO_HTTP_CHECK_REVOKE", "false"),
+            ("CARGO_ALIAS_B", "bench"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_owned(), v.to_owned()))
+        .collect();
+
+        let read = |pointer: &str| {
+            super::read_cargo_config_with(
+                Some(root.clone()),
+                Some(&home),
+                &env,
+                &Default::default(),
+                |config| {
+                    let value = config.pointer(pointer)?;
+                    value
+                        .as_str()
+                        .map(String::from)
+                        .or_else(|| value.as_bool().map(|b| b.to_string()))
+                        .or_else(|| value.as_integer().map(|i| i.to_string()))
+                },
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            read("/registries/my_registry/index").as_deref(),
+            Some("sparse+https://env.example.com/")
+        );
+        assert_eq!(
+            read("/registries/crates-io/protocol").as_deref(),
+            Some("git")
+        );
+        assert_eq!(
+            read("/source/crates-io/replace-with").as_deref(),
+            Some("vendored")
+        );
+        assert_eq!(read("/net/offline").as_deref(), Some("false"));
+        assert_eq!(read("/http/timeout").as_deref(), Some("10"));
+        assert!(read("/home").is_none());
+        assert_eq!(
+            read("/registries/my_registry/token").as_deref(),
+            Some("secret")
+        );
+        assert_eq!(
+            read("/source/my-git/git").as_deref(),
+            Some("https://example.com/repo.git")
+        );
+        assert_eq!(read("/http/check-revoke").as_deref(), Some("false"));
+        // Any key set in a config file can be overridden, even undocumented ones
+        assert_eq!(read("/alias/b").as_deref(), Some("bench"));
+    }
+
+    /// Verifies an explicit protocol overrides the config, but not source
+    /// replacement
+    #[test]
+    fn explicit_protocol() {
+        use super::{IndexUrl, Protocol};
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::create_dir(&home).unwrap();
+
+        let config = root.join(".cargo/config.toml");
+        std::fs::write(&config, "[registries.crates-io]\nprotocol = \"sparse\"\n").unwrap();
+
+        let crates_io =
+            |protocol| IndexUrl::crates_io_with_protocol(Some(root.clone()), Some(&home), protocol);
+
+        assert!(matches!(
+            crates_io(Protocol::Git).unwrap(),
+            IndexUrl::CratesIoGit
+        ));
+        assert!(matches!(
+            crates_io(Protocol::Auto).unwrap(),
+            IndexUrl::CratesIoSparse
+        ));
+
+        std::fs::write(
+            &config,
+            r#"[source.crates-io]
+replace-with = "mirror"
+
+[source.mirror]
+registry = "sparse+https://mirror.example.com/"
+"#,
+        )
+        .unwrap();
+
+        for protocol in [Protocol::Auto, Protocol::Sparse, Protocol::Git] {
+            assert_eq!(
+                crates_io(protocol).unwrap().as_str(),
+                "sparse+https://mirror.example.com/"
+            );
+        }
+    }
+
+    /// Verifies urls are parsed into the appropriate variant, and round trip
+    #[test]
+    fn parses_urls() {
+        use super::IndexUrl;
+
+        let parse = |s: &str| s.parse::<IndexUrl<'static>>();
+
+        assert!(matches!(
+            parse(crate::CRATES_IO_HTTP_INDEX).unwrap(),
+            IndexUrl::CratesIoSparse
+        ));
+        assert!(matches!(
+            parse("sparse+https://index.crates.io").unwrap(),
+            IndexUrl::CratesIoSparse
+        ));
+        assert!(matches!(
+            parse(crate::CRATES_IO_INDEX).unwrap(),
+            IndexUrl::CratesIoGit
+        ));
+        assert!(matches!(
+            parse("registry+https://github.com/rust-lang/crates.io-index").unwrap(),
+            IndexUrl::CratesIoGit
+        ));
+        assert!(matches!(
+            parse("/srv/registry").unwrap(),
+            IndexUrl::Local(p) if p.as_str() == "/srv/registry"
+        ));
+        assert!(matches!(
+            parse("./registry").unwrap(),
+            IndexUrl::Local(p) if p.as_str() == "./registry"
+        ));
+
+        for url in [
+            "sparse+https://my-registry.example.com/index/",
+            "registry+https://my-registry.example.com/index.git",
+            "git+https://github.com/EmbarkStudios/cargo-test-index",
+            "ssh://git@github.com/EmbarkStudios/cargo-test-index",
+        ] {
+            let iu = parse(url).unwrap();
+            assert!(matches!(iu, IndexUrl::NonCratesIo(_)));
+            assert_eq!(iu.to_string(), url);
+        }
+
+        for iu in [IndexUrl::CratesIoSparse, IndexUrl::CratesIoGit] {
+            assert_eq!(iu.to_string(), iu.as_str());
+            assert!(matches!(
+                (&iu, parse(&iu.to_string()).unwrap()),
+                (IndexUrl::CratesIoSparse, IndexUrl::CratesIoSparse)
+                    | (IndexUrl::CratesIoGit, IndexUrl::CratesIoGit)
+            ));
+        }
+
+        for garbage in [
+            "",
+            "registry",
+            "not a url",
+            "nope+https://example.com/index",
+            "https://index.crates.io/",
+        ] {
+            assert!(
+                matches!(parse(garbage), Err(crate::Error::InvalidUrl(_))),
+                "{garbage} should be invalid"
+            );
+        }
+    }
+
+    /// Verifies the reason for the crates.io protocol is reported correctly
+    #[test]
+    fn protocol_provenance() {
+        use super::{Protocol, ProtocolProvenance as Pp};
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let project = root.join("project");
+        let home = root.join("cargo-home");
+        std::fs::create_dir_all(project.join(".cargo")).unwrap();
+        std::fs::create_dir(&home).unwrap();
+
+        let decide = |env: Option<&str>, version: Option<&str>| {
+            super::crates_io_protocol_with(
+                env.map(String::from),
+                Some(project.clone()),
+                Some(&home),
+                version,
+                &Default::default(),
+            )
+            .unwrap()
+        };
+
+        let d = decide(None, Some("1.69.0"));
+        assert_eq!(d.protocol, Protocol::Git);
+        assert!(
+            matches!(d.provenance, Pp::CargoVersionDefault(v) if v == semver::Version::new(1, 69, 0))
+        );
+
+        let d = decide(None, Some("1.70.0"));
+        assert_eq!(d.protocol, Protocol::Sparse);
+
+        let home_config = home.join("config.toml");
+        std::fs::write(&home_config, "[registries.crates-io]\nprotocol = \"git\"\n").unwrap();
+        let d = decide(None, Some("1.80.0"));
+        assert_eq!(d.protocol, Protocol::Git);
+        assert!(matches!(&d.provenance, Pp::ConfigFile(p) if *p == home_config));
+
+        // Closer configs take precedence
+        let project_config = project.join(".cargo/config.toml");
+        std::fs::write(
+            &project_config,
+            "[registries.crates-io]\nprotocol = \"sparse\"\n",
+        )
+        .unwrap();
+        let d = decide(None, Some("1.80.0"));
+        assert_eq!(d.protocol, Protocol::Sparse);
+        assert!(matches!(&d.provenance, Pp::ConfigFile(p) if *p == project_config));
+
+        let d = decide(Some("git"), None);
+        assert_eq!(d.protocol, Protocol::Git);
+        assert!(matches!(d.provenance, Pp::EnvVar));
+
+        std::fs::write(
+            &project_config,
+            r#"[source.crates-io]
+replace-with = "mirror"
+
+[source.mirror]
+registry = "sparse+https://mirror.example.com/"
+"#,
+        )
+        .unwrap();
+        let d = decide(Some("git"), None);
+        assert_eq!(d.protocol, Protocol::Sparse);
+        assert!(
+            matches!(&d.provenance, Pp::SourceReplacement(url) if url.as_str() == "sparse+https://mirror.example.com/")
+        );
+    }
+
+    /// Verifies config overrides take precedence over the environment, which
+    /// takes precedence over config files
+    #[test]
+    fn config_overrides() {
+        use super::{ConfigOverrides, IndexUrl, Protocol, ProtocolProvenance};
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::create_dir(&home).unwrap();
+
+        std::fs::write(
+            root.join(".cargo/config.toml"),
+            r#"
+[registries.tame-index-layers]
+index = "sparse+https://file.example.com/"
+
+[registries.crates-io]
+protocol = "sparse"
+"#,
+        )
+        .unwrap();
+
+        let env = vec![(
+            "CARGO_REGISTRIES_TAME_INDEX_LAYERS_INDEX".to_owned(),
+            "sparse+https://env.example.com/".to_owned(),
+        )];
+        let overrides = ConfigOverrides::parse([
+            r#"registries.tame-index-layers.index = "sparse+https://first.example.com/""#,
+            r#"registries.tame-index-layers.index="sparse+https://override.example.com/""#,
+            r#"registries.crates-io.protocol = "git""#,
+        ])
+        .unwrap();
+
+        let index = |env: &[(String, String)], overrides: &ConfigOverrides| {
+            super::read_cargo_config_with(
+                Some(root.clone()),
+                Some(&home),
+                env,
+                overrides,
+                |config| {
+                    config
+                        .pointer("/registries/tame-index-layers/index")?
+                        .as_str()
+                        .map(String::from)
+                },
+            )
+            .unwrap()
+            .unwrap()
+        };
+
+        let none = ConfigOverrides::default();
+        assert_eq!(index(&[], &none), "sparse+https://file.example.com/");
+        assert_eq!(index(&env, &none), "sparse+https://env.example.com/");
+        assert_eq!(
+            index(&env, &overrides),
+            "sparse+https://override.example.com/"
+        );
+        assert_eq!(
+            index(&[], &overrides),
+            "sparse+https://override.example.com/"
+        );
+
+        let iurl = IndexUrl::for_registry_with_overrides(
+            "tame-index-layers",
+            Some(root.clone()),
+            Some(&home),
+            &overrides,
+        )
+        .unwrap();
+        assert_eq!(iurl.as_str(), "sparse+https://override.example.com/");
+
+        let d = super::crates_io_protocol_with(
+            Some("sparse".to_owned()),
+            Some(root.clone()),
+            Some(&home),
+            None,
+            &overrides,
+        )
+        .unwrap();
+        assert_eq!(d.protocol, Protocol::Git);
+        assert!(matches!(d.provenance, ProtocolProvenance::ConfigOverride));
+
+        for invalid in [
+            "",
+            "registries",
+            "a = 1\nb = 2",
+            "a = { b = 1, c = 2 }",
+            "= 1",
+        ] {
+            assert!(
+                ConfigOverrides::parse([invalid]).is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    /// Verifies hashed roots place each index directly in its url directory
+    #[test]
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    fn hashed_root() {
+        use super::{IndexLocation, IndexPath, IndexUrl};
+
+        let parts = |url| {
+            IndexLocation {
+                url,
+                root: IndexPath::Hashed("/cache".into()),
+            }
+            .into_parts()
+            .unwrap()
+        };
+
+        assert_eq!(
+            parts(IndexUrl::CratesIoSparse),
+            (
+                "/cache/index.crates.io-6f17d22bba15001f".into(),
+                crate::CRATES_IO_HTTP_INDEX.to_owned()
+            )
+        );
+        assert_eq!(
+            parts(IndexUrl::CratesIoGit),
+            (
+                "/cache/github.com-1ecc6299db9ec823".into(),
+                format!("{}/", crate::CRATES_IO_INDEX)
+            )
+        );
+        assert_eq!(
+            parts("https://github.com/EmbarkStudios/cargo-test-index".into()),
+            (
+                "/cache/github.com-655148e0a865c9e0".into(),
+                "https://github.com/EmbarkStudios/cargo-test-index/".to_owned()
+            )
+        );
+    }
+
+    /// Verifies the search for configs stops where requested, and that a cargo
+    /// home found while searching keeps the precedence of its location
+    #[test]
+    fn config_search_stop() {
+        use super::{ConfigOverrides, ConfigSearchStop};
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let user = root.join("home/user");
+        let home = user.join(".cargo");
+        let project = user.join("projects/nested/project");
+
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::create_dir_all(&home).unwrap();
+        std::fs::create_dir_all(project.join(".cargo")).unwrap();
+
+        let index = |index: &str| format!("[registries.tame-index-search]\nindex = '{index}'\n");
+        std::fs::write(root.join(".cargo/config.toml"), index("root")).unwrap();
+        std::fs::write(home.join("config.toml"), index("home")).unwrap();
+        std::fs::write(project.join(".cargo/config.toml"), "").unwrap();
+
+        let read = |stop: ConfigSearchStop| {
+            let mut overrides = ConfigOverrides::default();
+            overrides.search_stop(stop);
+            super::read_cargo_config(Some(project.clone()), Some(&home), &overrides, |config| {
+                config
+                    .pointer("/registries/tame-index-search/index")?
+                    .as_str()
+                    .map(String::from)
+            })
+            .unwrap()
+        };
+
+        // The cargo home is an ancestor of the project, so it is only found
+        // once, and takes precedence over the config further up the tree
+        let paths = super::cargo_config_paths(
+            Some(project.clone()),
+            Some(&home),
+            &ConfigSearchStop::Dir(root.clone()),
+        );
+        assert_eq!(
+            paths,
+            [
+                project.join(".cargo/config.toml"),
+                home.join("config.toml"),
+                root.join(".cargo/config.toml"),
+            ]
+        );
+        assert_eq!(
+            read(ConfigSearchStop::Dir(root.clone())).as_deref(),
+            Some("home")
+        );
+
+        // The search stops at the directory containing the cargo home
+        let paths =
+            super::cargo_config_paths(Some(project.clone()), Some(&home), &ConfigSearchStop::Home);
+        assert_eq!(
+            paths,
+            [project.join(".cargo/config.toml"), home.join("config.toml")]
+        );
+
+        std::fs::write(home.join("config.toml"), "").unwrap();
+        assert_eq!(
+            read(ConfigSearchStop::Dir(root.clone())).as_deref(),
+            Some("root")
+        );
+        assert_eq!(read(ConfigSearchStop::Dir(user.clone())), None);
+        assert_eq!(read(ConfigSearchStop::Home), None);
+
+        // If the root isn't a descendant of the stop directory the search
+        // continues, and the cargo home is still added at the end
+        let paths = super::cargo_config_paths(
+            Some(project.clone()),
+            Some(&home),
+            &ConfigSearchStop::Dir(root.join("elsewhere")),
+        );
+        assert_eq!(paths[0], project.join(".cargo/config.toml"));
+        assert_eq!(
+            paths
+                .iter()
+                .filter(|p| **p == home.join("config.toml"))
+                .count(),
+            1
+        );
+        assert!(paths.contains(&root.join(".cargo/config.toml")));
+
+        // A cargo home outside of the searched directories is added last
+        let other_home = root.join("cargo-home");
+        std::fs::create_dir_all(&other_home).unwrap();
+        std::fs::write(other_home.join("config"), "").unwrap();
+        let paths = super::cargo_config_paths(
+            Some(project.clone()),
+            Some(&other_home),
+            &ConfigSearchStop::Dir(user.clone()),
+        );
+        assert_eq!(
+            paths,
+            [
+                project.join(".cargo/config.toml"),
+                home.join("config.toml"),
+                other_home.join("config"),
+            ]
+        );
+    }
+
+    /// Verifies the default registry is resolved from the config
+    #[test]
+    fn default_registry() {
+        assert!(std::env::var_os("CARGO_REGISTRY_DEFAULT").is_none());
+
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let cfg_toml = td.path().join(".cargo/config.toml");
+
+        std::fs::create_dir_all(cfg_toml.parent().unwrap()).unwrap();
+        let home = root.join("cargo-home");
+
+        assert!(
+            super::IndexUrl::default_registry(Some(root.clone()), Some(&home))
+                .unwrap()
+                .is_none()
+        );
+
+        std::fs::write(
+            &cfg_toml,
+            r#"[registry]
+default = "tame-index-default"
+
+[registries.tame-index-default]
+index = "sparse+https://default-registry.com"
+"#,
+        )
+        .unwrap();
+
+        let (name, iurl) = super::IndexUrl::default_registry(Some(root.clone()), Some(&home))
+            .unwrap()
+            .expect("default registry should be configured");
+        assert_eq!(name, "tame-index-default");
+        assert_eq!(iurl.as_str(), "sparse+https://default-registry.com");
+
+        let iurl = super::RegistrySelector::Named(&name)
+            .into_url(Some(root.clone()), Some(&home))
+            .unwrap();
+        assert_eq!(iurl.as_str(), "sparse+https://default-registry.com");
+    }
+
     #[test]
     fn custom() {
         assert!(std::env::var_os("CARGO_REGISTRIES_TAME_INDEX_TEST_INDEX").is_none());
 
         let td = tempfile::tempdir().unwrap();
-        let root = crate::PathBuf::from_path_buf(td.path().to_owned()).unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
         let cfg_toml = td.path().join(".cargo/config.toml");
 
         std::fs::create_dir_all(cfg_toml.parent().unwrap()).unwrap();
@@ -409,8 +2514,7 @@ index = "sparse+https://some-url.com"
             std::fs::write(&cfg_toml, SPARSE).unwrap();
 
             let iurl =
-                super::IndexUrl::for_registry_name(Some(root.clone()), None, "tame-index-test")
-                    .unwrap();
+                super::IndexUrl::for_registry("tame-index-test", Some(root.clone()), None).unwrap();
             assert_eq!(iurl.as_str(), "sparse+https://some-url.com");
             assert!(iurl.is_sparse());
 
@@ -420,8 +2524,7 @@ index = "sparse+https://some-url.com"
             );
 
             let iurl =
-                super::IndexUrl::for_registry_name(Some(root.clone()), None, "tame-index-test")
-                    .unwrap();
+                super::IndexUrl::for_registry("tame-index-test", Some(root.clone()), None).unwrap();
             assert_eq!(iurl.as_str(), "sparse+https://some-other-url.com");
             assert!(iurl.is_sparse());
 
@@ -432,8 +2535,7 @@ index = "sparse+https://some-url.com"
             std::fs::write(&cfg_toml, GIT).unwrap();
 
             let iurl =
-                super::IndexUrl::for_registry_name(Some(root.clone()), None, "tame-index-test")
-                    .unwrap();
+                super::IndexUrl::for_registry("tame-index-test", Some(root.clone()), None).unwrap();
             assert_eq!(iurl.as_str(), "https://some-url.com");
             assert!(!iurl.is_sparse());
 
@@ -443,21 +2545,40 @@ index = "sparse+https://some-url.com"
             );
 
             let iurl =
-                super::IndexUrl::for_registry_name(Some(root.clone()), None, "tame-index-test")
-                    .unwrap();
+                super::IndexUrl::for_registry("tame-index-test", Some(root.clone()), None).unwrap();
             assert_eq!(iurl.as_str(), "https://some-other-url.com");
             assert!(!iurl.is_sparse());
 
             std::env::remove_var("CARGO_REGISTRIES_TAME_INDEX_TEST_INDEX");
         }
 
-        #[allow(unused_variables)]
         {
-            let err = crate::Error::UnknownRegistry("non-existant".to_owned());
-            assert!(matches!(
-                super::IndexUrl::for_registry_name(Some(root.clone()), None, "non-existant"),
-                Err(err),
-            ));
+            let Err(crate::Error::UnknownRegistry {
+                name,
+                env,
+                searched,
+            }) = super::IndexUrl::for_registry("non-existant", Some(root.clone()), None)
+            else {
+                panic!("expected an unknown registry error");
+            };
+
+            assert_eq!(name, "non-existant");
+            assert_eq!(env, "CARGO_REGISTRIES_NON_EXISTANT_INDEX");
+            assert_eq!(searched[0], root.join(".cargo/config.toml"));
+        }
+
+        // Replacing the registry takes precedence over its index
+        {
+            std::fs::write(
+                &cfg_toml,
+                format!("{GIT}\n[source.tame-index-test]\nreplace-with = 'mirror'\n[source.mirror]\nregistry = 'sparse+https://mirror.com'"),
+            )
+            .unwrap();
+
+            let iurl =
+                super::IndexUrl::for_registry("tame-index-test", Some(root.clone()), None).unwrap();
+            assert_eq!(iurl.as_str(), "sparse+https://mirror.com");
+            assert!(iurl.is_sparse());
         }
     }
 }
@@ -1,5 +1,8 @@
-use super::{cache::ValidCacheEntry, FileLock, IndexCache};
-use crate::{Error, HttpError, IndexKrate, KrateName};
+use super::{
+    cache::{Revision, ValidCacheEntry},
+    FileLock, IndexCache,
+};
+use crate::{utils::flock::LockOptions, Error, HttpError, IndexKrate, KrateName};
 
 /// The default URL of the crates.io HTTP index
 pub const CRATES_IO_HTTP_INDEX: &str = "sparse+https://index.crates.io/";
@@ -14,6 +17,7 @@ pub const CRATES_IO_HTTP_INDEX: &str = "sparse+https://index.crates.io/";
 pub struct SparseIndex {
     cache: IndexCache,
     url: String,
+    crate_locks: bool,
 }
 
 impl SparseIndex {
@@ -32,9 +36,36 @@ impl SparseIndex {
         Ok(Self {
             cache: IndexCache::at_path(path),
             url,
+            crate_locks: false,
         })
     }
 
+    /// If true, an exclusive lock for the individual crate is held while
+    /// writing its cache entry, in addition to the lock passed by the caller
+    ///
+    /// This allows concurrent processes that only hold shared, or no, locks
+    /// to update cache entries without clobbering each other, see
+    /// [`LockOptions::crate_cache_lock`]
+    #[inline]
+    pub fn with_crate_locks(mut self, crate_locks: bool) -> Self {
+        self.crate_locks = crate_locks;
+        self
+    }
+
+    /// Acquires the lock for writing the crate's cache entry, if enabled
+    ///
+    /// This is best effort, the same as writing the cache entry itself
+    #[inline]
+    fn crate_lock(&self, name: KrateName<'_>) -> Option<FileLock> {
+        if !self.crate_locks {
+            return None;
+        }
+
+        LockOptions::crate_cache_lock(&self.cache, name)
+            .lock(|_path| None)
+            .ok()
+    }
+
     /// Get the configuration of the index.
     ///
     /// See the [cargo docs](https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration)
@@ -54,8 +85,11 @@ impl SparseIndex {
     /// See [`Self::make_remote_request`] for a way to make a complete request
     #[inline]
     pub fn crate_url(&self, name: KrateName<'_>) -> String {
-        let rel_path = name.relative_path(Some('/'));
-        format!("{}{rel_path}", self.url())
+        let url = self.url();
+        let mut crate_url = String::with_capacity(url.len() + name.0.len() + 6);
+        crate_url.push_str(url);
+        name.push_relative_path(&mut crate_url, Some('/'));
+        crate_url
     }
 
     /// The HTTP url of the index
@@ -70,6 +104,13 @@ impl SparseIndex {
         &self.cache
     }
 
+    /// Gets the mutable accessor to the local index cache, eg. to change its
+    /// [`CacheLayout`](super::cache::CacheLayout)
+    #[inline]
+    pub fn cache_mut(&mut self) -> &mut IndexCache {
+        &mut self.cache
+    }
+
     /// Attempts to read the locally cached crate information
     #[inline]
     pub fn cached_krate(
@@ -97,6 +138,47 @@ impl SparseIndex {
         name: KrateName<'_>,
         etag: Option<&str>,
         lock: &FileLock,
+    ) -> Result<http::Request<()>, Error> {
+        let cache_entry = if etag.is_none() {
+            // If we're unable to read the cache entry we can just ignore it,
+            // guaranteeing we'll get the full index contents if the crate exists
+            self.cache.read_cache_file(name, lock).ok().flatten()
+        } else {
+            None
+        };
+
+        self.build_remote_request(name, etag, cache_entry.as_deref())
+    }
+
+    /// Async version of [`Self::make_remote_request`]
+    ///
+    /// The only difference is that the cache entry, if one is needed, is read
+    /// via [`tokio::fs`] so that it doesn't block the runtime
+    #[cfg(feature = "tokio")]
+    pub async fn make_remote_request_async(
+        &self,
+        name: KrateName<'_>,
+        etag: Option<&str>,
+        lock: &FileLock,
+    ) -> Result<http::Request<()>, Error> {
+        let cache_entry = if etag.is_none() {
+            self.cache
+                .read_cache_file_async(name, lock)
+                .await
+                .ok()
+                .flatten()
+        } else {
+            None
+        };
+
+        self.build_remote_request(name, etag, cache_entry.as_deref())
+    }
+
+    fn build_remote_request(
+        &self,
+        name: KrateName<'_>,
+        etag: Option<&str>,
+        cache_entry: Option<&[u8]>,
     ) -> Result<http::Request<()>, Error> {
         use http::header;
 
@@ -134,22 +216,18 @@ impl SparseIndex {
             // If we're unable to get the cache version we can just ignore setting the
             // header, guaranteeing we'll get the full index contents if the crate exists
             let set_cache_version = |headers: &mut header::HeaderMap| -> Option<()> {
-                let contents = self.cache.read_cache_file(name, lock).ok()??;
-                let valid = ValidCacheEntry::read(&contents).ok()?;
-
-                let (key, value) = valid.revision.split_once(':')?;
-                let value = header::HeaderValue::from_str(value.trim()).ok()?;
-                let name = if key == header::ETAG {
-                    header::IF_NONE_MATCH
-                } else if key == header::LAST_MODIFIED {
-                    header::IF_MODIFIED_SINCE
-                } else {
+                let valid = ValidCacheEntry::read(cache_entry?).ok()?;
+
+                let (name, value) = match valid.parsed_revision() {
+                    Revision::ETag(etag) => (header::IF_NONE_MATCH, etag),
+                    Revision::LastModified(lm) => (header::IF_MODIFIED_SINCE, lm),
                     // We could error here, but that's kind of pointless
                     // since the response will be sent in full if we haven't
                     // specified one of the above headers. Though it does
                     // potentially indicate something weird is going on
-                    return None;
+                    _ => return None,
                 };
+                let value = header::HeaderValue::from_str(value).ok()?;
 
                 headers.insert(name, value);
                 None
@@ -188,7 +266,7 @@ impl SparseIndex {
         write_cache_entry: bool,
         lock: &FileLock,
     ) -> Result<Option<IndexKrate>, Error> {
-        use http::{header, StatusCode};
+        use http::StatusCode;
         let (parts, body) = response.into_parts();
 
         match parts.status {
@@ -197,20 +275,8 @@ impl SparseIndex {
                 let krate = IndexKrate::from_slice(&body)?;
 
                 if write_cache_entry {
-                    // The same as cargo, prefer etag over last-modified
-                    let version = if let Some(etag) = parts.headers.get(header::ETAG) {
-                        etag.to_str()
-                            .ok()
-                            .map(|etag| format!("{}: {etag}", header::ETAG))
-                    } else if let Some(lm) = parts.headers.get(header::LAST_MODIFIED) {
-                        lm.to_str()
-                            .ok()
-                            .map(|lm| format!("{}: {lm}", header::LAST_MODIFIED))
-                    } else {
-                        None
-                    };
-
-                    let revision = version.unwrap_or_else(|| "Unknown".to_owned());
+                    let revision = cache_revision(&parts.headers);
+                    let _crate_lock = self.crate_lock(name);
 
                     // It's unfortunate if we can't write to the cache, but we
                     // don't treat it as a hard error since we still have the
@@ -223,21 +289,91 @@ impl SparseIndex {
             // The local cache entry is up to date with the latest entry on the
             // server, we can just return the local one
             StatusCode::NOT_MODIFIED => self.cache.cached_krate(name, None, lock),
-            // The server requires authorization but the user didn't provide it
-            StatusCode::UNAUTHORIZED => Err(HttpError::StatusCode {
-                code: StatusCode::UNAUTHORIZED,
-                msg: "the request was not authorized",
-            }
-            .into()),
-            // The crate does not exist, or has been removed
-            StatusCode::NOT_FOUND
-            | StatusCode::GONE
-            | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => Ok(None),
-            code => Err(HttpError::StatusCode {
-                code,
-                msg: "the status code is invalid for this protocol",
+            code => status_code_response(code),
+        }
+    }
+
+    /// Async version of [`Self::parse_remote_response`]
+    ///
+    /// The only difference is that the disk I/O for reading and writing cache
+    /// entries is done via [`tokio::fs`] so that it doesn't block the runtime
+    #[cfg(feature = "tokio")]
+    pub async fn parse_remote_response_async(
+        &self,
+        name: KrateName<'_>,
+        response: http::Response<Vec<u8>>,
+        write_cache_entry: bool,
+        lock: &FileLock,
+    ) -> Result<Option<IndexKrate>, Error> {
+        use http::StatusCode;
+        let (parts, body) = response.into_parts();
+
+        match parts.status {
+            StatusCode::OK => {
+                let krate = IndexKrate::from_slice(&body)?;
+
+                if write_cache_entry {
+                    let revision = cache_revision(&parts.headers);
+                    let _crate_lock = if self.crate_locks {
+                        LockOptions::crate_cache_lock(&self.cache, name)
+                            .lock_async(|_path| None)
+                            .await
+                            .ok()
+                    } else {
+                        None
+                    };
+                    let _err = self
+                        .cache
+                        .write_to_cache_async(&krate, &revision, lock)
+                        .await;
+                }
+
+                Ok(Some(krate))
             }
-            .into()),
+            StatusCode::NOT_MODIFIED => self.cache.cached_krate_async(name, None, lock).await,
+            code => status_code_response(code),
+        }
+    }
+}
+
+/// Gets the revision to write to a cache entry from the headers of a successful
+/// response
+#[inline]
+fn cache_revision(headers: &http::HeaderMap) -> String {
+    use http::header;
+
+    // The same as cargo, prefer etag over last-modified
+    let version = if let Some(etag) = headers.get(header::ETAG) {
+        etag.to_str().ok().map(Revision::etag)
+    } else if let Some(lm) = headers.get(header::LAST_MODIFIED) {
+        lm.to_str().ok().map(Revision::last_modified)
+    } else {
+        None
+    };
+
+    version.unwrap_or(Revision::Unknown("Unknown")).to_string()
+}
+
+/// Handles the status codes other than `200` and `304`
+#[inline]
+fn status_code_response(code: http::StatusCode) -> Result<Option<IndexKrate>, Error> {
+    use http::StatusCode;
+
+    match code {
+        // The server requires authorization but the user didn't provide it
+        StatusCode::UNAUTHORIZED => Err(HttpError::StatusCode {
+            code: StatusCode::UNAUTHORIZED,
+            msg: "the request was not authorized",
+        }
+        .into()),
+        // The crate does not exist, or has been removed
+        StatusCode::NOT_FOUND | StatusCode::GONE | StatusCode::UNAVAILABLE_FOR_LEGAL_REASONS => {
+            Ok(None)
+        }
+        code => Err(HttpError::StatusCode {
+            code,
+            msg: "the status code is invalid for this protocol",
         }
+        .into()),
     }
 }
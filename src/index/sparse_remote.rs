@@ -80,6 +80,35 @@ impl RemoteSparseIndex {
         self.index.cached_krate(name, lock)
     }
 
+    /// Get the configuration of the index.
+    ///
+    /// The `config.json` on disk is used if it exists, otherwise it is
+    /// requested from the remote index, but not written to disk
+    ///
+    /// See the [cargo docs](https://doc.rust-lang.org/cargo/reference/registry-index.html#index-configuration)
+    pub fn index_config(&self) -> Result<super::IndexConfig, Error> {
+        match self.index.index_config() {
+            Err(Error::IoPath(err, _)) if err.kind() == std::io::ErrorKind::NotFound => {}
+            res => return res,
+        }
+
+        let res = self
+            .client
+            .get(format!("{}config.json", self.index.url()))
+            .send()?;
+
+        let code = res.status();
+        if !code.is_success() {
+            return Err(crate::HttpError::StatusCode {
+                code,
+                msg: "failed to retrieve config.json",
+            }
+            .into());
+        }
+
+        Ok(serde_json::from_slice(&res.bytes()?)?)
+    }
+
     /// Helper method for downloading multiple crates in parallel
     ///
     /// Note that in most cases using [`AsyncRemoteSparseIndex::krates_blocking`]
@@ -143,7 +172,10 @@ impl AsyncRemoteSparseIndex {
         write_cache_entry: bool,
         lock: &FileLock,
     ) -> Result<Option<IndexKrate>, Error> {
-        let req = self.index.make_remote_request(name, None, lock)?;
+        let req = self
+            .index
+            .make_remote_request_async(name, None, lock)
+            .await?;
 
         let (
             http::request::Parts {
@@ -163,7 +195,8 @@ impl AsyncRemoteSparseIndex {
         let res = Self::exec_request(&self.client, req.build()?).await?;
 
         self.index
-            .parse_remote_response(name, res, write_cache_entry, lock)
+            .parse_remote_response_async(name, res, write_cache_entry, lock)
+            .await
     }
 
     async fn exec_request(
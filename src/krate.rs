@@ -1,19 +1,28 @@
 //! Provides types for the structured metadata stored in a registry index
 
+#[cfg(feature = "api")]
+pub mod api;
 mod dedupe;
 
 use crate::Error;
-use dedupe::DedupeContext;
+pub use dedupe::DedupeContext;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use smol_str::SmolStr;
-use std::{collections::BTreeMap, sync::Arc};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
 
 /// A mapping of feature name to the features it enables
 pub type FeatureMap = BTreeMap<String, Vec<String>>;
 
 /// A single version of a crate (package) published to the index
-#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq)]
+///
+/// Constructing this struct directly is discouraged, the public fields will be
+/// made private in a future release as new fields are added to the index format,
+/// use [`IndexVersionBuilder`] instead
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq)]
 pub struct IndexVersion {
     /// [Name](https://doc.rust-lang.org/cargo/reference/manifest.html#the-name-field)
     pub name: SmolStr,
@@ -40,9 +49,16 @@ pub struct IndexVersion {
     /// [Rust Version](https://doc.rust-lang.org/cargo/reference/manifest.html#the-rust-version-field)
     #[serde(skip_serializing_if = "Option::is_none")]
     pub rust_version: Option<SmolStr>,
-    /// The index version, 1 if not set, v2 indicates presence of feature2 field
+    /// The index format version, 1 if not set, v2 indicates presence of feature2 field
+    ///
+    /// See [`crate::index::cache::INDEX_V_MAX`] for the maximum version supported
+    /// by this crate
     #[serde(skip_serializing_if = "Option::is_none")]
-    v: Option<u32>,
+    pub v: Option<u32>,
+    /// Fields that are not known to this crate, only populated if the version
+    /// was parsed with [`ParseOptions::preserve_extras`]
+    #[serde(skip)]
+    pub extra: Option<Box<Extras>>,
 }
 
 impl IndexVersion {
@@ -60,6 +76,7 @@ impl IndexVersion {
             checksum: Chksum(Default::default()),
             yanked: false,
             v: None,
+            extra: None,
         }
     }
 
@@ -69,6 +86,42 @@ impl IndexVersion {
         &self.deps
     }
 
+    /// Dependencies of the specified kind
+    #[inline]
+    pub fn deps_of_kind(&self, kind: DependencyKind) -> impl Iterator<Item = &IndexDependency> {
+        self.deps.iter().filter(move |dep| dep.is_kind(&kind))
+    }
+
+    /// [Normal](DependencyKind::Normal) dependencies
+    #[inline]
+    pub fn normal_deps(&self) -> impl Iterator<Item = &IndexDependency> {
+        self.deps_of_kind(DependencyKind::Normal)
+    }
+
+    /// [Build](DependencyKind::Build) dependencies
+    #[inline]
+    pub fn build_deps(&self) -> impl Iterator<Item = &IndexDependency> {
+        self.deps_of_kind(DependencyKind::Build)
+    }
+
+    /// [Dev](DependencyKind::Dev) dependencies
+    #[inline]
+    pub fn dev_deps(&self) -> impl Iterator<Item = &IndexDependency> {
+        self.deps_of_kind(DependencyKind::Dev)
+    }
+
+    /// Dependencies that apply to the specified target triple, see
+    /// [`IndexDependency::matches_target`]
+    #[inline]
+    pub fn deps_for_target<'s>(
+        &'s self,
+        target: &'s str,
+    ) -> impl Iterator<Item = &'s IndexDependency> + 's {
+        self.deps
+            .iter()
+            .filter(move |dep| dep.matches_target(target))
+    }
+
     /// Checksum of the package for this version
     ///
     /// SHA256 of the .crate file
@@ -94,6 +147,90 @@ impl IndexVersion {
         )
     }
 
+    /// Resolves the features and optional dependencies of this version that
+    /// are enabled by the specified features, the same as cargo
+    ///
+    /// - `default` is enabled if `default_features` is true and it exists
+    /// - Optional dependencies have an implicit feature of the same name,
+    ///   unless they are referred to with the `dep:` syntax
+    /// - `dep:foo` enables the optional dependency `foo`
+    /// - `foo/bar` enables the optional dependency `foo`, as well as the `foo`
+    ///   feature if it exists
+    /// - `foo?/bar` does not enable `foo`
+    ///
+    /// Requested features may also use the `foo/bar` syntax, the same as
+    /// `cargo build --features`
+    ///
+    /// Note this only resolves features within this crate, it doesn't resolve
+    /// the features that are enabled on dependencies
+    pub fn resolve_features(
+        &self,
+        requested: &[&str],
+        default_features: bool,
+    ) -> FeatureResolution {
+        let explicit: BTreeMap<&str, &[String]> = self
+            .features()
+            .map(|(name, values)| (name.as_str(), values.as_slice()))
+            .collect();
+        let optional: BTreeSet<&str> = self
+            .deps
+            .iter()
+            .filter(|dep| dep.is_optional())
+            .map(|dep| dep.name.as_str())
+            .collect();
+        let namespaced: BTreeSet<&str> = explicit
+            .values()
+            .flat_map(|values| values.iter())
+            .filter_map(|value| value.strip_prefix("dep:"))
+            .collect();
+
+        let is_feature = |name: &str| {
+            explicit.contains_key(name) || (optional.contains(name) && !namespaced.contains(name))
+        };
+
+        let mut resolved = FeatureResolution::default();
+        let mut queue = Vec::new();
+
+        if default_features && explicit.contains_key("default") {
+            queue.push("default");
+        }
+
+        for req in requested {
+            if req.contains('/') || is_feature(req) {
+                queue.push(*req);
+            } else {
+                resolved.missing.push((*req).to_owned());
+            }
+        }
+
+        while let Some(value) = queue.pop() {
+            if let Some(dep) = value.strip_prefix("dep:") {
+                if optional.contains(dep) {
+                    resolved.optional_deps.insert(dep.to_owned());
+                }
+            } else if let Some((dep, _feature)) = value.split_once('/') {
+                // Weak dependency features never enable the dependency
+                if dep.ends_with('?') || !optional.contains(dep) {
+                    continue;
+                }
+
+                resolved.optional_deps.insert(dep.to_owned());
+                if is_feature(dep) {
+                    queue.push(dep);
+                }
+            } else if is_feature(value) && resolved.features.insert(value.to_owned()) {
+                if let Some(values) = explicit.get(value) {
+                    queue.extend(values.iter().map(String::as_str));
+                } else {
+                    // Implicit feature for an optional dependency
+                    resolved.optional_deps.insert(value.to_owned());
+                }
+            }
+        }
+
+        resolved
+    }
+
     /// Exclusivity flag. If this is a sys crate, it informs it
     /// conflicts with any other crate with the same links string.
     ///
@@ -121,15 +258,191 @@ impl IndexVersion {
         self.rust_version.as_deref()
     }
 
+    /// The [`Self::rust_version`] parsed as a [`Version`]
+    ///
+    /// The same as cargo, partial versions such as `1.65` are accepted, with
+    /// the missing components being filled with zero, but pre-release and
+    /// build metadata is rejected. `None` means the version did not specify
+    /// a `rust-version`
+    #[inline]
+    pub fn rust_version_parsed(&self) -> Option<Result<Version, Error>> {
+        self.rust_version().map(parse_rust_version)
+    }
+
+    /// The index format version of this entry, defaulting to 1 if the field
+    /// was not present
+    #[inline]
+    pub fn index_version(&self) -> u32 {
+        self.v.unwrap_or(1)
+    }
+
     /// Retrieves the URL this crate version's tarball can be downloaded from
+    ///
+    /// Fails if the version's name is not a valid crate name
+    #[inline]
+    pub fn download_url(&self, index: &crate::index::IndexConfig) -> Result<String, Error> {
+        Ok(index.download_url_with_checksum(
+            self.name.as_str().try_into()?,
+            self.version.as_ref(),
+            &self.checksum,
+        ))
+    }
+}
+
+impl IndexVersion {
+    #[inline]
+    fn sort_key(&self) -> (bool, Option<Version>) {
+        let vers = Version::parse(&self.version).ok();
+        (vers.is_none(), vers)
+    }
+
+    /// Compares versions by their semver version, with versions that aren't
+    /// valid semver ordered after all valid ones, and by the version string
+    /// after that, eg. for use with [`slice::sort_by`]
+    ///
+    /// Note that this only considers the version, so two versions that are
+    /// not equal, eg. one is yanked, can still be compared as equal, which is
+    /// why [`IndexVersion`] does not implement [`Ord`]
+    #[inline]
+    pub fn cmp_by_version(&self, other: &Self) -> std::cmp::Ordering {
+        self.sort_key()
+            .cmp(&other.sort_key())
+            .then_with(|| self.version.cmp(&other.version))
+    }
+}
+
+/// Builds an [`IndexVersion`]
+///
+/// ```
+/// let iv = tame_index::krate::IndexVersionBuilder::new("tame-index", "0.1.0")
+///     .feature("default", ["std"])
+///     .feature("std", Vec::<String>::new())
+///     .feature("serde", ["dep:serde"])
+///     .rust_version("1.70")
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(iv.features().count(), 3);
+/// ```
+pub struct IndexVersionBuilder {
+    name: SmolStr,
+    version: SmolStr,
+    deps: Vec<IndexDependency>,
+    checksum: [u8; 32],
+    features: FeatureMap,
+    features2: FeatureMap,
+    yanked: bool,
+    links: Option<SmolStr>,
+    rust_version: Option<SmolStr>,
+}
+
+impl IndexVersionBuilder {
+    /// Creates a builder for the specified crate name and version
+    #[inline]
+    pub fn new(name: impl Into<SmolStr>, version: impl Into<SmolStr>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            deps: Vec::new(),
+            checksum: [0; 32],
+            features: FeatureMap::new(),
+            features2: FeatureMap::new(),
+            yanked: false,
+            links: None,
+            rust_version: None,
+        }
+    }
+
+    /// Adds a dependency
+    #[inline]
+    pub fn dependency(mut self, dep: IndexDependency) -> Self {
+        self.deps.push(dep);
+        self
+    }
+
+    /// Adds a feature and the features and/or dependencies it enables
+    ///
+    /// The same as cargo, features that use the `dep:` or `?/` syntax are
+    /// placed in `features2`, which bumps the index version to 2
+    pub fn feature<I, S>(mut self, name: impl Into<String>, enables: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        let enables: Vec<String> = enables.into_iter().map(Into::into).collect();
+
+        let map = if enables
+            .iter()
+            .any(|e| e.starts_with("dep:") || e.contains("?/"))
+        {
+            &mut self.features2
+        } else {
+            &mut self.features
+        };
+
+        map.insert(name.into(), enables);
+        self
+    }
+
+    /// Sets whether the version is yanked
+    #[inline]
+    pub fn yanked(mut self, yanked: bool) -> Self {
+        self.yanked = yanked;
+        self
+    }
+
+    /// Sets the [links](https://doc.rust-lang.org/cargo/reference/manifest.html#the-links-field)
+    /// for the version
     #[inline]
-    pub fn download_url(&self, index: &crate::index::IndexConfig) -> Option<String> {
-        Some(index.download_url(self.name.as_str().try_into().ok()?, self.version.as_ref()))
+    pub fn links(mut self, links: impl Into<SmolStr>) -> Self {
+        self.links = Some(links.into());
+        self
+    }
+
+    /// Sets the [rust-version](https://doc.rust-lang.org/cargo/reference/manifest.html#the-rust-version-field)
+    /// for the version
+    #[inline]
+    pub fn rust_version(mut self, rv: impl Into<SmolStr>) -> Self {
+        self.rust_version = Some(rv.into());
+        self
+    }
+
+    /// Sets the SHA-256 checksum of the version's .crate file
+    #[inline]
+    pub fn checksum(mut self, checksum: [u8; 32]) -> Self {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Validates the name and version and builds the [`IndexVersion`]
+    pub fn build(self) -> Result<IndexVersion, Error> {
+        let _name: crate::KrateName<'_> = self.name.as_str().try_into()?;
+        let _version: Version = self.version.parse()?;
+
+        let (features2, v) = if self.features2.is_empty() {
+            (None, None)
+        } else {
+            (Some(Arc::new(self.features2)), Some(2))
+        };
+
+        Ok(IndexVersion {
+            name: self.name,
+            version: self.version,
+            deps: self.deps.into(),
+            checksum: Chksum(self.checksum),
+            features: Arc::new(self.features),
+            features2,
+            yanked: self.yanked,
+            links: self.links.map(Box::new),
+            rust_version: self.rust_version,
+            v,
+            extra: None,
+        })
     }
 }
 
 /// A single dependency of a specific crate version
-#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
+#[derive(Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
 pub struct IndexDependency {
     /// Dependency's arbitrary nickname (it may be an alias). Use [`Self::crate_name`] for actual crate name.
     pub name: SmolStr,
@@ -149,6 +462,26 @@ pub struct IndexDependency {
     /// The name of the actual crate, if it was renamed in the crate's manifest
     #[serde(skip_serializing_if = "Option::is_none")]
     pub package: Option<Box<SmolStr>>,
+    /// The [artifact kinds](https://doc.rust-lang.org/cargo/reference/unstable.html#artifact-dependencies)
+    /// requested for an artifact dependency, eg. `bin`, `cdylib`, `staticlib`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact: Option<Box<[String]>>,
+    /// The target triple an artifact dependency is built for, if different
+    /// from the target of the dependent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bindep_target: Option<Box<SmolStr>>,
+    /// True if an artifact dependency also makes the library of the dependency
+    /// available to the dependent
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub lib: bool,
+    /// Whether the dependency is [public](https://rust-lang.github.io/rfcs/3516-public-private-dependencies.html)
+    /// or not, only set by cargo versions that support public dependencies
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public: Option<bool>,
+    /// Fields that are not known to this crate, only populated if the version
+    /// was parsed with [`ParseOptions::preserve_extras`]
+    #[serde(skip)]
+    pub extra: Option<Box<Extras>>,
 }
 
 impl IndexDependency {
@@ -188,7 +521,44 @@ impl IndexDependency {
     /// The kind of the dependency
     #[inline]
     pub fn kind(&self) -> DependencyKind {
-        self.kind.unwrap_or_default()
+        self.kind.clone().unwrap_or_default()
+    }
+
+    /// True if the dependency is of the specified kind, this avoids the clone
+    /// of [`Self::kind`]
+    #[inline]
+    pub fn is_kind(&self, kind: &DependencyKind) -> bool {
+        self.kind.as_ref().unwrap_or(&DependencyKind::Normal) == kind
+    }
+
+    /// True if this dependency applies to the specified target triple
+    ///
+    /// Dependencies without a [`Self::target`] apply to every target. If the
+    /// `cfg-expr` feature is enabled, `cfg()` expressions are evaluated against
+    /// the builtin rustc target of the same name, otherwise, and for targets
+    /// not known to `cfg-expr`, the target must match exactly.
+    pub fn matches_target(&self, target: &str) -> bool {
+        let Some(dep_target) = self.target() else {
+            return true;
+        };
+
+        #[cfg(feature = "cfg-expr")]
+        if dep_target.starts_with("cfg(") {
+            let Some(ti) = cfg_expr::targets::get_builtin_target_by_triple(target) else {
+                return false;
+            };
+
+            let Ok(expr) = cfg_expr::Expression::parse(dep_target) else {
+                return false;
+            };
+
+            return expr.eval(|pred| match pred {
+                cfg_expr::Predicate::Target(tp) => tp.matches(ti),
+                _ => false,
+            });
+        }
+
+        dep_target == target
     }
 
     /// Set if dependency's crate name is different from the `name` (alias)
@@ -197,6 +567,33 @@ impl IndexDependency {
         self.package.as_ref().map(|s| s.as_str())
     }
 
+    /// The artifact kinds for an artifact dependency, `None` if this is a
+    /// normal dependency
+    #[inline]
+    pub fn artifact_kinds(&self) -> Option<&[String]> {
+        self.artifact.as_deref()
+    }
+
+    /// The target an artifact dependency is built for
+    #[inline]
+    pub fn bindep_target(&self) -> Option<&str> {
+        self.bindep_target.as_ref().map(|s| s.as_str())
+    }
+
+    /// If `true`, the library of an artifact dependency is available in
+    /// addition to its artifacts
+    #[inline]
+    pub fn includes_lib(&self) -> bool {
+        self.lib
+    }
+
+    /// True if the dependency is public, ie. its types are exposed as part of
+    /// the dependent's public API
+    #[inline]
+    pub fn is_public(&self) -> bool {
+        self.public.unwrap_or_default()
+    }
+
     /// Returns the name of the crate providing the dependency.
     /// This is equivalent to `name()` unless `self.package()`
     /// is not `None`, in which case it's equal to `self.package()`.
@@ -220,8 +617,7 @@ impl IndexDependency {
 }
 
 /// Section in which this dependency was defined
-#[derive(Debug, Copy, Clone, Serialize, Deserialize, Eq, PartialEq, Hash, Default)]
-#[serde(rename_all = "lowercase")]
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Default)]
 pub enum DependencyKind {
     /// Used at run time
     #[default]
@@ -230,6 +626,124 @@ pub enum DependencyKind {
     Dev,
     /// Used at build time, not available at run time
     Build,
+    /// A kind not known to this crate, eg. one written by a future version
+    /// of cargo or a third party registry, the original value is retained so
+    /// that it is round tripped when serialized
+    Unknown(SmolStr),
+}
+
+impl DependencyKind {
+    /// The string representation of the kind, as it appears in the index
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Normal => "normal",
+            Self::Dev => "dev",
+            Self::Build => "build",
+            Self::Unknown(kind) => kind,
+        }
+    }
+}
+
+impl fmt::Display for DependencyKind {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for DependencyKind {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let kind = SmolStr::deserialize(deserializer)?;
+
+        Ok(match kind.as_str() {
+            "normal" => Self::Normal,
+            "dev" => Self::Dev,
+            "build" => Self::Build,
+            _ => Self::Unknown(kind),
+        })
+    }
+}
+
+impl Serialize for DependencyKind {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+/// A version whose checksum can't be used for verification, see
+/// [`IndexKrate::from_slice_with_report`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChecksumWarning {
+    /// The index of the version in [`IndexKrate::versions`]
+    pub index: usize,
+    /// The version
+    pub version: SmolStr,
+    /// The problem with the checksum
+    pub issue: ChecksumIssue,
+}
+
+/// The reason a [`ChecksumWarning`] was raised
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ChecksumIssue {
+    /// The checksum was all zeroes, which is a placeholder rather than the
+    /// actual checksum of the .crate file
+    Zeroed,
+    /// The checksum was not a valid sha-256 hex string, the version's checksum
+    /// was zeroed
+    Invalid(ChksumParseError),
+}
+
+/// The result of [`IndexVersion::resolve_features`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FeatureResolution {
+    /// The features that are enabled, including implicit features for
+    /// optional dependencies
+    pub features: BTreeSet<String>,
+    /// The optional dependencies that are enabled
+    pub optional_deps: BTreeSet<String>,
+    /// The requested features that don't exist
+    pub missing: Vec<String>,
+}
+
+/// The result of [`IndexKrate::merge`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeSummary {
+    /// The number of versions that were not previously present
+    pub added: usize,
+    /// The number of versions that were present, but differed from the incoming
+    /// version, eg. due to being yanked
+    pub updated: usize,
+}
+
+/// Options for which versions are eligible when matching a version requirement
+/// via [`IndexKrate::versions_matching`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct MatchOptions {
+    /// If true, yanked versions can be matched
+    pub yanked: bool,
+    /// If false, pre-release versions will never be matched, even if the
+    /// requirement would otherwise match them
+    pub pre_release: bool,
+}
+
+impl Default for MatchOptions {
+    /// The same as cargo, yanked versions are ignored and pre-releases are only
+    /// matched if the requirement opts in to them
+    #[inline]
+    fn default() -> Self {
+        Self {
+            yanked: false,
+            pre_release: true,
+        }
+    }
 }
 
 /// A whole crate with all its versions
@@ -240,6 +754,74 @@ pub struct IndexKrate {
 }
 
 impl IndexKrate {
+    /// Creates a [`Self`] from the specified versions
+    ///
+    /// Since the publish order of the versions is unknown, the versions are
+    /// sorted by their semantic version, with any versions that are not
+    /// valid semver placed last, in their original order
+    pub fn from_versions(versions: Vec<IndexVersion>) -> Result<Self, Error> {
+        if versions.is_empty() {
+            return Err(Error::NoCrateVersions);
+        }
+
+        let mut ik = Self { versions };
+        ik.sort_by_version();
+        Ok(ik)
+    }
+
+    /// Sorts the versions of this crate by their semver version, with any
+    /// versions that aren't valid semver placed last
+    ///
+    /// Note this means [`Self::earliest_version`] and [`Self::most_recent_version`]
+    /// no longer reflect publication order
+    #[inline]
+    pub fn sort_by_version(&mut self) {
+        self.versions.sort_by_cached_key(IndexVersion::sort_key);
+    }
+
+    /// Validates the invariants this crate's index entry is expected to uphold
+    ///
+    /// 1. There is at least one version
+    /// 2. Every version has the same name
+    /// 3. Every version is valid semver
+    /// 4. No version appears more than once
+    ///
+    /// Index entries from a registry will always uphold these, but ones that
+    /// are constructed manually or [merged](Self::merge) may not
+    pub fn validate(&self) -> Result<(), crate::error::ValidationError> {
+        use crate::error::ValidationError as Ve;
+
+        let first = self.versions.first().ok_or(Ve::NoVersions)?;
+        let mut seen = std::collections::HashMap::with_capacity(self.versions.len());
+
+        for (index, iv) in self.versions.iter().enumerate() {
+            if iv.name != first.name {
+                return Err(Ve::NameMismatch {
+                    index,
+                    version: iv.version.to_string(),
+                    name: iv.name.to_string(),
+                    expected: first.name.to_string(),
+                });
+            }
+
+            let vers = Version::parse(&iv.version).map_err(|source| Ve::InvalidVersion {
+                index,
+                version: iv.version.to_string(),
+                source,
+            })?;
+
+            if let Some(original) = seen.insert(vers, index) {
+                return Err(Ve::DuplicateVersion {
+                    index,
+                    original,
+                    version: iv.version.to_string(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     /// The highest version as per semantic versioning specification
     ///
     /// Note this may be a pre-release or yanked, use [`Self::highest_normal_version`]
@@ -280,104 +862,901 @@ impl IndexKrate {
             .map(|(v, _vs)| v)
     }
 
-    /// The crate's unique registry name. Case-sensitive, mostly.
-    #[inline]
-    pub fn name(&self) -> &str {
-        &self.versions[0].name
-    }
-
-    /// The last release by date, even if it's yanked or less than highest version.
+    /// Gets the specified version of this crate, if it exists
     ///
-    /// See [`Self::highest_normal_version`]
-    #[inline]
-    pub fn most_recent_version(&self) -> &IndexVersion {
-        &self.versions[self.versions.len() - 1]
+    /// If the versions are [sorted](Self::sort_by_version) this is a binary
+    /// search, otherwise it falls back to a linear search
+    pub fn get_version(&self, version: &Version) -> Option<&IndexVersion> {
+        let found = self
+            .versions
+            .binary_search_by(|iv| match Version::parse(&iv.version) {
+                Ok(vers) => vers.cmp(version),
+                // Invalid versions are sorted last
+                Err(_) => std::cmp::Ordering::Greater,
+            })
+            .ok()
+            .map(|i| &self.versions[i]);
+
+        // If the versions aren't sorted the binary search can only be trusted
+        // if it actually found the version
+        found.or_else(|| {
+            self.versions
+                .iter()
+                .find(|iv| Version::parse(&iv.version).ok().as_ref() == Some(version))
+        })
     }
 
-    /// First version ever published. May be yanked.
-    ///
-    /// It is not guaranteed to be the lowest version number.
+    /// Returns whether the specified version is yanked, or `None` if the
+    /// version doesn't exist
     #[inline]
-    pub fn earliest_version(&self) -> &IndexVersion {
-        &self.versions[0]
+    pub fn is_version_yanked(&self, version: &Version) -> Option<bool> {
+        self.get_version(version).map(IndexVersion::is_yanked)
     }
-}
 
-impl IndexKrate {
-    /// Parse an index file with all of crate's versions.
-    ///
-    /// The file must contain at least one version.
+    /// Gets the versions of this crate that have not been yanked, in publish order
     #[inline]
-    pub fn new(index_path: impl AsRef<crate::Path>) -> Result<Self, Error> {
-        let lines = std::fs::read(index_path.as_ref())?;
-        Self::from_slice(&lines)
+    pub fn unyanked_versions(&self) -> impl Iterator<Item = &IndexVersion> {
+        self.versions.iter().filter(|iv| !iv.is_yanked())
     }
 
-    /// Parse a crate from in-memory JSON-lines data
+    /// Returns true if every version of this crate has been yanked
     #[inline]
-    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
-        let mut dedupe = DedupeContext::default();
-        Self::from_slice_with_context(bytes, &mut dedupe)
+    pub fn all_versions_yanked(&self) -> bool {
+        self.versions.iter().all(IndexVersion::is_yanked)
     }
 
-    /// Parse a [`Self`] file from in-memory JSON data
-    pub(crate) fn from_slice_with_context(
-        mut bytes: &[u8],
-        dedupe: &mut DedupeContext,
-    ) -> Result<Self, Error> {
-        use crate::index::cache::split;
-        // Trim last newline(s) so we don't need to special case the split
-        while bytes.last() == Some(&b'\n') {
-            bytes = &bytes[..bytes.len() - 1];
-        }
-
-        let num_versions = split(bytes, b'\n').count();
-        let mut versions = Vec::with_capacity(num_versions);
-        for line in split(bytes, b'\n') {
-            let mut version: IndexVersion = serde_json::from_slice(line)?;
+    /// Gets the versions of this crate that match the specified requirement
+    ///
+    /// Matching is done via [`matches_cargo`]. Versions that aren't valid
+    /// semver are never matched.
+    ///
+    /// The versions are yielded in the same (publish) order as [`Self::versions`]
+    #[inline]
+    pub fn versions_matching<'s>(
+        &'s self,
+        req: &semver::VersionReq,
+        opts: MatchOptions,
+    ) -> impl Iterator<Item = &'s IndexVersion> + 's {
+        // The requirement is cloned so that the iterator only borrows self
+        let req = req.clone();
+
+        self.versions.iter().filter(move |iv| {
+            if iv.is_yanked() && !opts.yanked {
+                return false;
+            }
 
-            // Many versions have identical dependencies and features
-            dedupe.deps(&mut version.deps);
-            dedupe.features(&mut version.features);
+            let Ok(vers) = iv.version.parse::<Version>() else {
+                return false;
+            };
 
-            if let Some(features2) = &mut version.features2 {
-                dedupe.features(features2);
+            if !vers.pre.is_empty() && !opts.pre_release {
+                return false;
             }
 
-            versions.push(version);
-        }
+            matches_cargo(&req, &vers)
+        })
+    }
 
-        if versions.is_empty() {
-            return Err(Error::NoCrateVersions);
-        }
+    /// Gets the highest version of this crate that matches the specified
+    /// requirement, see [`Self::versions_matching`]
+    #[inline]
+    pub fn highest_matching(
+        &self,
+        req: &semver::VersionReq,
+        opts: MatchOptions,
+    ) -> Option<&IndexVersion> {
+        self.versions_matching(req, opts)
+            .max_by_key(|iv| Version::parse(&iv.version).ok())
+    }
 
-        Ok(Self { versions })
+    /// Gets the lowest version of this crate that matches the specified
+    /// requirement, see [`Self::versions_matching`], eg. for
+    /// `-Z minimal-versions` style resolution
+    ///
+    /// Stable versions are preferred over pre-releases, so a pre-release is
+    /// only returned if no stable version matches. If multiple versions are
+    /// equal, the earliest published one is returned
+    #[inline]
+    pub fn lowest_matching(
+        &self,
+        req: &semver::VersionReq,
+        opts: MatchOptions,
+    ) -> Option<&IndexVersion> {
+        self.versions_matching(req, opts)
+            .filter_map(|iv| Some((iv, Version::parse(&iv.version).ok()?)))
+            .min_by(|(_, a), (_, b)| {
+                b.pre
+                    .is_empty()
+                    .cmp(&a.pre.is_empty())
+                    .then_with(|| a.cmp(b))
+            })
+            .map(|(iv, _)| iv)
     }
 
-    /// Writes this crate into a JSON-lines formatted buffer
+    /// Gets the versions of this crate whose [`IndexVersion::rust_version`] is
+    /// satisfied by the specified rustc version
     ///
-    /// Note this creates its own internal [`std::io::BufWriter`], there is no
-    /// need to wrap it in your own
-    pub fn write_json_lines<W: std::io::Write>(&self, writer: &mut W) -> Result<(), Error> {
-        use std::io::{BufWriter, Write};
+    /// The same as cargo, versions without a `rust_version` are considered
+    /// compatible, as are versions whose `rust_version` could not be parsed.
+    /// Partial versions such as `1.65` are treated as `1.65.0`, and any
+    /// pre-release of `rustc` is ignored, ie. `1.70.0-nightly` satisfies `1.70`
+    #[inline]
+    pub fn versions_for_msrv<'s>(
+        &'s self,
+        rustc: &Version,
+    ) -> impl Iterator<Item = &'s IndexVersion> + 's {
+        let rustc = (rustc.major, rustc.minor, rustc.patch);
+
+        self.versions.iter().filter(move |iv| {
+            let Some(Ok(rv)) = iv.rust_version_parsed() else {
+                return true;
+            };
+
+            (rv.major, rv.minor, rv.patch) <= rustc
+        })
+    }
 
-        let mut w = BufWriter::new(writer);
-        for iv in &self.versions {
-            serde_json::to_writer(&mut w, &iv)?;
-            w.write_all(b"\n")?;
-        }
+    /// The same as [`Self::highest_normal_version`], but only considers versions
+    /// that are compatible with the specified rustc version, see [`Self::versions_for_msrv`]
+    #[inline]
+    pub fn highest_normal_version_for_msrv(&self, rustc: &Version) -> Option<&IndexVersion> {
+        self.versions_for_msrv(rustc)
+            .filter_map(|v| {
+                if v.is_yanked() {
+                    return None;
+                }
+
+                v.version
+                    .parse::<Version>()
+                    .ok()
+                    .filter(|v| v.pre.is_empty())
+                    .map(|vs| (v, vs))
+            })
+            .max_by(|a, b| a.1.cmp(&b.1))
+            .map(|(v, _vs)| v)
+    }
+
+    /// The maximum [index format version](IndexVersion::index_version) of all
+    /// of the versions of this crate
+    ///
+    /// Callers can compare this against [`crate::index::cache::INDEX_V_MAX`] to
+    /// reject entries that use features of the index format this crate doesn't
+    /// understand
+    #[inline]
+    pub fn max_index_version(&self) -> u32 {
+        self.versions
+            .iter()
+            .map(IndexVersion::index_version)
+            .max()
+            .unwrap_or(1)
+    }
+
+    /// Merges the versions from another copy of this crate's index entry, eg.
+    /// an updated one retrieved from an upstream registry, into this one
+    ///
+    /// Versions are considered the same if both their name (case-insensitive)
+    /// and version are equal. If a version exists in both, the incoming one is
+    /// preferred so that changes such as yanking are propagated. Versions that
+    /// only exist in `other` are appended in the order they appear in it, so
+    /// the chronological ordering of [`Self::versions`] is maintained as long
+    /// as `other` is a newer copy of the same entry
+    pub fn merge(&mut self, other: IndexKrate) -> MergeSummary {
+        #[derive(PartialEq, Eq, Hash)]
+        enum VersionKey {
+            Semver(Version),
+            Raw(SmolStr),
+        }
+
+        fn key(iv: &IndexVersion) -> (String, VersionKey) {
+            (
+                iv.name.to_ascii_lowercase(),
+                iv.version
+                    .parse()
+                    .map_or_else(|_| VersionKey::Raw(iv.version.clone()), VersionKey::Semver),
+            )
+        }
+
+        let mut existing: std::collections::HashMap<_, _> = self
+            .versions
+            .iter()
+            .enumerate()
+            .map(|(i, iv)| (key(iv), i))
+            .collect();
+
+        let mut summary = MergeSummary::default();
+
+        for iv in other.versions {
+            let key = key(&iv);
+
+            if let Some(&i) = existing.get(&key) {
+                if self.versions[i] != iv {
+                    self.versions[i] = iv;
+                    summary.updated += 1;
+                }
+            } else {
+                existing.insert(key, self.versions.len());
+                self.versions.push(iv);
+                summary.added += 1;
+            }
+        }
+
+        summary
+    }
+
+    /// The crate's unique registry name. Case-sensitive, mostly.
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.versions[0].name
+    }
+
+    /// The last release by date, even if it's yanked or less than highest version.
+    ///
+    /// See [`Self::highest_normal_version`]
+    #[inline]
+    pub fn most_recent_version(&self) -> &IndexVersion {
+        &self.versions[self.versions.len() - 1]
+    }
+
+    /// First version ever published. May be yanked.
+    ///
+    /// It is not guaranteed to be the lowest version number.
+    #[inline]
+    pub fn earliest_version(&self) -> &IndexVersion {
+        &self.versions[0]
+    }
+}
+
+impl IndexKrate {
+    /// Parse an index file with all of crate's versions.
+    ///
+    /// The file must contain at least one version.
+    #[inline]
+    pub fn new(index_path: impl AsRef<crate::Path>) -> Result<Self, Error> {
+        let path = index_path.as_ref();
+        let file = std::fs::File::open(path).map_err(|err| Error::IoPath(err, path.to_owned()))?;
+        Self::from_reader(std::io::BufReader::new(file))
+    }
+
+    /// Parse a crate from a stream of JSON-lines data
+    ///
+    /// Unlike [`Self::from_slice`], this does not require the entire index
+    /// entry to be in memory at once, reducing peak memory usage for crates
+    /// with a huge number of versions
+    #[inline]
+    pub fn from_reader<R: std::io::BufRead>(reader: R) -> Result<Self, Error> {
+        let mut versions = Vec::new();
+        Self::for_each_version(reader, |iv| versions.push(iv))?;
+
+        if versions.is_empty() {
+            return Err(Error::NoCrateVersions);
+        }
+
+        Ok(Self { versions })
+    }
+
+    /// Parses each version in a stream of JSON-lines data, calling the
+    /// specified callback with each one in order, returning the number of
+    /// versions that were parsed
+    ///
+    /// This is useful if you don't need to retain every version of the crate,
+    /// eg. if you only want to find the highest version that matches some
+    /// criteria
+    pub fn for_each_version<R: std::io::BufRead>(
+        mut reader: R,
+        mut callback: impl FnMut(IndexVersion),
+    ) -> Result<usize, Error> {
+        let mut dedupe = DedupeContext::new();
+        let mut line = Vec::with_capacity(1024);
+        let mut count = 0;
+
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+
+            while matches!(line.last(), Some(b'\n' | b'\r')) {
+                line.pop();
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            callback(IndexVersion::parse_line(
+                &line,
+                &mut dedupe,
+                ParseOptions::default(),
+            )?);
+            count += 1;
+        }
+
+        Ok(count)
+    }
+
+    /// Parse a crate from in-memory JSON-lines data
+    #[inline]
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, Error> {
+        Self::from_slice_with_options(bytes, ParseOptions::default())
+    }
+
+    /// Parse a crate from in-memory JSON-lines data with the specified options
+    #[inline]
+    pub fn from_slice_with_options(bytes: &[u8], opts: ParseOptions) -> Result<Self, Error> {
+        let mut dedupe = DedupeContext::new();
+        Self::from_slice_with_context(bytes, &mut dedupe, opts)
+    }
+
+    /// Parse a crate from in-memory JSON-lines data, reporting versions whose
+    /// checksum can't be used to verify the version's .crate file
+    ///
+    /// Hand-rolled registries and test fixtures sometimes contain placeholder
+    /// checksums. Rather than failing, versions whose checksum is not a valid
+    /// sha-256 hex string are parsed with a [zeroed](Chksum::is_zero) checksum,
+    /// and both those and versions that already had a zeroed checksum are
+    /// reported so that they aren't treated as verifiable
+    pub fn from_slice_with_report(mut bytes: &[u8]) -> Result<(Self, Vec<ChecksumWarning>), Error> {
+        use crate::index::cache::split;
+        while bytes.last() == Some(&b'\n') {
+            bytes = &bytes[..bytes.len() - 1];
+        }
+
+        let mut dedupe = DedupeContext::new();
+        let mut versions = Vec::new();
+        let mut warnings = Vec::new();
+
+        for line in split(bytes, b'\n') {
+            let (version, issue) =
+                match IndexVersion::parse_line(line, &mut dedupe, ParseOptions::default()) {
+                    Ok(iv) => {
+                        let issue = iv.checksum.is_zero().then_some(ChecksumIssue::Zeroed);
+                        (iv, issue)
+                    }
+                    Err(err) => {
+                        let Some((iv, perr)) =
+                            IndexVersion::parse_line_with_invalid_checksum(line, &mut dedupe)
+                        else {
+                            return Err(err);
+                        };
+                        (iv, Some(ChecksumIssue::Invalid(perr)))
+                    }
+                };
+
+            if let Some(issue) = issue {
+                warnings.push(ChecksumWarning {
+                    index: versions.len(),
+                    version: version.version.clone(),
+                    issue,
+                });
+            }
+
+            versions.push(version);
+        }
+
+        if versions.is_empty() {
+            return Err(Error::NoCrateVersions);
+        }
+
+        Ok((Self { versions }, warnings))
+    }
+
+    /// Parse a crate from in-memory JSON-lines data, sharing identical features
+    /// and dependencies with all other crates parsed with the same context
+    ///
+    /// See [`DedupeContext`] for more info
+    #[inline]
+    pub fn from_slice_with_dedupe(bytes: &[u8], dedupe: &mut DedupeContext) -> Result<Self, Error> {
+        Self::from_slice_with_context(bytes, dedupe, ParseOptions::default())
+    }
+
+    /// Parse a [`Self`] file from in-memory JSON data
+    pub(crate) fn from_slice_with_context(
+        mut bytes: &[u8],
+        dedupe: &mut DedupeContext,
+        opts: ParseOptions,
+    ) -> Result<Self, Error> {
+        use crate::index::cache::split;
+        // Trim last newline(s) so we don't need to special case the split
+        while bytes.last() == Some(&b'\n') {
+            bytes = &bytes[..bytes.len() - 1];
+        }
+
+        let num_versions = split(bytes, b'\n').count();
+        let mut versions = Vec::with_capacity(num_versions);
+        for line in split(bytes, b'\n') {
+            versions.push(IndexVersion::parse_line(line, dedupe, opts)?);
+        }
+
+        if versions.is_empty() {
+            return Err(Error::NoCrateVersions);
+        }
+
+        Ok(Self { versions })
+    }
+
+    /// Writes this crate into a JSON-lines formatted buffer
+    ///
+    /// Note this creates its own internal [`std::io::BufWriter`], there is no
+    /// need to wrap it in your own
+    pub fn write_json_lines<W: std::io::Write>(&self, writer: &mut W) -> Result<(), Error> {
+        self.write_json_lines_filtered(writer, |_iv| true)
+    }
+
+    /// Writes this crate into a JSON-lines formatted buffer with the specified
+    /// options
+    pub fn write_json_lines_with_options<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        opts: WriteOptions,
+    ) -> Result<(), Error> {
+        self.write_json_lines_impl(writer, |_iv| true, opts)
+    }
+
+    /// Writes only the versions of this crate that pass the specified filter
+    /// into a JSON-lines formatted buffer
+    ///
+    /// Since an index entry with no versions is invalid, this fails with
+    /// [`Error::NoCrateVersions`] without writing anything if the filter
+    /// excludes every version
+    pub fn write_json_lines_filtered<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        filter: impl Fn(&IndexVersion) -> bool,
+    ) -> Result<(), Error> {
+        self.write_json_lines_impl(writer, filter, WriteOptions::default())
+    }
+
+    /// Async version of [`Self::write_json_lines`]
+    ///
+    /// The output is identical to the sync version, it is formatted into an
+    /// internal buffer that is then written in one go, there is no need to
+    /// wrap the writer in your own buffering
+    #[cfg(feature = "tokio")]
+    pub async fn write_json_lines_async<W: tokio::io::AsyncWrite + Unpin>(
+        &self,
+        writer: &mut W,
+    ) -> Result<(), Error> {
+        use tokio::io::AsyncWriteExt;
+
+        let mut buffer = Vec::new();
+        self.write_json_lines(&mut buffer)?;
+
+        writer.write_all(&buffer).await?;
+        Ok(writer.flush().await?)
+    }
+
+    fn write_json_lines_impl<W: std::io::Write>(
+        &self,
+        writer: &mut W,
+        filter: impl Fn(&IndexVersion) -> bool,
+        opts: WriteOptions,
+    ) -> Result<(), Error> {
+        use std::io::{BufWriter, Write};
+
+        if !self.versions.iter().any(&filter) {
+            return Err(Error::NoCrateVersions);
+        }
+
+        let mut w = BufWriter::new(writer);
+        for iv in self.versions.iter().filter(|iv| filter(iv)) {
+            if opts.split_features2 {
+                serde_json::to_writer(&mut w, &SplitFeatures(iv))?;
+            } else {
+                serde_json::to_writer(&mut w, &iv)?;
+            }
+            w.write_all(b"\n")?;
+        }
 
         Ok(w.flush()?)
     }
 }
 
+/// Returns true if the version satisfies the requirement, using the same
+/// semantics as cargo's resolver
+///
+/// 1. Build metadata is ignored
+/// 2. A pre-release version only matches if the requirement has a comparator
+///    with a pre-release on the same `major.minor.patch`, eg. `^1.0.0-alpha`
+///    matches `1.0.0-beta` but not `1.0.1-beta`
+/// 3. Requirements without a pre-release, including `*`, never match a
+///    pre-release version
+///
+/// These are the rules implemented by [`semver::VersionReq::matches`], this
+/// function exists so that there is a single place that all matching in this
+/// crate goes through, should cargo's resolver ever diverge
+#[inline]
+pub fn matches_cargo(req: &semver::VersionReq, version: &Version) -> bool {
+    req.matches(version)
+}
+
+/// Parses a `rust-version`, which unlike normal semver versions, may omit the
+/// minor and patch components, and may not have a pre-release or build metadata
+fn parse_rust_version(rv: &str) -> Result<Version, Error> {
+    let invalid = || Error::InvalidRustVersion(rv.to_owned());
+
+    let trimmed = rv.trim();
+    if trimmed.contains(['-', '+']) {
+        return Err(invalid());
+    }
+
+    let mut parts = trimmed.splitn(3, '.');
+    let mut component = |required: bool| -> Result<u64, Error> {
+        match parts.next() {
+            Some(c) => c.parse().map_err(|_err| invalid()),
+            None if required => Err(invalid()),
+            None => Ok(0),
+        }
+    };
+
+    let major = component(true)?;
+    let minor = component(false)?;
+    let patch = component(false)?;
+
+    Ok(Version::new(major, minor, patch))
+}
+
+/// JSON object fields that are not known to this crate
+///
+/// These are preserved so that index entries using newer additions to the
+/// index format can be round tripped without losing information
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct Extras(pub serde_json::Map<String, serde_json::Value>);
+
+impl std::hash::Hash for Extras {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        // Values aren't hashable, but the keys are enough to be consistent with Eq
+        for key in self.0.keys() {
+            key.hash(state);
+        }
+    }
+}
+
+impl Extras {
+    /// Removes the known fields from the specified object, returning the
+    /// remainder, if any
+    fn from_object(
+        obj: &serde_json::Map<String, serde_json::Value>,
+        known: &[&str],
+    ) -> Option<Box<Self>> {
+        let extra: serde_json::Map<_, _> = obj
+            .iter()
+            .filter(|(k, _v)| !known.contains(&k.as_str()))
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+
+        (!extra.is_empty()).then(|| Box::new(Self(extra)))
+    }
+}
+
+/// Options for parsing index entries
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ParseOptions {
+    /// Preserves any fields that are not known to this crate in [`IndexVersion::extra`]
+    /// and [`IndexDependency::extra`] so that they are round tripped when
+    /// serializing.
+    ///
+    /// This is off by default as it uses significantly more memory and time
+    /// to parse
+    pub preserve_extras: bool,
+}
+
+/// Options for writing index entries
+#[derive(Copy, Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// Moves any features that use namespaced (`dep:`) or weak (`?/`)
+    /// dependency syntax from `features` into `features2`, setting the index
+    /// format version to 2, the same as crates.io does when publishing.
+    ///
+    /// Older cargo versions fail to parse entries that have such features in
+    /// the `features` map, so this should be enabled when writing entries that
+    /// will be read by cargo, eg. in a local registry
+    pub split_features2: bool,
+}
+
+/// The fields of [`IndexVersion`], used to split off the [`Extras`]
+const VERSION_FIELDS: &[&str] = &[
+    "name",
+    "vers",
+    "deps",
+    "cksum",
+    "features",
+    "features2",
+    "yanked",
+    "links",
+    "rust_version",
+    "v",
+];
+
+/// The fields of [`IndexDependency`], used to split off the [`Extras`]
+const DEPENDENCY_FIELDS: &[&str] = &[
+    "name",
+    "req",
+    "features",
+    "optional",
+    "default_features",
+    "target",
+    "kind",
+    "package",
+    "artifact",
+    "bindep_target",
+    "lib",
+    "public",
+];
+
+impl IndexVersion {
+    /// Parses a single JSON line, deduplicating its dependencies and features
+    fn parse_line(
+        line: &[u8],
+        dedupe: &mut DedupeContext,
+        opts: ParseOptions,
+    ) -> Result<Self, Error> {
+        let mut version = if opts.preserve_extras {
+            Self::from_slice_with_extras(line)?
+        } else {
+            serde_json::from_slice(line)?
+        };
+
+        version.dedupe(dedupe);
+        Ok(version)
+    }
+
+    /// Many versions have identical dependencies and features
+    #[inline]
+    fn dedupe(&mut self, dedupe: &mut DedupeContext) {
+        dedupe.deps(&mut self.deps);
+        dedupe.features(&mut self.features);
+
+        if let Some(features2) = &mut self.features2 {
+            dedupe.features(features2);
+        }
+    }
+
+    /// Parses a version whose checksum is not a valid sha-256 hex string,
+    /// replacing the checksum with zeroes
+    ///
+    /// Returns `None` if the line can't be parsed for any other reason
+    fn parse_line_with_invalid_checksum(
+        line: &[u8],
+        dedupe: &mut DedupeContext,
+    ) -> Option<(Self, ChksumParseError)> {
+        let mut value: serde_json::Value = serde_json::from_slice(line).ok()?;
+        let cksum = value.get_mut("cksum")?;
+        let err = cksum.as_str()?.parse::<Chksum>().err()?;
+        *cksum = serde_json::Value::String("0".repeat(64));
+
+        let mut version: Self = serde_json::from_value(value).ok()?;
+        version.dedupe(dedupe);
+        Some((version, err))
+    }
+
+    /// Parses a single JSON line, preserving any unknown fields
+    fn from_slice_with_extras(line: &[u8]) -> Result<Self, Error> {
+        let value: serde_json::Value = serde_json::from_slice(line)?;
+        let mut version = Self::deserialize(&value)?;
+
+        let Some(obj) = value.as_object() else {
+            return Ok(version);
+        };
+
+        version.extra = Extras::from_object(obj, VERSION_FIELDS);
+
+        if let Some(deps) = obj.get("deps").and_then(|d| d.as_array()) {
+            // The deps were just deserialized so we are the only owner
+            if let Some(ideps) = Arc::get_mut(&mut version.deps) {
+                for (idep, dep) in ideps.iter_mut().zip(deps) {
+                    if let Some(dep) = dep.as_object() {
+                        idep.extra = Extras::from_object(dep, DEPENDENCY_FIELDS);
+                    }
+                }
+            }
+        }
+
+        Ok(version)
+    }
+}
+
+impl Serialize for IndexVersion {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.serialize_repr(serializer, false)
+    }
+}
+
+/// Serializes an [`IndexVersion`] with [`WriteOptions::split_features2`]
+pub(crate) struct SplitFeatures<'iv>(pub(crate) &'iv IndexVersion);
+
+impl Serialize for SplitFeatures<'_> {
+    #[inline]
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        self.0.serialize_repr(serializer, true)
+    }
+}
+
+impl IndexVersion {
+    /// Splits the features that use namespaced (`dep:`) or weak (`?/`)
+    /// dependency syntax out of `features` and into `features2`, returning
+    /// `None` if there are no such features in `features`
+    fn split_features2(&self) -> Option<(FeatureMap, FeatureMap)> {
+        let is_v2 = |values: &Vec<String>| {
+            values
+                .iter()
+                .any(|v| v.starts_with("dep:") || v.contains("?/"))
+        };
+
+        if !self.features.values().any(is_v2) {
+            return None;
+        }
+
+        let (features2, features): (FeatureMap, FeatureMap) = self
+            .features
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .partition(|(_k, v)| is_v2(v));
+
+        let mut merged = self.features2.as_deref().cloned().unwrap_or_default();
+        merged.extend(features2);
+
+        Some((features, merged))
+    }
+
+    fn serialize_repr<S>(&self, serializer: S, split_features2: bool) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        #[derive(Serialize)]
+        struct Repr<'iv> {
+            name: &'iv str,
+            vers: &'iv str,
+            deps: &'iv [IndexDependency],
+            cksum: &'iv Chksum,
+            features: &'iv FeatureMap,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            features2: Option<&'iv FeatureMap>,
+            yanked: bool,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            links: Option<&'iv str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            rust_version: Option<&'iv str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            v: Option<u32>,
+            #[serde(flatten)]
+            extra: Option<&'iv Extras>,
+        }
+
+        let split = if split_features2 {
+            self.split_features2()
+        } else {
+            None
+        };
+
+        let (features, features2, v) = match &split {
+            Some((features, features2)) => {
+                (features, Some(features2), Some(self.index_version().max(2)))
+            }
+            None => (&*self.features, self.features2.as_deref(), self.v),
+        };
+
+        Repr {
+            name: &self.name,
+            vers: &self.version,
+            deps: &self.deps,
+            cksum: &self.checksum,
+            features,
+            features2,
+            yanked: self.yanked,
+            links: self.links(),
+            rust_version: self.rust_version(),
+            v,
+            extra: self.extra.as_deref(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl Serialize for IndexDependency {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        #[derive(Serialize)]
+        struct Repr<'id> {
+            name: &'id str,
+            req: &'id str,
+            features: &'id [String],
+            optional: bool,
+            default_features: bool,
+            target: Option<&'id str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            kind: Option<&'id DependencyKind>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            package: Option<&'id str>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            artifact: Option<&'id [String]>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            bindep_target: Option<&'id str>,
+            #[serde(skip_serializing_if = "std::ops::Not::not")]
+            lib: bool,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            public: Option<bool>,
+            #[serde(flatten)]
+            extra: Option<&'id Extras>,
+        }
+
+        Repr {
+            name: &self.name,
+            req: &self.req,
+            features: &self.features,
+            optional: self.optional,
+            default_features: self.default_features,
+            target: self.target(),
+            kind: self.kind.as_ref(),
+            package: self.package(),
+            artifact: self.artifact_kinds(),
+            bindep_target: self.bindep_target(),
+            lib: self.lib,
+            public: self.public,
+            extra: self.extra.as_deref(),
+        }
+        .serialize(serializer)
+    }
+}
+
 /// A SHA-256 checksum, this is used by cargo to verify the contents of a crate's
 /// tarball
-#[derive(Clone, Eq, PartialEq)]
+///
+/// Can be parsed from a 64 character hex string via [`str::parse`] or
+/// [`TryFrom<&str>`], or created directly from the raw digest bytes
+#[derive(Clone, Eq, PartialEq, Hash, PartialOrd, Ord)]
 pub struct Chksum(pub [u8; 32]);
 
 use std::fmt;
 
+impl Chksum {
+    /// Returns true if the checksum is all zeroes, which is never the actual
+    /// checksum of a .crate file, but rather a placeholder
+    #[inline]
+    pub fn is_zero(&self) -> bool {
+        self.0 == [0; 32]
+    }
+
+    /// Gets the lowercase hex string representation of the checksum
+    #[inline]
+    pub fn to_hex_string(&self) -> String {
+        let mut hex = [0; 64];
+        crate::utils::encode_hex(&self.0, &mut hex).to_owned()
+    }
+}
+
+impl From<[u8; 32]> for Chksum {
+    #[inline]
+    fn from(digest: [u8; 32]) -> Self {
+        Self(digest)
+    }
+}
+
+impl AsRef<[u8]> for Chksum {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl<'s> TryFrom<&'s str> for Chksum {
+    type Error = ChksumParseError;
+
+    #[inline]
+    fn try_from(s: &'s str) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
 impl fmt::Debug for Chksum {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let mut hex = [0; 64];
@@ -397,12 +1776,17 @@ impl fmt::Display for Chksum {
 }
 
 /// Errors that can occur parsing a sha-256 hex string
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ChksumParseError {
     /// The checksum string had an invalid length
     InvalidLength(usize),
     /// The checksum string contained a non-hex character
-    InvalidValue(char),
+    InvalidValue {
+        /// The invalid character
+        invalid: char,
+        /// The byte index of the character in the provided string
+        index: usize,
+    },
 }
 
 impl std::error::Error for ChksumParseError {}
@@ -413,7 +1797,9 @@ impl fmt::Display for ChksumParseError {
             Self::InvalidLength(len) => {
                 write!(f, "expected string with length 64 but got length {len}")
             }
-            Self::InvalidValue(c) => write!(f, "encountered non-hex character '{c}'"),
+            Self::InvalidValue { invalid, index } => {
+                write!(f, "encountered non-hex character '{invalid}' @ {index}")
+            }
         }
     }
 }
@@ -421,34 +1807,21 @@ impl fmt::Display for ChksumParseError {
 impl std::str::FromStr for Chksum {
     type Err = ChksumParseError;
 
+    #[inline]
     fn from_str(data: &str) -> Result<Self, Self::Err> {
-        if data.len() != 64 {
-            return Err(ChksumParseError::InvalidLength(data.len()));
-        }
-
-        let mut array = [0u8; 32];
-
-        for (ind, chunk) in data.as_bytes().chunks(2).enumerate() {
-            #[inline]
-            fn parse_hex(b: u8) -> Result<u8, ChksumParseError> {
-                Ok(match b {
-                    b'A'..=b'F' => b - b'A' + 10,
-                    b'a'..=b'f' => b - b'a' + 10,
-                    b'0'..=b'9' => b - b'0',
-                    c => {
-                        return Err(ChksumParseError::InvalidValue(c as char));
-                    }
-                })
-            }
+        Ok(Self(crate::utils::decode_hex_array(data)?))
+    }
+}
 
-            let mut cur = parse_hex(chunk[0])?;
-            cur <<= 4;
-            cur |= parse_hex(chunk[1])?;
+impl From<crate::error::HexError> for ChksumParseError {
+    #[inline]
+    fn from(err: crate::error::HexError) -> Self {
+        use crate::error::HexError;
 
-            array[ind] = cur;
+        match err {
+            HexError::InvalidLength { len, .. } => Self::InvalidLength(len),
+            HexError::InvalidCharacter { invalid, index } => Self::InvalidValue { invalid, index },
         }
-
-        Ok(Self(array))
     }
 }
 
@@ -472,10 +1845,12 @@ impl<'de> Deserialize<'de> for Chksum {
                     ChksumParseError::InvalidLength(len) => {
                         serde::de::Error::invalid_length(len, &"a string with 64 characters")
                     }
-                    ChksumParseError::InvalidValue(c) => serde::de::Error::invalid_value(
-                        serde::de::Unexpected::Char(c),
-                        &"a hexadecimal character",
-                    ),
+                    ChksumParseError::InvalidValue { invalid, .. } => {
+                        serde::de::Error::invalid_value(
+                            serde::de::Unexpected::Char(invalid),
+                            &"a hexadecimal character",
+                        )
+                    }
                 })
             }
 
@@ -527,4 +1902,1104 @@ mod test {
         assert_eq!(ik.highest_version().version, "0.5.0");
         assert_eq!(ik.highest_normal_version().unwrap().version, "0.4.0");
     }
+
+    /// Validates the `v` field is preserved when round tripping
+    #[test]
+    fn preserves_index_version() {
+        const LINES: &str = concat!(
+            r#"{"name":"tame-index","vers":"0.1.0","deps":[{"name":"serde","req":"^1.0","features":["derive"],"optional":true,"default_features":true,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false,"rust_version":"1.67.0"}"#,
+            "\n",
+            r#"{"name":"tame-index","vers":"0.2.0","deps":[{"name":"serde","req":"^1.0","features":["derive"],"optional":true,"default_features":true,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"features2":{"serde":["dep:serde"]},"yanked":false,"rust_version":"1.67.0","v":2}"#,
+            "\n",
+        );
+
+        let ik = super::IndexKrate::from_slice(LINES.as_bytes()).unwrap();
+        assert_eq!(ik.versions[0].v, None);
+        assert_eq!(ik.versions[0].index_version(), 1);
+        assert_eq!(ik.versions[1].v, Some(2));
+        assert_eq!(ik.max_index_version(), 2);
+        assert!(ik.max_index_version() <= crate::index::cache::INDEX_V_MAX);
+
+        let mut out = Vec::new();
+        ik.write_json_lines(&mut out).unwrap();
+        assert_eq!(LINES, std::str::from_utf8(&out).unwrap());
+    }
+
+    /// Validates artifact dependency fields are parsed, and only serialized if present
+    #[test]
+    fn artifact_dependencies() {
+        const LINE: &str = r#"{"name":"bindeps","vers":"0.1.0","deps":[{"name":"cc","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"build"},{"name":"wasm-bin","req":"^0.2","features":[],"optional":false,"default_features":true,"target":null,"kind":"build","artifact":["bin","cdylib"],"bindep_target":"wasm32-unknown-unknown","lib":true}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#;
+
+        let ik = super::IndexKrate::from_slice(LINE.as_bytes()).unwrap();
+        let deps = ik.versions[0].dependencies();
+
+        assert!(deps[0].artifact_kinds().is_none());
+        assert!(deps[0].bindep_target().is_none());
+        assert!(!deps[0].includes_lib());
+
+        assert_eq!(
+            deps[1].artifact_kinds().unwrap(),
+            &["bin".to_owned(), "cdylib".to_owned()]
+        );
+        assert_eq!(deps[1].bindep_target(), Some("wasm32-unknown-unknown"));
+        assert!(deps[1].includes_lib());
+
+        let mut out = Vec::new();
+        ik.write_json_lines(&mut out).unwrap();
+        assert_eq!(format!("{LINE}\n"), std::str::from_utf8(&out).unwrap());
+    }
+
+    /// Validates an invalid crate name is reported rather than hidden when
+    /// getting a download url
+    #[test]
+    fn download_url_invalid_name() {
+        let ic = crate::index::IndexConfig {
+            dl: "https://crates.io/api/v1/crates".into(),
+            api: None,
+        };
+
+        assert_eq!(
+            super::IndexVersion::fake("valid", "1.0.0")
+                .download_url(&ic)
+                .unwrap(),
+            "https://static.crates.io/crates/valid/valid-1.0.0.crate"
+        );
+        assert!(matches!(
+            super::IndexVersion::fake("trailing ", "1.0.0").download_url(&ic),
+            Err(crate::Error::InvalidKrateName(
+                crate::error::InvalidKrateName::InvalidCharacter {
+                    invalid: ' ',
+                    index: 8
+                }
+            ))
+        ));
+    }
+
+    /// Validates the `{sha256-checksum}` marker is substituted with the checksum
+    /// of the version
+    #[test]
+    fn download_url_checksum() {
+        let ic = crate::index::IndexConfig {
+            dl: "https://dl.example.com/{lowerprefix}/{crate}/{sha256-checksum}.crate".into(),
+            api: None,
+        };
+
+        let mut iv = super::IndexVersion::fake("Checked", "1.0.0");
+        iv.checksum.0[0] = 0xab;
+        iv.checksum.0[31] = 0x01;
+
+        let url = iv.download_url(&ic).unwrap();
+        assert_eq!(
+            url,
+            format!("https://dl.example.com/ch/ec/Checked/{}.crate", iv.checksum)
+        );
+        assert!(url.contains("/ab00") && url.ends_with("01.crate"));
+
+        // The marker is left as is if the checksum isn't known
+        assert_eq!(
+            ic.download_url("Checked".try_into().unwrap(), "1.0.0"),
+            "https://dl.example.com/ch/ec/Checked/{sha256-checksum}.crate"
+        );
+    }
+
+    /// Validates merging adds new versions in order and updates existing ones
+    #[test]
+    fn merge() {
+        use super::{IndexKrate, IndexVersion as iv, MergeSummary};
+
+        let mut ik = IndexKrate {
+            versions: vec![iv::fake("merge", "0.1.0"), iv::fake("merge", "0.2.0")],
+        };
+
+        let mut yanked = iv::fake("Merge", "0.1.0");
+        yanked.yanked = true;
+
+        let upstream = IndexKrate {
+            versions: vec![
+                yanked,
+                iv::fake("merge", "0.2.0"),
+                iv::fake("merge", "0.3.0"),
+                iv::fake("merge", "0.2.1"),
+            ],
+        };
+
+        assert_eq!(
+            ik.merge(upstream.clone()),
+            MergeSummary {
+                added: 2,
+                updated: 1
+            }
+        );
+        assert_eq!(
+            ik.versions
+                .iter()
+                .map(|v| v.version.as_str())
+                .collect::<Vec<_>>(),
+            ["0.1.0", "0.2.0", "0.3.0", "0.2.1"]
+        );
+        assert!(ik.versions[0].is_yanked());
+        assert_eq!(ik, upstream);
+
+        assert_eq!(ik.merge(upstream), MergeSummary::default());
+    }
+
+    /// Validates invariant violations are reported with the offending version,
+    /// and that sorting orders by semver
+    #[test]
+    fn validate_and_sort() {
+        use super::{IndexKrate, IndexVersion as iv};
+        use crate::error::ValidationError as Ve;
+
+        let mut ik = IndexKrate {
+            versions: vec![
+                iv::fake("valid", "0.2.0"),
+                iv::fake("valid", "0.10.0"),
+                iv::fake("valid", "0.2.0-alpha.1"),
+                iv::fake("valid", "0.9.1"),
+            ],
+        };
+        ik.validate().unwrap();
+
+        ik.sort_by_version();
+        assert_eq!(
+            ik.versions
+                .iter()
+                .map(|v| v.version.as_str())
+                .collect::<Vec<_>>(),
+            ["0.2.0-alpha.1", "0.2.0", "0.9.1", "0.10.0"]
+        );
+        assert_eq!(
+            ik.versions[0].cmp_by_version(&ik.versions[1]),
+            std::cmp::Ordering::Less
+        );
+        assert_eq!(
+            ik.versions.iter().max_by(|a, b| a.cmp_by_version(b)),
+            Some(ik.highest_version())
+        );
+        ik.validate().unwrap();
+
+        assert!(matches!(
+            IndexKrate {
+                versions: Vec::new()
+            }
+            .validate(),
+            Err(Ve::NoVersions)
+        ));
+
+        let mut mismatch = ik.clone();
+        mismatch.versions.push(iv::fake("Valid", "1.0.0"));
+        assert!(matches!(
+            mismatch.validate(),
+            Err(Ve::NameMismatch { index: 4, .. })
+        ));
+
+        let mut invalid = ik.clone();
+        invalid.versions.insert(1, iv::fake("valid", "1.0"));
+        assert!(matches!(
+            invalid.validate(),
+            Err(Ve::InvalidVersion { index: 1, .. })
+        ));
+        invalid.sort_by_version();
+        assert_eq!(invalid.versions[4].version, "1.0");
+
+        let mut duplicate = ik;
+        duplicate.versions.push(iv::fake("valid", "0.9.1"));
+        let err = duplicate.validate().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "version '0.9.1' @ 4 is a duplicate of the version @ 2"
+        );
+    }
+
+    /// Validates yank helpers for fully yanked, partially yanked, and
+    /// pre-release heavy crates, both sorted and unsorted
+    #[test]
+    fn yanked() {
+        use super::{IndexKrate, IndexVersion as iv};
+        use semver::Version;
+
+        let make = |versions: &[(&str, bool)]| IndexKrate {
+            versions: versions
+                .iter()
+                .map(|(v, yanked)| {
+                    let mut iv = iv::fake("yanked", *v);
+                    iv.yanked = *yanked;
+                    iv
+                })
+                .collect(),
+        };
+        let v = |s: &str| Version::parse(s).unwrap();
+
+        let full = make(&[("0.1.0", true), ("0.2.0", true)]);
+        assert!(full.all_versions_yanked());
+        assert_eq!(full.unyanked_versions().count(), 0);
+        assert_eq!(full.is_version_yanked(&v("0.1.0")), Some(true));
+        assert_eq!(full.is_version_yanked(&v("0.3.0")), None);
+
+        let mut partial = make(&[
+            ("1.0.0", false),
+            ("1.0.0-alpha.1", true),
+            ("1.0.0-alpha.10", false),
+            ("1.0.0-alpha.2", true),
+            ("0.9.0", false),
+            ("1.0.1", true),
+        ]);
+
+        for _ in 0..2 {
+            assert!(!partial.all_versions_yanked());
+            assert_eq!(partial.unyanked_versions().count(), 3);
+
+            assert_eq!(partial.is_version_yanked(&v("1.0.0-alpha.1")), Some(true));
+            assert_eq!(partial.is_version_yanked(&v("1.0.0-alpha.10")), Some(false));
+            assert_eq!(partial.is_version_yanked(&v("1.0.0-alpha.2")), Some(true));
+            assert_eq!(partial.is_version_yanked(&v("1.0.0-alpha.3")), None);
+            assert_eq!(partial.is_version_yanked(&v("1.0.1")), Some(true));
+
+            for iv in &partial.versions {
+                assert!(std::ptr::eq(
+                    partial.get_version(&v(&iv.version)).unwrap(),
+                    iv
+                ));
+            }
+
+            partial.sort_by_version();
+        }
+    }
+
+    /// Validates namespaced and weak dependency features are split into
+    /// `features2` when requested
+    #[test]
+    fn split_features2() {
+        const UNSPLIT: &str = r#"{"name":"split","vers":"0.1.0","deps":[],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"default":["std"],"serde":["dep:serde"],"std":[],"weak":["serde?/std"]},"features2":{"other":["dep:other"]},"yanked":false}"#;
+        const SPLIT: &str = r#"{"name":"split","vers":"0.1.0","deps":[],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"default":["std"],"std":[]},"features2":{"other":["dep:other"],"serde":["dep:serde"],"weak":["serde?/std"]},"yanked":false,"v":2}"#;
+        const PLAIN: &str = r#"{"name":"split","vers":"0.2.0","deps":[],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"default":["std"],"std":[]},"yanked":false}"#;
+
+        let ik = super::IndexKrate::from_slice(format!("{UNSPLIT}\n{PLAIN}").as_bytes()).unwrap();
+        let opts = super::WriteOptions {
+            split_features2: true,
+        };
+
+        let mut out = Vec::new();
+        ik.write_json_lines(&mut out).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            format!("{UNSPLIT}\n{PLAIN}\n")
+        );
+
+        out.clear();
+        ik.write_json_lines_with_options(&mut out, opts).unwrap();
+        assert_eq!(
+            std::str::from_utf8(&out).unwrap(),
+            format!("{SPLIT}\n{PLAIN}\n")
+        );
+
+        let split = super::IndexKrate::from_slice(&out).unwrap();
+        assert_eq!(
+            split.versions[0]
+                .features()
+                .collect::<std::collections::BTreeMap<_, _>>(),
+            ik.versions[0]
+                .features()
+                .collect::<std::collections::BTreeMap<_, _>>()
+        );
+
+        let mut cache = Vec::new();
+        ik.write_cache_entry_with_options(&mut cache, "etag: \"split\"", opts)
+            .unwrap();
+        let entry = crate::index::cache::ValidCacheEntry::read(&cache).unwrap();
+        assert_eq!(entry.to_krate(None).unwrap().unwrap(), split);
+    }
+
+    /// Validates dependencies can be filtered by kind and target
+    #[test]
+    fn filter_deps() {
+        const LINE: &str = r#"{"name":"filter","vers":"0.1.0","deps":[{"name":"normal","req":"^1","features":[],"optional":false,"default_features":true,"target":null},{"name":"build","req":"^1","features":[],"optional":false,"default_features":true,"target":null,"kind":"build"},{"name":"dev","req":"^1","features":[],"optional":false,"default_features":true,"target":null,"kind":"dev"},{"name":"triple","req":"^1","features":[],"optional":false,"default_features":true,"target":"x86_64-pc-windows-msvc","kind":"normal"},{"name":"windows","req":"^1","features":[],"optional":false,"default_features":true,"target":"cfg(windows)","kind":"normal"},{"name":"any","req":"^1","features":[],"optional":false,"default_features":true,"target":"cfg(any(unix, target_arch = \"wasm32\"))","kind":"build"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#;
+
+        let ik = super::IndexKrate::from_slice(LINE.as_bytes()).unwrap();
+        let iv = &ik.versions[0];
+
+        let names = |deps: &mut dyn Iterator<Item = &super::IndexDependency>| {
+            deps.map(|dep| dep.name.to_string()).collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            names(&mut iv.normal_deps()),
+            ["normal", "triple", "windows"]
+        );
+        assert_eq!(names(&mut iv.build_deps()), ["build", "any"]);
+        assert_eq!(names(&mut iv.dev_deps()), ["dev"]);
+
+        let cfg_expr = cfg!(feature = "cfg-expr");
+
+        let mut expected = vec!["normal", "build", "dev", "triple"];
+        if cfg_expr {
+            expected.push("windows");
+        }
+        assert_eq!(
+            names(&mut iv.deps_for_target("x86_64-pc-windows-msvc")),
+            expected
+        );
+
+        let mut expected = vec!["normal", "build", "dev"];
+        if cfg_expr {
+            expected.push("any");
+        }
+        assert_eq!(
+            names(&mut iv.deps_for_target("x86_64-unknown-linux-gnu")),
+            expected
+        );
+        assert_eq!(
+            names(&mut iv.deps_for_target("wasm32-unknown-unknown")),
+            expected
+        );
+    }
+
+    /// Validates feature resolution for implicit, namespaced, and weak
+    /// dependency features, using entries modeled after real crates.io entries
+    #[test]
+    fn resolve_features() {
+        use std::collections::BTreeSet;
+
+        // Older style crate relying on implicit optional dependency features
+        const IMPLICIT: &str = r#"{"name":"implicit","vers":"0.4.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":true,"default_features":true,"target":null,"kind":"normal"},{"name":"time","req":"^0.1","features":[],"optional":true,"default_features":true,"target":null,"kind":"normal"},{"name":"num-traits","req":"^0.2","features":[],"optional":false,"default_features":false,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"clock":["std","time"],"default":["clock","std"],"std":[],"unstable":["serde/derive"]},"yanked":false}"#;
+        // Newer style crate using namespaced and weak dependency features
+        const NAMESPACED: &str = r#"{"name":"namespaced","vers":"1.0.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":true,"default_features":false,"target":null,"kind":"normal"},{"name":"serde_json","req":"^1.0","features":[],"optional":true,"default_features":false,"target":null,"kind":"normal"},{"name":"tracing","req":"^0.1","features":[],"optional":true,"default_features":false,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"default":["std"],"std":["serde?/std","serde_json?/std"]},"features2":{"json":["dep:serde_json","serde"],"serde":["dep:serde"]},"yanked":false,"v":2}"#;
+
+        let set = |items: &[&str]| {
+            items
+                .iter()
+                .map(|s| (*s).to_owned())
+                .collect::<BTreeSet<_>>()
+        };
+
+        let implicit = super::IndexKrate::from_slice(IMPLICIT.as_bytes()).unwrap();
+        let implicit = &implicit.versions[0];
+
+        let res = implicit.resolve_features(&[], true);
+        assert_eq!(res.features, set(&["clock", "default", "std", "time"]));
+        assert_eq!(res.optional_deps, set(&["time"]));
+        assert!(res.missing.is_empty());
+
+        let res = implicit.resolve_features(&["unstable", "nope"], false);
+        assert_eq!(res.features, set(&["serde", "unstable"]));
+        assert_eq!(res.optional_deps, set(&["serde"]));
+        assert_eq!(res.missing, ["nope"]);
+
+        // num-traits is not optional, so it isn't a feature
+        let res = implicit.resolve_features(&["num-traits", "serde"], false);
+        assert_eq!(res.features, set(&["serde"]));
+        assert_eq!(res.missing, ["num-traits"]);
+
+        let namespaced = super::IndexKrate::from_slice(NAMESPACED.as_bytes()).unwrap();
+        let namespaced = &namespaced.versions[0];
+
+        // Weak features don't enable the optional dependencies
+        let res = namespaced.resolve_features(&[], true);
+        assert_eq!(res.features, set(&["default", "std"]));
+        assert!(res.optional_deps.is_empty());
+
+        let res = namespaced.resolve_features(&["json"], false);
+        assert_eq!(res.features, set(&["json", "serde"]));
+        assert_eq!(res.optional_deps, set(&["serde", "serde_json"]));
+
+        // `dep:` hides the implicit feature, but tracing has no `dep:` usage
+        let res = namespaced.resolve_features(&["serde_json", "tracing"], false);
+        assert_eq!(res.features, set(&["tracing"]));
+        assert_eq!(res.optional_deps, set(&["tracing"]));
+        assert_eq!(res.missing, ["serde_json"]);
+
+        // Dependency features can be requested directly
+        let res = namespaced.resolve_features(&["serde/derive"], false);
+        assert_eq!(res.features, set(&["serde"]));
+        assert_eq!(res.optional_deps, set(&["serde"]));
+    }
+
+    /// Validates the async writers produce identical output to the sync ones
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_writes() {
+        let ik = super::IndexKrate {
+            versions: (0..20)
+                .map(|i| super::IndexVersion::fake("async", format!("0.{i}.0")))
+                .collect(),
+        };
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+
+        let mut sync = Vec::new();
+        ik.write_json_lines(&mut sync).unwrap();
+        let mut actual = Vec::new();
+        rt.block_on(ik.write_json_lines_async(&mut actual)).unwrap();
+        assert_eq!(sync, actual);
+
+        sync.clear();
+        actual.clear();
+        ik.write_cache_entry(&mut sync, "etag: \"async\"").unwrap();
+        rt.block_on(ik.write_cache_entry_async(&mut actual, "etag: \"async\""))
+            .unwrap();
+        assert_eq!(sync, actual);
+    }
+
+    /// Validates zeroed and invalid checksums are reported rather than
+    /// failing the parse
+    #[test]
+    fn checksum_report() {
+        use super::{ChecksumIssue, ChksumParseError, IndexKrate};
+
+        const LINES: &str = concat!(
+            r#"{"name":"report","vers":"0.1.0","deps":[],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"report","vers":"0.2.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"report","vers":"0.3.0","deps":[],"cksum":"deadbeef","features":{},"yanked":false}"#,
+            "\n",
+        );
+
+        assert!(IndexKrate::from_slice(LINES.as_bytes()).is_err());
+
+        let (ik, warnings) = IndexKrate::from_slice_with_report(LINES.as_bytes()).unwrap();
+        assert_eq!(ik.versions.len(), 3);
+        assert!(!ik.versions[0].checksum.is_zero());
+        assert!(ik.versions[1].checksum.is_zero());
+        assert!(ik.versions[2].checksum.is_zero());
+
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].index, 1);
+        assert_eq!(warnings[0].version, "0.2.0");
+        assert_eq!(warnings[0].issue, ChecksumIssue::Zeroed);
+        assert_eq!(warnings[1].index, 2);
+        assert_eq!(
+            warnings[1].issue,
+            ChecksumIssue::Invalid(ChksumParseError::InvalidLength(8))
+        );
+
+        // Other errors are still errors
+        assert!(IndexKrate::from_slice_with_report(br#"{"name":"report"}"#).is_err());
+    }
+
+    /// Validates a shared dedupe context shares dependencies across crates
+    #[test]
+    fn shared_dedupe() {
+        const A: &str = r#"{"name":"a","vers":"0.1.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"std":[]},"yanked":false}"#;
+        const B: &str = r#"{"name":"b","vers":"1.0.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"std":[]},"yanked":false}"#;
+
+        let a = super::IndexKrate::from_slice(A.as_bytes()).unwrap();
+        let b = super::IndexKrate::from_slice(B.as_bytes()).unwrap();
+        assert!(!std::sync::Arc::ptr_eq(
+            &a.versions[0].deps,
+            &b.versions[0].deps
+        ));
+
+        let mut dedupe = super::DedupeContext::new();
+        let a = super::IndexKrate::from_slice_with_dedupe(A.as_bytes(), &mut dedupe).unwrap();
+        let b = super::IndexKrate::from_slice_with_dedupe(B.as_bytes(), &mut dedupe).unwrap();
+        assert!(std::sync::Arc::ptr_eq(
+            &a.versions[0].deps,
+            &b.versions[0].deps
+        ));
+        assert!(std::sync::Arc::ptr_eq(
+            &a.versions[0].features,
+            &b.versions[0].features
+        ));
+
+        dedupe.clear();
+        let c = super::IndexKrate::from_slice_with_dedupe(B.as_bytes(), &mut dedupe).unwrap();
+        assert!(!std::sync::Arc::ptr_eq(
+            &b.versions[0].deps,
+            &c.versions[0].deps
+        ));
+    }
+
+    /// Validates unknown dependency kinds don't fail the parse, and are round
+    /// tripped both as JSON and through a cache entry
+    #[test]
+    fn unknown_dependency_kind() {
+        use super::DependencyKind;
+
+        const LINE: &str = r#"{"name":"future","vers":"0.1.0","deps":[{"name":"meta","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"metabuild"},{"name":"cc","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"build"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#;
+
+        let ik = super::IndexKrate::from_slice(LINE.as_bytes()).unwrap();
+        let deps = ik.versions[0].dependencies();
+
+        assert_eq!(deps[0].kind(), DependencyKind::Unknown("metabuild".into()));
+        assert_eq!(deps[0].kind().as_str(), "metabuild");
+        assert_eq!(deps[1].kind(), DependencyKind::Build);
+
+        let mut out = Vec::new();
+        ik.write_json_lines(&mut out).unwrap();
+        assert_eq!(format!("{LINE}\n"), std::str::from_utf8(&out).unwrap());
+
+        let mut cache = Vec::new();
+        ik.write_cache_entry(&mut cache, "etag: \"future\"")
+            .unwrap();
+        let entry = crate::index::cache::ValidCacheEntry::read(&cache).unwrap();
+        assert_eq!(entry.to_krate(None).unwrap().unwrap(), ik);
+    }
+
+    /// Validates the `public` field is parsed, only serialized if present, and
+    /// doesn't prevent dependency deduplication
+    #[test]
+    fn public_dependencies() {
+        const LINES: &str = concat!(
+            r#"{"name":"public","vers":"0.1.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal"}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"public","vers":"0.2.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal","public":true}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#,
+            "\n",
+            r#"{"name":"public","vers":"0.3.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal","public":true}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false}"#,
+            "\n",
+        );
+
+        let ik = super::IndexKrate::from_slice(LINES.as_bytes()).unwrap();
+
+        assert!(!ik.versions[0].deps[0].is_public());
+        assert_eq!(ik.versions[0].deps[0].public, None);
+        assert!(ik.versions[1].deps[0].is_public());
+
+        assert!(!std::sync::Arc::ptr_eq(
+            &ik.versions[0].deps,
+            &ik.versions[1].deps
+        ));
+        assert!(std::sync::Arc::ptr_eq(
+            &ik.versions[1].deps,
+            &ik.versions[2].deps
+        ));
+
+        let mut out = Vec::new();
+        ik.write_json_lines(&mut out).unwrap();
+        assert_eq!(LINES, std::str::from_utf8(&out).unwrap());
+    }
+
+    /// Validates unknown fields are only preserved if requested, and are
+    /// round tripped
+    #[test]
+    fn preserves_extras() {
+        const LINE: &str = r#"{"name":"extras","vers":"0.1.0","deps":[{"name":"serde","req":"^1.0","features":[],"optional":false,"default_features":true,"target":null,"kind":"normal","future_dep_field":{"nested":[1,2]}}],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{},"yanked":false,"v":3,"future_field":"hello"}"#;
+
+        let ik = super::IndexKrate::from_slice(LINE.as_bytes()).unwrap();
+        assert!(ik.versions[0].extra.is_none());
+        assert!(ik.versions[0].deps[0].extra.is_none());
+
+        let ik = super::IndexKrate::from_slice_with_options(
+            LINE.as_bytes(),
+            super::ParseOptions {
+                preserve_extras: true,
+            },
+        )
+        .unwrap();
+
+        let extra = ik.versions[0].extra.as_ref().unwrap();
+        assert_eq!(extra.0.len(), 1);
+        assert_eq!(extra.0["future_field"], "hello");
+
+        let dep_extra = ik.versions[0].deps[0].extra.as_ref().unwrap();
+        assert_eq!(dep_extra.0.len(), 1);
+        assert!(dep_extra.0.contains_key("future_dep_field"));
+
+        let mut out = Vec::new();
+        ik.write_json_lines(&mut out).unwrap();
+        assert_eq!(format!("{LINE}\n"), std::str::from_utf8(&out).unwrap());
+    }
+
+    /// Validates that `features2` is never lost for versions that share the
+    /// same (deduplicated) `features` map
+    #[test]
+    fn features2_with_shared_features() {
+        const LINES: &str = concat!(
+            r#"{"name":"f2","vers":"0.1.0","deps":[],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"default":["std"],"std":[]},"features2":{"serde":["dep:serde"]},"yanked":false,"v":2}"#,
+            "\n",
+            r#"{"name":"f2","vers":"0.2.0","deps":[],"cksum":"c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2","features":{"default":["std"],"std":[]},"features2":{"json":["dep:serde_json","serde_json?/std"]},"yanked":false,"v":2}"#,
+            "\n",
+        );
+
+        let ik = super::IndexKrate::from_slice(LINES.as_bytes()).unwrap();
+
+        assert!(std::sync::Arc::ptr_eq(
+            &ik.versions[0].features,
+            &ik.versions[1].features
+        ));
+
+        let features = |i: usize| -> Vec<&str> {
+            ik.versions[i]
+                .features()
+                .map(|(name, _)| name.as_str())
+                .collect()
+        };
+
+        assert_eq!(features(0), ["default", "std", "serde"]);
+        assert_eq!(features(1), ["default", "std", "json"]);
+    }
+
+    /// Validates streaming parsing matches parsing from a slice
+    #[test]
+    fn from_reader() {
+        let ik = super::IndexKrate {
+            versions: (0..20)
+                .map(|i| super::IndexVersion::fake("streaming", format!("0.{i}.0")))
+                .collect(),
+        };
+
+        let mut lines = Vec::new();
+        ik.write_json_lines(&mut lines).unwrap();
+
+        assert_eq!(
+            super::IndexKrate::from_reader(lines.as_slice()).unwrap(),
+            super::IndexKrate::from_slice(&lines).unwrap()
+        );
+
+        // CRLF and blank lines are tolerated
+        let crlf = String::from_utf8(lines.clone())
+            .unwrap()
+            .replace('\n', "\r\n\n");
+        assert_eq!(super::IndexKrate::from_reader(crlf.as_bytes()).unwrap(), ik);
+
+        let mut highest = None;
+        let count = super::IndexKrate::for_each_version(lines.as_slice(), |iv| {
+            if iv.version.starts_with("0.1") {
+                highest = Some(iv);
+            }
+        })
+        .unwrap();
+        assert_eq!(count, 20);
+        assert_eq!(highest.unwrap().version, "0.19.0");
+
+        assert!(matches!(
+            super::IndexKrate::from_reader(&b"\n\n"[..]),
+            Err(crate::Error::NoCrateVersions)
+        ));
+    }
+
+    /// Validates filtered writing only writes the requested versions, and fails
+    /// if no versions would be written
+    #[test]
+    fn write_filtered() {
+        let ik = super::IndexKrate {
+            versions: (0..10)
+                .map(|i| {
+                    let mut iv = super::IndexVersion::fake("filtered", format!("0.{i}.0"));
+                    iv.yanked = i % 2 == 0;
+                    iv
+                })
+                .collect(),
+        };
+
+        let mut lines = Vec::new();
+        ik.write_json_lines_filtered(&mut lines, |iv| !iv.is_yanked())
+            .unwrap();
+
+        let filtered = super::IndexKrate::from_slice(&lines).unwrap();
+        assert_eq!(filtered.versions.len(), 5);
+        assert!(filtered.versions.iter().all(|iv| !iv.is_yanked()));
+
+        let mut cache = Vec::new();
+        ik.write_cache_entry_filtered(&mut cache, "etag: \"filtered\"", |iv| !iv.is_yanked())
+            .unwrap();
+        let entry = crate::index::cache::ValidCacheEntry::read(&cache).unwrap();
+        assert_eq!(entry.to_krate(None).unwrap().unwrap(), filtered);
+
+        let mut empty = Vec::new();
+        assert!(matches!(
+            ik.write_json_lines_filtered(&mut empty, |_iv| false),
+            Err(crate::Error::NoCrateVersions)
+        ));
+        assert!(matches!(
+            ik.write_cache_entry_filtered(&mut empty, "", |_iv| false),
+            Err(crate::Error::NoCrateVersions)
+        ));
+        assert!(empty.is_empty());
+    }
+
+    /// Validates the builder produces the same version as one parsed from the index
+    #[test]
+    fn builds_version() {
+        const LINE: &str = r#"{"name":"built","vers":"0.1.0","deps":[],"cksum":"0101010101010101010101010101010101010101010101010101010101010101","features":{"default":["std"],"std":[]},"features2":{"serde":["dep:serde","std"]},"yanked":true,"links":"built","rust_version":"1.70","v":2}"#;
+
+        let built = super::IndexVersionBuilder::new("built", "0.1.0")
+            .feature("default", ["std"])
+            .feature("std", Vec::<String>::new())
+            .feature("serde", ["dep:serde", "std"])
+            .yanked(true)
+            .links("built")
+            .rust_version("1.70")
+            .checksum([1; 32])
+            .build()
+            .unwrap();
+
+        assert_eq!(built, serde_json::from_str(LINE).unwrap());
+
+        assert!(super::IndexVersionBuilder::new("no.pe", "0.1.0")
+            .build()
+            .is_err());
+        assert!(super::IndexVersionBuilder::new("nope", "0.1")
+            .build()
+            .is_err());
+
+        let ik = super::IndexKrate::from_versions(vec![
+            super::IndexVersionBuilder::new("built", "0.2.0")
+                .build()
+                .unwrap(),
+            super::IndexVersionBuilder::new("built", "0.1.0")
+                .build()
+                .unwrap(),
+            super::IndexVersionBuilder::new("built", "0.10.0")
+                .build()
+                .unwrap(),
+        ])
+        .unwrap();
+
+        assert_eq!(
+            ik.versions
+                .iter()
+                .map(|iv| iv.version.as_str())
+                .collect::<Vec<_>>(),
+            ["0.1.0", "0.2.0", "0.10.0"]
+        );
+        assert!(matches!(
+            super::IndexKrate::from_versions(Vec::new()),
+            Err(crate::Error::NoCrateVersions)
+        ));
+    }
+
+    /// Validates the lowest matching version prefers stable versions and
+    /// respects the match options
+    #[test]
+    fn lowest_matching() {
+        use super::{IndexKrate, IndexVersion as iv, MatchOptions};
+        use semver::VersionReq;
+
+        let yanked = |v: &str| {
+            let mut iv = iv::fake("lowest", v);
+            iv.yanked = true;
+            iv
+        };
+
+        let ik = IndexKrate {
+            versions: vec![
+                iv::fake("lowest", "1.0.0-alpha.1"),
+                yanked("1.0.0"),
+                iv::fake("lowest", "1.0.1"),
+                iv::fake("lowest", "1.1.0-rc.1"),
+                iv::fake("lowest", "1.0.2"),
+                iv::fake("lowest", "2.0.0-alpha.1"),
+            ],
+        };
+
+        let lowest = |req: &str, opts: MatchOptions| {
+            ik.lowest_matching(&VersionReq::parse(req).unwrap(), opts)
+                .map(|iv| iv.version.as_str())
+        };
+
+        let default = MatchOptions::default();
+        let with_yanked = MatchOptions {
+            yanked: true,
+            ..default
+        };
+        let no_pre = MatchOptions {
+            pre_release: false,
+            ..default
+        };
+
+        assert_eq!(lowest("^1", default), Some("1.0.1"));
+        assert_eq!(lowest("^1", with_yanked), Some("1.0.0"));
+        // The pre-release is lower, but stable versions are preferred
+        assert_eq!(lowest("^1.0.0-alpha.1", default), Some("1.0.1"));
+        assert_eq!(lowest("=1.0.0-alpha.1", default), Some("1.0.0-alpha.1"));
+        assert_eq!(lowest("=1.0.0-alpha.1", no_pre), None);
+        assert_eq!(lowest("^2.0.0-alpha", default), Some("2.0.0-alpha.1"));
+        assert_eq!(lowest("^3", default), None);
+    }
+
+    /// Validates rust-version parsing matches cargo
+    #[test]
+    fn rust_version_parsed() {
+        let parse = |rv: Option<&str>| {
+            let mut iv = super::IndexVersion::fake("rv", "0.1.0");
+            iv.rust_version = rv.map(Into::into);
+            iv.rust_version_parsed()
+                .map(|res| res.map_err(|err| err.to_string()))
+        };
+
+        assert_eq!(parse(None), None);
+        assert_eq!(parse(Some("1")), Some(Ok(semver::Version::new(1, 0, 0))));
+        assert_eq!(
+            parse(Some("1.65")),
+            Some(Ok(semver::Version::new(1, 65, 0)))
+        );
+        assert_eq!(
+            parse(Some("1.70.1")),
+            Some(Ok(semver::Version::new(1, 70, 1)))
+        );
+
+        for invalid in [
+            "",
+            "1.",
+            "1.70.0.1",
+            "1.70.0-nightly",
+            "1.70.0+build",
+            "v1.70",
+            "garbage",
+        ] {
+            assert_eq!(
+                parse(Some(invalid)),
+                Some(Err(format!("'{invalid}' is not a valid rust-version"))),
+            );
+        }
+    }
+
+    #[test]
+    fn msrv() {
+        use super::IndexVersion as iv;
+
+        let with_rv = |vers: &str, rv: Option<&str>| {
+            let mut iv = iv::fake("msrv", vers);
+            iv.rust_version = rv.map(Into::into);
+            iv
+        };
+
+        let ik = super::IndexKrate {
+            versions: vec![
+                with_rv("0.1.0", None),
+                with_rv("0.2.0", Some("1.56")),
+                with_rv("0.3.0", Some("1.65.0")),
+                with_rv("0.4.0", Some("not a version")),
+                with_rv("0.5.0", Some("1.70")),
+                with_rv("0.6.0-alpha.1", Some("1.56")),
+                {
+                    let mut iv = with_rv("0.7.0", Some("1.60"));
+                    iv.yanked = true;
+                    iv
+                },
+                with_rv("0.8.0", Some("1.80.1")),
+            ],
+        };
+
+        let versions = |rustc: semver::Version| -> Vec<&str> {
+            ik.versions_for_msrv(&rustc)
+                .map(|iv| iv.version.as_str())
+                .collect()
+        };
+
+        assert_eq!(versions(semver::Version::new(1, 55, 0)), ["0.1.0", "0.4.0"]);
+        assert_eq!(
+            versions(semver::Version::new(1, 65, 0)),
+            ["0.1.0", "0.2.0", "0.3.0", "0.4.0", "0.6.0-alpha.1", "0.7.0"]
+        );
+        assert_eq!(
+            versions("1.70.0-nightly".parse().unwrap()),
+            [
+                "0.1.0",
+                "0.2.0",
+                "0.3.0",
+                "0.4.0",
+                "0.5.0",
+                "0.6.0-alpha.1",
+                "0.7.0"
+            ]
+        );
+
+        let highest = |rustc: semver::Version| {
+            ik.highest_normal_version_for_msrv(&rustc)
+                .map(|iv| iv.version.as_str())
+        };
+
+        assert_eq!(highest(semver::Version::new(1, 55, 0)), Some("0.4.0"));
+        assert_eq!(highest(semver::Version::new(1, 79, 0)), Some("0.5.0"));
+        assert_eq!(highest(semver::Version::new(1, 80, 1)), Some("0.8.0"));
+    }
+
+    /// Validates pre-release and build metadata matching behaves the same as
+    /// cargo's resolver
+    #[test]
+    fn matches_cargo() {
+        use super::matches_cargo;
+        use semver::{Version, VersionReq};
+
+        let cases: &[(&str, &[&str], &[&str])] = &[
+            (
+                "^1.0.0-alpha",
+                &[
+                    "1.0.0-alpha",
+                    "1.0.0-alpha.2",
+                    "1.0.0-beta",
+                    "1.0.0",
+                    "1.2.3",
+                ],
+                &["0.9.9", "1.0.1-alpha", "2.0.0-alpha", "2.0.0"],
+            ),
+            (
+                "1.0.0",
+                &["1.0.0", "1.0.0+build", "1.9.9"],
+                &["1.0.1-alpha", "1.1.0-rc.1", "2.0.0-alpha"],
+            ),
+            (
+                "*",
+                &["0.0.0", "0.1.0", "1.0.0", "1.0.0+build"],
+                &["0.1.0-alpha", "1.0.0-0"],
+            ),
+            (
+                "=0.1.0-beta.2",
+                &["0.1.0-beta.2", "0.1.0-beta.2+build"],
+                &["0.1.0-beta.1", "0.1.0-beta.3", "0.1.0"],
+            ),
+            (
+                ">=1.0.0-alpha, <2.0.0",
+                &["1.0.0-alpha", "1.0.0-rc.1", "1.5.0"],
+                &["1.5.0-alpha", "2.0.0-alpha", "2.0.0"],
+            ),
+            (
+                "~0.3.0-rc",
+                &["0.3.0-rc", "0.3.0-rc.1", "0.3.0", "0.3.9"],
+                &["0.3.1-rc", "0.4.0"],
+            ),
+            ("<1.0.0", &["0.9.0"], &["1.0.0-alpha", "1.0.0", "0.9.1-pre"]),
+        ];
+
+        for (req, matches, rejects) in cases {
+            let req = VersionReq::parse(req).unwrap();
+
+            for vers in *matches {
+                assert!(
+                    matches_cargo(&req, &Version::parse(vers).unwrap()),
+                    "{req} should match {vers}"
+                );
+            }
+
+            for vers in *rejects {
+                assert!(
+                    !matches_cargo(&req, &Version::parse(vers).unwrap()),
+                    "{req} should not match {vers}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn versions_matching() {
+        use super::{IndexVersion as iv, MatchOptions};
+        use semver::{Version, VersionReq};
+
+        // Generate a version set with every combination of major/minor/patch,
+        // pre-releases, and yanked versions
+        let mut versions = Vec::new();
+        for major in 0..3 {
+            for minor in 0..3 {
+                for patch in 0..3 {
+                    for pre in ["", "-alpha.1", "-beta.2"] {
+                        let mut v = iv::fake("matching", format!("{major}.{minor}.{patch}{pre}"));
+                        v.yanked = (major + minor + patch) % 4 == 0;
+                        versions.push(v);
+                    }
+                }
+            }
+        }
+
+        let ik = super::IndexKrate { versions };
+
+        let reqs = [
+            "^1.1",
+            "~0.2.1",
+            "=1.0.0",
+            ">=0.1, <2",
+            "*",
+            "^1.1.1-alpha.1",
+            ">1.2.2-alpha.1",
+            "=2.2.2-beta.2",
+            "<0.0.1",
+        ];
+
+        for req in reqs {
+            let req: VersionReq = req.parse().unwrap();
+
+            for opts in [
+                MatchOptions::default(),
+                MatchOptions {
+                    yanked: true,
+                    pre_release: true,
+                },
+                MatchOptions {
+                    yanked: false,
+                    pre_release: false,
+                },
+            ] {
+                let matched: Vec<_> = ik.versions_matching(&req, opts).collect();
+
+                for v in &ik.versions {
+                    let vers: Version = v.version.parse().unwrap();
+                    let expected = (opts.yanked || !v.yanked)
+                        && (opts.pre_release || vers.pre.is_empty())
+                        && req.matches(&vers);
+
+                    assert_eq!(
+                        expected,
+                        matched.iter().any(|m| std::ptr::eq(*m, v)),
+                        "{req} {opts:?} {}",
+                        v.version
+                    );
+
+                    // Pre-releases are only matched if the requirement has a pre-release
+                    if !vers.pre.is_empty() && expected {
+                        assert!(req.comparators.iter().any(|c| !c.pre.is_empty()));
+                    }
+                }
+
+                let highest = ik.highest_matching(&req, opts);
+                assert_eq!(
+                    highest.map(|v| Version::parse(&v.version).unwrap()),
+                    matched
+                        .iter()
+                        .map(|v| Version::parse(&v.version).unwrap())
+                        .max()
+                );
+            }
+        }
+    }
+
+    /// Validates checksums can be parsed from hex and converted to and from bytes
+    #[test]
+    fn chksum_conversions() {
+        use super::{Chksum, ChksumParseError};
+
+        const HEX: &str = "9a7a4ce9d2b6a4a5b9f8e0a6c3d4fe1b2a3c4d5e6f708192a3b4c5d6e7f80912";
+
+        let parsed: Chksum = HEX.parse().unwrap();
+        assert_eq!(parsed.to_hex_string(), HEX);
+        assert_eq!(parsed.to_string(), HEX);
+        assert_eq!(
+            Chksum::try_from(HEX.to_uppercase().as_str()).unwrap(),
+            parsed
+        );
+        assert_eq!(Chksum::from(parsed.0), parsed);
+        assert_eq!(parsed.as_ref(), &parsed.0[..]);
+
+        assert_eq!(
+            Chksum::try_from(&HEX[..63]).unwrap_err(),
+            ChksumParseError::InvalidLength(63)
+        );
+
+        let mut bad = HEX.to_owned();
+        bad.replace_range(17..18, "g");
+        assert_eq!(
+            bad.parse::<Chksum>().unwrap_err(),
+            ChksumParseError::InvalidValue {
+                invalid: 'g',
+                index: 17
+            }
+        );
+
+        let mut bad = HEX[..62].to_owned();
+        bad.insert(10, 'é');
+        assert_eq!(
+            bad.parse::<Chksum>().unwrap_err(),
+            ChksumParseError::InvalidValue {
+                invalid: 'é',
+                index: 10
+            }
+        );
+
+        let mut set = std::collections::BTreeSet::new();
+        set.insert(parsed.clone());
+        set.insert(Chksum([0; 32]));
+        assert_eq!(set.iter().next().unwrap(), &Chksum([0; 32]));
+    }
 }
@@ -0,0 +1,210 @@
+//! Contains the response types for the [crates.io web API](https://crates.io/data-access#api),
+//! and a lossy conversion from them into the index format types in this crate
+//!
+//! The API can be useful when an index entry is not yet available, eg. a
+//! brand new crate that has not yet propagated to the sparse index, or when
+//! working from saved API dumps. However, the API does not expose every piece
+//! of information that is available in an index entry, so the conversion is
+//! lossy:
+//!
+//! - The API only exposes the actual name of a dependency, not the name it is
+//!   renamed to in the manifest, so [`IndexDependency::package`] is never set
+//!   and [`IndexDependency::name`] is always the actual crate name
+//! - The API merges `features` and `features2`, they are split again based on
+//!   whether a feature uses namespaced (`dep:`) or weak (`?/`) dependency
+//!   syntax, which is the same rule crates.io uses when publishing
+//! - Artifact dependency and public dependency information is not available
+//! - Unknown fields are never preserved
+
+use super::{DependencyKind, FeatureMap, IndexDependency, IndexKrate, IndexVersionBuilder};
+use crate::Error;
+use serde::Deserialize;
+use smol_str::SmolStr;
+
+/// The response from `https://crates.io/api/v1/crates/{name}`
+#[derive(Deserialize, Clone, Debug)]
+pub struct CrateResponse {
+    /// Every version of the crate
+    pub versions: Vec<Version>,
+}
+
+/// A single version in a [`CrateResponse`]
+#[derive(Deserialize, Clone, Debug)]
+pub struct Version {
+    /// The name of the crate
+    #[serde(rename = "crate")]
+    pub name: SmolStr,
+    /// The version
+    pub num: SmolStr,
+    /// The hex encoded SHA-256 checksum of the version's .crate file
+    pub checksum: super::Chksum,
+    /// Whether the version is yanked
+    #[serde(default)]
+    pub yanked: bool,
+    /// Every feature of the version, including ones that would be in
+    /// `features2` in the index
+    #[serde(default)]
+    pub features: FeatureMap,
+    /// The `links` of the version
+    #[serde(default)]
+    pub links: Option<SmolStr>,
+    /// The `rust-version` of the version
+    #[serde(default)]
+    pub rust_version: Option<SmolStr>,
+    /// The RFC 3339 timestamp of when the version was published
+    #[serde(default)]
+    pub created_at: Option<String>,
+}
+
+/// The response from `https://crates.io/api/v1/crates/{name}/{version}/dependencies`
+#[derive(Deserialize, Clone, Debug)]
+pub struct DependenciesResponse {
+    /// The dependencies of the version
+    pub dependencies: Vec<Dependency>,
+}
+
+/// A single dependency in a [`DependenciesResponse`]
+#[derive(Deserialize, Clone, Debug)]
+pub struct Dependency {
+    /// The actual name of the crate
+    pub crate_id: SmolStr,
+    /// The version requirement
+    pub req: SmolStr,
+    /// Whether the dependency is optional
+    #[serde(default)]
+    pub optional: bool,
+    /// Whether the default features of the dependency are enabled
+    #[serde(default = "default_true")]
+    pub default_features: bool,
+    /// The features enabled for the dependency
+    #[serde(default)]
+    pub features: Vec<String>,
+    /// The cfg expression or target triple the dependency is limited to
+    #[serde(default)]
+    pub target: Option<SmolStr>,
+    /// The kind of the dependency
+    #[serde(default)]
+    pub kind: Option<DependencyKind>,
+}
+
+#[inline]
+fn default_true() -> bool {
+    true
+}
+
+impl From<Dependency> for IndexDependency {
+    fn from(dep: Dependency) -> Self {
+        Self {
+            name: dep.crate_id,
+            req: dep.req,
+            features: Box::new(dep.features.into_boxed_slice()),
+            optional: dep.optional,
+            default_features: dep.default_features,
+            target: dep.target.map(Box::new),
+            kind: dep.kind,
+            package: None,
+            artifact: None,
+            bindep_target: None,
+            lib: false,
+            public: None,
+            extra: None,
+        }
+    }
+}
+
+impl Version {
+    /// Converts this version into an [`IndexVersion`](super::IndexVersion)
+    /// with the specified dependencies
+    pub fn into_index_version(self, deps: Vec<Dependency>) -> Result<super::IndexVersion, Error> {
+        let mut builder = IndexVersionBuilder::new(self.name, self.num)
+            .checksum(self.checksum.0)
+            .yanked(self.yanked);
+
+        for (name, enables) in self.features {
+            builder = builder.feature(name, enables);
+        }
+
+        if let Some(links) = self.links {
+            builder = builder.links(links);
+        }
+
+        if let Some(rv) = self.rust_version {
+            builder = builder.rust_version(rv);
+        }
+
+        for dep in deps {
+            builder = builder.dependency(dep.into());
+        }
+
+        builder.build()
+    }
+}
+
+impl IndexKrate {
+    /// Creates an index entry from the JSON response of `https://crates.io/api/v1/crates/{name}`
+    ///
+    /// Since the crate response does not include dependencies, the callback
+    /// is invoked for each version to retrieve its dependencies, eg. from the
+    /// `https://crates.io/api/v1/crates/{name}/{version}/dependencies` endpoint
+    /// or a saved dump of it.
+    ///
+    /// The versions are ordered by their publish time, oldest first, the same
+    /// as an index entry. See the [module](super::api) documentation for the
+    /// information that is lost in the conversion.
+    pub fn from_crates_io_api(
+        krate_json: &[u8],
+        mut versions_deps: impl FnMut(&Version) -> Result<Vec<Dependency>, Error>,
+    ) -> Result<Self, Error> {
+        let mut response: CrateResponse = serde_json::from_slice(krate_json)?;
+
+        // The API returns the newest versions first
+        response.versions.reverse();
+        response
+            .versions
+            .sort_by(|a, b| a.created_at.cmp(&b.created_at));
+
+        let versions = response
+            .versions
+            .into_iter()
+            .map(|version| {
+                let deps = versions_deps(&version)?;
+                version.into_index_version(deps)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if versions.is_empty() {
+            return Err(Error::NoCrateVersions);
+        }
+
+        Ok(Self { versions })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    const KRATE: &str = r#"{"crate":{"id":"api-test","name":"api-test"},"versions":[{"id":2,"crate":"api-test","num":"0.2.0","checksum":"0202020202020202020202020202020202020202020202020202020202020202","yanked":true,"features":{"default":["std"],"serde":["dep:serde"],"std":[]},"links":null,"rust_version":"1.70","created_at":"2024-02-01T00:00:00.000000+00:00"},{"id":1,"crate":"api-test","num":"0.1.0","checksum":"0101010101010101010101010101010101010101010101010101010101010101","yanked":false,"features":{},"links":"api","rust_version":null,"created_at":"2024-01-01T00:00:00.000000+00:00"}]}"#;
+    const DEPS: &str = r#"{"dependencies":[{"id":1,"version_id":2,"crate_id":"serde","req":"^1.0","optional":true,"default_features":false,"features":["derive"],"target":null,"kind":"normal","downloads":0},{"id":2,"version_id":2,"crate_id":"cc","req":"^1","optional":false,"default_features":true,"features":[],"target":"cfg(windows)","kind":"build","downloads":0}]}"#;
+
+    /// Validates crates.io API responses are converted into the same index
+    /// entry that crates.io would publish
+    #[test]
+    fn converts_api_response() {
+        let ik = super::IndexKrate::from_crates_io_api(KRATE.as_bytes(), |vers| {
+            Ok(if vers.num == "0.2.0" {
+                serde_json::from_str::<super::DependenciesResponse>(DEPS)?.dependencies
+            } else {
+                Vec::new()
+            })
+        })
+        .unwrap();
+
+        let expected = super::IndexKrate::from_slice(concat!(
+            r#"{"name":"api-test","vers":"0.1.0","deps":[],"cksum":"0101010101010101010101010101010101010101010101010101010101010101","features":{},"yanked":false,"links":"api"}"#,
+            "\n",
+            r#"{"name":"api-test","vers":"0.2.0","deps":[{"name":"serde","req":"^1.0","features":["derive"],"optional":true,"default_features":false,"target":null,"kind":"normal"},{"name":"cc","req":"^1","features":[],"optional":false,"default_features":true,"target":"cfg(windows)","kind":"build"}],"cksum":"0202020202020202020202020202020202020202020202020202020202020202","features":{"default":["std"],"std":[]},"features2":{"serde":["dep:serde"]},"yanked":true,"rust_version":"1.70","v":2}"#,
+        ).as_bytes())
+        .unwrap();
+
+        assert_eq!(ik, expected);
+    }
+}
@@ -1,6 +1,6 @@
-//! Contains helpers for deduplicating dependencies and features for a single
-//! crate during parsing as each individual version of a crate tends to be mostly
-//! the same
+//! Contains helpers for deduplicating dependencies and features during parsing
+//! as each individual version of a crate tends to be mostly the same, and many
+//! crates share identical dependency lists
 //!
 //! Copied from <https://github.com/frewsxcv/rust-crates-index/blob/master/src/dedupe.rs>
 
@@ -14,20 +14,79 @@ type XxSet<K> = std::collections::HashSet<K, BuildHasherDefault<twox_hash::XxHas
 use super::FeatureMap;
 
 /// Many crate versions have the same features and dependencies
-#[derive(Default)]
-pub(crate) struct DedupeContext {
+///
+/// Each [`IndexKrate::from_slice`](super::IndexKrate::from_slice) call uses a
+/// fresh context, so identical features and dependencies are only shared
+/// between versions of the same crate. When parsing many index entries, the
+/// same context can be reused via [`IndexKrate::from_slice_with_dedupe`](super::IndexKrate::from_slice_with_dedupe)
+/// so that they are also shared between different crates.
+///
+/// The context is not thread safe, when parsing in parallel use one context
+/// per worker, eg.
+///
+/// ```
+/// use rayon::prelude::*;
+/// use tame_index::{krate::DedupeContext, IndexKrate};
+///
+/// let entries: Vec<Vec<u8>> = vec![/* index entries read from disk */];
+///
+/// let krates: Vec<_> = entries
+///     .par_iter()
+///     .map_init(DedupeContext::new, |dedupe, entry| {
+///         IndexKrate::from_slice_with_dedupe(entry, dedupe)
+///     })
+///     .collect();
+/// ```
+pub struct DedupeContext {
     features: XxSet<HashedFeatureMap>,
     deps: XxSet<Arc<[IndexDependency]>>,
+    limit: usize,
+}
+
+impl Default for DedupeContext {
+    #[inline]
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl DedupeContext {
+    /// The default maximum number of unique feature maps and dependency lists
+    /// that are retained
+    pub const DEFAULT_LIMIT: usize = 16 * 1024;
+
+    /// Creates a new context with the [default](Self::DEFAULT_LIMIT) limit
+    #[inline]
+    pub fn new() -> Self {
+        Self::with_limit(Self::DEFAULT_LIMIT)
+    }
+
+    /// Creates a new context that retains at most `limit` unique feature maps
+    /// and dependency lists each, clearing them when the limit is exceeded to
+    /// keep peak memory usage bounded
+    #[inline]
+    pub fn with_limit(limit: usize) -> Self {
+        Self {
+            features: XxSet::default(),
+            deps: XxSet::default(),
+            limit,
+        }
+    }
+
+    /// Clears all of the retained feature maps and dependency lists
+    #[inline]
+    pub fn clear(&mut self) {
+        self.features.clear();
+        self.deps.clear();
+    }
+
     pub(crate) fn features(&mut self, features: &mut Arc<FeatureMap>) {
         let features_to_dedupe = HashedFeatureMap::new(Arc::clone(features));
         if let Some(has_feats) = self.features.get(&features_to_dedupe) {
             *features = Arc::clone(&has_feats.map);
         } else {
             // keeps peak memory low (must clear, remove is leaving tombstones)
-            if self.features.len() > 16 * 1024 {
+            if self.features.len() > self.limit {
                 self.features.clear();
             }
             self.features.insert(features_to_dedupe);
@@ -39,7 +98,7 @@ impl DedupeContext {
             *deps = Arc::clone(has_deps);
         } else {
             // keeps peak memory low (must clear, remove is leaving tombstones)
-            if self.deps.len() > 16 * 1024 {
+            if self.deps.len() > self.limit {
                 self.deps.clear();
             }
             self.deps.insert(Arc::clone(deps));
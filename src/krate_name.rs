@@ -1,4 +1,8 @@
-use crate::error::{Error, InvalidKrateName};
+use crate::error::{
+    Error, InvalidKrateName,
+    ReservedNameKind::{self, Artifact, Keyword, Standard, Windows},
+    ReservedReason,
+};
 
 #[cfg(test)]
 /// Create a `KrateName` from a string literal.
@@ -9,10 +13,151 @@ macro_rules! kn {
     };
 }
 
+// This is a single table, binary sorted so that we can more easily just
+// check matches and move on
+//
+// 1. Rustlang keywords, see https://doc.rust-lang.org/reference/keywords.html
+// 2. Windows reserved, see https://github.com/rust-lang/cargo/blob/b40be8bdcf2eff9ed81702594d44bf96c27973a6/src/cargo/util/restricted_names.rs#L26-L32
+// 3. Cargo artifacts, see https://github.com/rust-lang/cargo/blob/b40be8bdcf2eff9ed81702594d44bf96c27973a6/src/cargo/util/restricted_names.rs#L35-L37
+// 4. Rustlang std, see https://github.com/rust-lang/cargo/blob/b40be8bdcf2eff9ed81702594d44bf96c27973a6/src/cargo/ops/cargo_new.rs#L225-L239
+const DISALLOWED: &[(&str, ReservedNameKind)] = &[
+    ("Self", Keyword),
+    ("abstract", Keyword),
+    ("alloc", Standard),
+    ("as", Keyword),
+    ("async", Keyword),
+    ("aux", Windows),
+    ("await", Keyword),
+    ("become", Keyword),
+    ("box", Keyword),
+    ("break", Keyword),
+    ("build", Artifact),
+    ("com1", Windows),
+    ("com2", Windows),
+    ("com3", Windows),
+    ("com4", Windows),
+    ("com5", Windows),
+    ("com6", Windows),
+    ("com7", Windows),
+    ("com8", Windows),
+    ("com9", Windows),
+    ("con", Windows),
+    ("const", Keyword),
+    ("continue", Keyword),
+    ("core", Standard),
+    ("crate", Keyword),
+    ("deps", Artifact),
+    ("do", Keyword),
+    ("dyn", Keyword),
+    ("else", Keyword),
+    ("enum", Keyword),
+    ("examples", Artifact),
+    ("extern", Keyword),
+    ("false", Keyword),
+    ("final", Keyword),
+    ("fn", Keyword),
+    ("for", Keyword),
+    ("if", Keyword),
+    ("impl", Keyword),
+    ("in", Keyword),
+    ("incremental", Artifact),
+    ("let", Keyword),
+    ("loop", Keyword),
+    ("lpt1", Windows),
+    ("lpt2", Windows),
+    ("lpt3", Windows),
+    ("lpt4", Windows),
+    ("lpt5", Windows),
+    ("lpt6", Windows),
+    ("lpt7", Windows),
+    ("lpt8", Windows),
+    ("lpt9", Windows),
+    ("macro", Keyword),
+    ("match", Keyword),
+    ("mod", Keyword),
+    ("move", Keyword),
+    ("mut", Keyword),
+    ("nul", Windows),
+    ("override", Keyword),
+    ("priv", Keyword),
+    ("prn", Windows),
+    ("proc-macro", Standard),
+    ("proc_macro", Standard),
+    ("pub", Keyword),
+    ("ref", Keyword),
+    ("return", Keyword),
+    ("self", Keyword),
+    ("static", Keyword),
+    ("std", Standard),
+    ("struct", Keyword),
+    ("super", Keyword),
+    ("test", Standard),
+    ("trait", Keyword),
+    ("true", Keyword),
+    ("try", Keyword),
+    ("type", Keyword),
+    ("typeof", Keyword),
+    ("unsafe", Keyword),
+    ("unsized", Keyword),
+    ("use", Keyword),
+    ("virtual", Keyword),
+    ("where", Keyword),
+    ("while", Keyword),
+    ("yield", Keyword),
+];
+
+// Names reserved by crates.io, from the `reserved_crate_names` table that
+// crates.io seeds in its migrations. This is binary sorted and in canonical
+// form (lowercase, `-` replaced with `_`) since crates.io compares names that way
+const CRATES_IO_RESERVED: &[&str] = &[
+    "alloc",
+    "arena",
+    "ast",
+    "builtins",
+    "collections",
+    "compiler_builtins",
+    "compiler_rt",
+    "compiletest",
+    "core",
+    "coretest",
+    "debug",
+    "driver",
+    "flate",
+    "fmt_macros",
+    "grammar",
+    "graphviz",
+    "macro",
+    "macros",
+    "proc_macro",
+    "rbml",
+    "rust_installer",
+    "rustbook",
+    "rustc",
+    "rustc_back",
+    "rustc_borrowck",
+    "rustc_driver",
+    "rustc_llvm",
+    "rustc_resolve",
+    "rustc_trans",
+    "rustc_typeck",
+    "rustdoc",
+    "rustllvm",
+    "rustuv",
+    "serialize",
+    "std",
+    "syntax",
+    "test",
+    "unicode",
+];
+
 /// Used to wrap user-provided strings so that bad crate names are required to
 /// be handled separately from things more outside the user control such as I/O
 /// errors
-#[derive(Copy, Clone)]
+///
+/// Comparisons and hashing use the name exactly as specified, see
+/// [`KrateName::eq_ignore_case`] and [`KrateName::eq_canonical`] for the
+/// comparisons used by cargo and crates.io
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct KrateName<'name>(pub(crate) &'name str);
 
 impl<'name> KrateName<'name> {
@@ -20,7 +165,7 @@ impl<'name> KrateName<'name> {
     /// to the (current) crates.io name restrictions
     ///
     /// 1. Non-empty
-    /// 2. May not start with a digit
+    /// 2. Must start with an ASCII alphabetic character
     /// 3. Maximum of 64 characters in length
     /// 4. Must be ASCII alphanumeric, `-`, or `_`
     /// 5. May not be a reserved name
@@ -30,13 +175,13 @@ impl<'name> KrateName<'name> {
     ///     * A reserved Windows name (such as `nul`)
     #[inline]
     pub fn crates_io(name: &'name str) -> Result<Self, Error> {
-        Self::validated(name, Some(64))
+        Self::validated(name, Some(64), false)
     }
 
     /// Ensures the specified string is a valid crate name according to [cargo](https://github.com/rust-lang/cargo/blob/00b8da63269420610758464c02fc46584e373dd3/src/cargo/ops/cargo_new.rs#L167-L264)
     ///
     /// 1. Non-empty
-    /// 2. May not start with a digit
+    /// 2. Must start with an ASCII alphabetic character or `_`
     /// 3. Must be ASCII alphanumeric, `-`, or `_`
     /// 4. May not be a reserved name
     ///     * A Rust keyword
@@ -45,10 +190,57 @@ impl<'name> KrateName<'name> {
     ///     * A reserved Windows name (such as `nul`)
     #[inline]
     pub fn cargo(name: &'name str) -> Result<Self, Error> {
-        Self::validated(name, None)
+        Self::validated(name, None, true)
     }
 
-    fn validated(name: &'name str, max_len: Option<usize>) -> Result<Self, Error> {
+    /// Ensures the specified string is a valid crate name, allowing non-ASCII
+    /// names, for use with alternative registries that don't have the ASCII
+    /// restriction imposed by crates.io
+    ///
+    /// 1. Non-empty
+    /// 2. Must start with an alphabetic character or `_`
+    /// 3. Must be alphanumeric, `-`, or `_`
+    ///
+    /// Unlike [`Self::crates_io`] and [`Self::cargo`], reserved names are not
+    /// rejected, see [`Self::reserved_reason`] if you want to check for them.
+    /// See [`Self::relative_path`] for how non-ASCII names are laid out in an index
+    ///
+    /// ```
+    /// use tame_index::KrateName;
+    ///
+    /// assert!(KrateName::cargo("Über").is_err());
+    /// let name = KrateName::try_relaxed("Über").unwrap();
+    /// assert_eq!(name.relative_path(Some('/')), "ü/be/über");
+    /// ```
+    pub fn try_relaxed(name: &'name str) -> Result<Self, Error> {
+        let mut chars = name.chars().enumerate();
+
+        let Some((_, first)) = chars.next() else {
+            return Err(InvalidKrateName::InvalidLength(0).into());
+        };
+
+        if !first.is_alphabetic() && first != '_' {
+            return Err(InvalidKrateName::InvalidCharacter {
+                invalid: first,
+                index: 0,
+            }
+            .into());
+        }
+
+        if let Some((index, invalid)) =
+            chars.find(|(_i, c)| *c != '-' && *c != '_' && !c.is_alphanumeric())
+        {
+            return Err(InvalidKrateName::InvalidCharacter { invalid, index }.into());
+        }
+
+        Ok(Self(name))
+    }
+
+    fn validated(
+        name: &'name str,
+        max_len: Option<usize>,
+        allow_leading_underscore: bool,
+    ) -> Result<Self, Error> {
         if name.is_empty() {
             return Err(InvalidKrateName::InvalidLength(0).into());
         }
@@ -56,7 +248,7 @@ impl<'name> KrateName<'name> {
         let mut chars = name.chars().enumerate();
 
         while let Some((i, c)) = chars.next() {
-            if i == 0 && c != '_' && !c.is_ascii_alphabetic() {
+            if i == 0 && !(c.is_ascii_alphabetic() || (allow_leading_underscore && c == '_')) {
                 return Err(InvalidKrateName::InvalidCharacter {
                     invalid: c,
                     index: i,
@@ -77,100 +269,6 @@ impl<'name> KrateName<'name> {
             }
         }
 
-        // This is a single table, binary sorted so that we can more easily just
-        // check matches and move on
-        //
-        // 1. Rustlang keywords, see https://doc.rust-lang.org/reference/keywords.html
-        // 2. Windows reserved, see https://github.com/rust-lang/cargo/blob/b40be8bdcf2eff9ed81702594d44bf96c27973a6/src/cargo/util/restricted_names.rs#L26-L32
-        // 3. Cargo artifacts, see https://github.com/rust-lang/cargo/blob/b40be8bdcf2eff9ed81702594d44bf96c27973a6/src/cargo/util/restricted_names.rs#L35-L37
-        // 4. Rustlang std, see https://github.com/rust-lang/cargo/blob/b40be8bdcf2eff9ed81702594d44bf96c27973a6/src/cargo/ops/cargo_new.rs#L225-L239
-        use crate::error::ReservedNameKind::{Artifact, Keyword, Standard, Windows};
-        const DISALLOWED: &[(&str, crate::error::ReservedNameKind)] = &[
-            ("Self", Keyword),
-            ("abstract", Keyword),
-            ("alloc", Standard),
-            ("as", Keyword),
-            ("async", Keyword),
-            ("aux", Windows),
-            ("await", Keyword),
-            ("become", Keyword),
-            ("box", Keyword),
-            ("break", Keyword),
-            ("build", Artifact),
-            ("com1", Windows),
-            ("com2", Windows),
-            ("com3", Windows),
-            ("com4", Windows),
-            ("com5", Windows),
-            ("com6", Windows),
-            ("com7", Windows),
-            ("com8", Windows),
-            ("com9", Windows),
-            ("con", Windows),
-            ("const", Keyword),
-            ("continue", Keyword),
-            ("core", Standard),
-            ("crate", Keyword),
-            ("deps", Artifact),
-            ("do", Keyword),
-            ("dyn", Keyword),
-            ("else", Keyword),
-            ("enum", Keyword),
-            ("examples", Artifact),
-            ("extern", Keyword),
-            ("false", Keyword),
-            ("final", Keyword),
-            ("fn", Keyword),
-            ("for", Keyword),
-            ("if", Keyword),
-            ("impl", Keyword),
-            ("in", Keyword),
-            ("incremental", Artifact),
-            ("let", Keyword),
-            ("loop", Keyword),
-            ("lpt1", Windows),
-            ("lpt2", Windows),
-            ("lpt3", Windows),
-            ("lpt4", Windows),
-            ("lpt5", Windows),
-            ("lpt6", Windows),
-            ("lpt7", Windows),
-            ("lpt8", Windows),
-            ("lpt9", Windows),
-            ("macro", Keyword),
-            ("match", Keyword),
-            ("mod", Keyword),
-            ("move", Keyword),
-            ("mut", Keyword),
-            ("nul", Windows),
-            ("override", Keyword),
-            ("priv", Keyword),
-            ("prn", Windows),
-            ("proc-macro", Standard),
-            ("proc_macro", Standard),
-            ("pub", Keyword),
-            ("ref", Keyword),
-            ("return", Keyword),
-            ("self", Keyword),
-            ("static", Keyword),
-            ("std", Standard),
-            ("struct", Keyword),
-            ("super", Keyword),
-            ("test", Standard),
-            ("trait", Keyword),
-            ("true", Keyword),
-            ("try", Keyword),
-            ("type", Keyword),
-            ("typeof", Keyword),
-            ("unsafe", Keyword),
-            ("unsized", Keyword),
-            ("use", Keyword),
-            ("virtual", Keyword),
-            ("where", Keyword),
-            ("while", Keyword),
-            ("yield", Keyword),
-        ];
-
         if let Ok(i) = DISALLOWED.binary_search_by_key(&name, |(k, _v)| k) {
             let (reserved, kind) = DISALLOWED[i];
             Err(InvalidKrateName::ReservedName { reserved, kind }.into())
@@ -181,8 +279,13 @@ impl<'name> KrateName<'name> {
 }
 
 /// The simplest way to create a crate name, this just ensures that the crate name
-/// is non-empty, and ASCII alphanumeric, `-`, or, `-`, the minimum requirements
+/// is non-empty, and ASCII alphanumeric, `-`, or, `_`, the minimum requirements
 /// for this crate
+///
+/// This is enough to guarantee that a name can't contain path separators or
+/// `.`, so [`KrateName::relative_path`] can never escape the index directory,
+/// but names from untrusted input should use the stricter [`KrateName::crates_io`]
+/// or [`KrateName::cargo`] constructors
 impl<'name> TryFrom<&'name str> for KrateName<'name> {
     type Error = Error;
     #[inline]
@@ -201,8 +304,39 @@ impl<'name> TryFrom<&'name str> for KrateName<'name> {
     }
 }
 
+/// The layout of a crate's prefix in a registry index, which depends on the
+/// length of the crate name, see [`KrateName::prefix`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PrefixKind {
+    /// A single character name, the prefix is `1`
+    One,
+    /// A two character name, the prefix is `2`
+    Two,
+    /// A three character name, the prefix is `3/{first-character}`
+    Three,
+    /// A name of four or more characters, the prefix is
+    /// `{first-two-characters}/{second-two-characters}`
+    Four,
+}
+
 impl KrateName<'_> {
-    /// Writes the crate's prefix to the specified string
+    /// Gets the kind of prefix used for this crate name
+    ///
+    /// Note that like cargo, this uses the length of the name in bytes, which
+    /// is the same as the number of characters for ASCII names
+    #[inline]
+    pub fn prefix_kind(&self) -> PrefixKind {
+        match self.0.len() {
+            0 => unreachable!(),
+            1 => PrefixKind::One,
+            2 => PrefixKind::Two,
+            3 => PrefixKind::Three,
+            _ => PrefixKind::Four,
+        }
+    }
+
+    /// Gets the crate's prefix, as used by the `{prefix}` marker in an
+    /// [`IndexConfig::dl`](crate::index::IndexConfig::dl) template
     ///
     /// Cargo uses a simple prefix in the registry index so that crate's can be
     /// partitioned, particularly on disk without running up against potential OS
@@ -211,55 +345,338 @@ impl KrateName<'_> {
     ///
     /// The separator should be [`std::path::MAIN_SEPARATOR`] in disk cases and
     /// '/' when used for urls
-    pub fn prefix(&self, acc: &mut String, sep: char) {
-        let name = self.0;
-        match name.len() {
-            0 => unreachable!(),
-            1 => acc.push('1'),
-            2 => acc.push('2'),
-            3 => {
-                acc.push('3');
-                acc.push(sep);
-                acc.push_str(&name[..1]);
-            }
-            _ => {
-                acc.push_str(&name[..2]);
-                acc.push(sep);
-                acc.push_str(&name[2..4]);
-            }
+    ///
+    /// ```
+    /// let name = tame_index::KrateName::cargo("Serde").unwrap();
+    /// assert_eq!(name.prefix('/'), "Se/rd");
+    /// ```
+    #[inline]
+    pub fn prefix(&self, sep: char) -> String {
+        let mut prefix = String::with_capacity(5);
+        self.push_prefix(&mut prefix, sep);
+        prefix
+    }
+
+    /// Gets the crate's prefix computed from the lowercased name, as used by
+    /// the `{lowerprefix}` marker in an [`IndexConfig::dl`](crate::index::IndexConfig::dl)
+    /// template, and in index paths, see [`Self::relative_path`]
+    ///
+    /// ```
+    /// let name = tame_index::KrateName::cargo("Serde").unwrap();
+    /// assert_eq!(name.lower_prefix('/'), "se/rd");
+    /// ```
+    #[inline]
+    pub fn lower_prefix(&self, sep: char) -> String {
+        let mut prefix = String::with_capacity(5);
+        self.push_lower_prefix(&mut prefix, sep);
+        prefix
+    }
+
+    /// Appends the crate's prefix to the specified string, see [`Self::prefix`]
+    #[inline]
+    pub fn push_prefix(&self, acc: &mut String, sep: char) {
+        write_prefix(self.0, acc, sep);
+    }
+
+    /// Appends the crate's lowercased prefix to the specified string, see
+    /// [`Self::lower_prefix`]
+    pub fn push_lower_prefix(&self, acc: &mut String, sep: char) {
+        if self.0.is_ascii() {
+            let start = acc.len();
+            write_prefix(self.0, acc, sep);
+            acc[start..].make_ascii_lowercase();
+        } else {
+            write_prefix(&lowercase(self.0), acc, sep);
         }
     }
 
     /// Gets the relative path to a crate
     ///
-    /// This will be of the form [`Self::prefix`] + `<sep>` + `<name>`
+    /// This will be of the form [`Self::lower_prefix`] + `<sep>` + `<name>`
     ///
     /// If not specified, the separator is [`std::path::MAIN_SEPARATOR`]
     ///
+    /// The path is lowercased the same as cargo does, including for non-ASCII
+    /// names created via [`Self::try_relaxed`], where the prefix is calculated
+    /// from the bytes of the lowercased name. Cargo panics if that would split
+    /// a character, in that case the prefix segment is extended to the end of
+    /// the character instead
+    ///
     /// ```
     /// let crate_name: tame_index::KrateName = "tame-index".try_into().unwrap();
     /// assert_eq!(crate_name.relative_path(Some('/')), "ta/me/tame-index");
     /// ```
     pub fn relative_path(&self, sep: Option<char>) -> String {
-        let name = self.0;
         // Preallocate with the maximum possible width of a crate prefix `aa/bb/`
-        let mut rel_path = String::with_capacity(name.len() + 6);
+        let mut rel_path = String::with_capacity(self.0.len() + 6);
+        self.push_relative_path(&mut rel_path, sep);
+        rel_path
+    }
+
+    /// Writes the relative path to a crate into the provided buffer, the same
+    /// as [`Self::relative_path`]
+    ///
+    /// The buffer is cleared before writing, allowing it to be reused without
+    /// allocating when getting the paths for many crates
+    ///
+    /// ```
+    /// let mut buf = String::new();
+    /// for name in ["tame-index", "cc"] {
+    ///     let crate_name: tame_index::KrateName = name.try_into().unwrap();
+    ///     crate_name.relative_path_into(&mut buf, Some('/'));
+    ///     assert_eq!(buf, crate_name.relative_path(Some('/')));
+    /// }
+    /// ```
+    #[inline]
+    pub fn relative_path_into(&self, out: &mut String, sep: Option<char>) {
+        out.clear();
+        self.push_relative_path(out, sep);
+    }
+
+    /// Appends the relative path to the end of the buffer
+    pub(crate) fn push_relative_path(&self, out: &mut String, sep: Option<char>) {
+        let name = self.0;
+        debug_assert!(
+            !name.contains(&['/', '\\', '.'][..]),
+            "crate name '{name}' was not validated"
+        );
         let sep = sep.unwrap_or(std::path::MAIN_SEPARATOR);
 
-        self.prefix(&mut rel_path, sep);
-        rel_path.push(sep);
-        rel_path.push_str(name);
+        if name.is_ascii() {
+            let start = out.len();
+            out.reserve(name.len() + 6);
 
-        // A valid krate name is ASCII only, we don't need to worry about
-        // lowercasing utf-8
-        rel_path.make_ascii_lowercase();
+            write_prefix(name, out, sep);
+            out.push(sep);
+            out.push_str(name);
 
-        rel_path
+            out[start..].make_ascii_lowercase();
+        } else {
+            let lower = lowercase(name);
+            out.reserve(lower.len() * 2 + 2);
+
+            write_prefix(&lower, out, sep);
+            out.push(sep);
+            out.push_str(&lower);
+        }
+    }
+}
+
+/// Lowercases the name the same way cargo does, which differs from
+/// [`str::to_lowercase`] for a final `Σ`
+#[inline]
+fn lowercase(name: &str) -> String {
+    name.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// Writes the prefix for the name, see [`KrateName::prefix`]
+fn write_prefix(name: &str, acc: &mut String, sep: char) {
+    // Cargo slices the name by byte, which will only split a character for
+    // non-ASCII names, extend the slice to the end of the character instead
+    let boundary = |i: usize| {
+        (i..=name.len())
+            .find(|i| name.is_char_boundary(*i))
+            .unwrap()
+    };
+
+    match name.len() {
+        0 => unreachable!(),
+        1 => acc.push('1'),
+        2 => acc.push('2'),
+        3 => {
+            acc.push('3');
+            acc.push(sep);
+            acc.push_str(&name[..boundary(1)]);
+        }
+        _ => {
+            let first = boundary(2);
+            acc.push_str(&name[..first]);
+            acc.push(sep);
+            acc.push_str(&name[first..boundary(4).max(first)]);
+        }
+    }
+}
+
+impl<'name> KrateName<'name> {
+    /// The crate name
+    #[inline]
+    pub fn as_str(&self) -> &'name str {
+        self.0
+    }
+
+    /// Normalizes the name to ASCII lowercase, the same as crates.io does when
+    /// looking up a crate
+    ///
+    /// Note that [`Self::relative_path`] is already lowercased, so lookups in
+    /// an index are case-insensitive regardless
+    #[inline]
+    pub fn normalized(&self) -> KrateNameBuf {
+        KrateNameBuf(self.0.to_ascii_lowercase())
+    }
+
+    /// Normalizes the name to ASCII lowercase, and folds `-` into `_`, the same
+    /// as crates.io's canonical name that is used to prevent crates from being
+    /// published with names that only differ by case or separator
+    #[inline]
+    pub fn canonical(&self) -> KrateNameBuf {
+        KrateNameBuf(self.0.to_ascii_lowercase().replace('-', "_"))
+    }
+
+    /// Compares the name with another, ignoring ASCII case
+    #[inline]
+    pub fn eq_ignore_case(&self, other: &str) -> bool {
+        self.0.eq_ignore_ascii_case(other)
+    }
+
+    /// Compares the [canonical](Self::canonical) form of the name with another,
+    /// without allocating
+    ///
+    /// This can be used to give a second chance to a lookup that failed due
+    /// to a user typing `-` instead of `_` or vice versa. Since crates.io
+    /// doesn't allow two crates with the same canonical name, at most one of
+    /// the separator variants can exist
+    ///
+    /// ```
+    /// use tame_index::KrateName;
+    ///
+    /// let user_input: KrateName<'_> = "Serde-Json".try_into().unwrap();
+    /// assert!(user_input.eq_canonical("serde_json"));
+    ///
+    /// // The index is case-insensitive, but not separator insensitive, so
+    /// // retry a failed lookup with the separators swapped
+    /// let swapped = user_input.as_str().replace('-', "_");
+    /// let retry: KrateName<'_> = swapped.as_str().try_into().unwrap();
+    /// assert_eq!(retry.relative_path(Some('/')), "se/rd/serde_json");
+    /// ```
+    #[inline]
+    pub fn eq_canonical(&self, other: &str) -> bool {
+        let fold = |c: u8| {
+            if c == b'-' {
+                b'_'
+            } else {
+                c.to_ascii_lowercase()
+            }
+        };
+
+        self.0.len() == other.len()
+            && self
+                .0
+                .bytes()
+                .zip(other.bytes())
+                .all(|(a, b)| fold(a) == fold(b))
+    }
+
+    /// Checks if the name is reserved or otherwise problematic, without
+    /// failing like the [`Self::crates_io`] and [`Self::cargo`] constructors
+    ///
+    /// In addition to the names rejected by those constructors, this also
+    /// reports
+    ///
+    /// * Windows device names regardless of case, as `NUL` is just as
+    ///   problematic as `nul` on a case-insensitive file system
+    /// * Names reserved by crates.io, compared the same way crates.io does,
+    ///   ie. ignoring case and treating `-` and `_` as equal
+    ///
+    /// ```
+    /// use tame_index::{error::ReservedNameKind, KrateName};
+    ///
+    /// let name: KrateName = "Con".try_into().unwrap();
+    /// assert_eq!(name.reserved_reason().unwrap().kind, ReservedNameKind::Windows);
+    ///
+    /// let name: KrateName = "rustc-driver".try_into().unwrap();
+    /// assert_eq!(name.reserved_reason().unwrap().kind, ReservedNameKind::CratesIo);
+    ///
+    /// let name: KrateName = "tame-index".try_into().unwrap();
+    /// assert!(name.reserved_reason().is_none());
+    /// ```
+    pub fn reserved_reason(&self) -> Option<ReservedReason> {
+        let name = self.0;
+
+        if let Ok(i) = DISALLOWED.binary_search_by_key(&name, |(k, _v)| k) {
+            let (reserved, kind) = DISALLOWED[i];
+            return Some(ReservedReason { reserved, kind });
+        }
+
+        if let Some((reserved, kind)) = DISALLOWED
+            .iter()
+            .find(|(k, kind)| *kind == Windows && k.eq_ignore_ascii_case(name))
+        {
+            return Some(ReservedReason {
+                reserved,
+                kind: *kind,
+            });
+        }
+
+        let canonical = name.bytes().map(|c| {
+            if c == b'-' {
+                b'_'
+            } else {
+                c.to_ascii_lowercase()
+            }
+        });
+
+        CRATES_IO_RESERVED
+            .binary_search_by(|r| r.bytes().cmp(canonical.clone()))
+            .ok()
+            .map(|i| ReservedReason {
+                reserved: CRATES_IO_RESERVED[i],
+                kind: ReservedNameKind::CratesIo,
+            })
+    }
+}
+
+/// An owned [`KrateName`]
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct KrateNameBuf(String);
+
+impl KrateNameBuf {
+    /// Borrows the name
+    #[inline]
+    pub fn as_name(&self) -> KrateName<'_> {
+        KrateName(&self.0)
+    }
+
+    /// The crate name
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<KrateName<'_>> for KrateNameBuf {
+    #[inline]
+    fn from(name: KrateName<'_>) -> Self {
+        Self(name.0.to_owned())
+    }
+}
+
+impl TryFrom<String> for KrateNameBuf {
+    type Error = Error;
+
+    /// Validates the name the same as [`KrateName`]'s [`TryFrom<&str>`]
+    #[inline]
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        KrateName::try_from(s.as_str())?;
+        Ok(Self(s))
     }
 }
 
 use std::fmt;
 
+impl fmt::Display for KrateNameBuf {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for KrateNameBuf {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 impl fmt::Display for KrateName<'_> {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -274,6 +691,78 @@ impl fmt::Debug for KrateName<'_> {
     }
 }
 
+impl AsRef<str> for KrateName<'_> {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.0
+    }
+}
+
+impl AsRef<str> for KrateNameBuf {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq<str> for KrateName<'_> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for KrateName<'_> {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<str> for KrateNameBuf {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for KrateNameBuf {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<KrateName<'_>> for KrateNameBuf {
+    #[inline]
+    fn eq(&self, other: &KrateName<'_>) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl serde::Serialize for KrateName<'_> {
+    #[inline]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+impl serde::Serialize for KrateNameBuf {
+    #[inline]
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Deserializes the name from a string, validating it the same as
+/// [`KrateNameBuf`]'s [`TryFrom<String>`]
+impl<'de> serde::Deserialize<'de> for KrateNameBuf {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Self::try_from(name).map_err(serde::de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::KrateName;
@@ -319,6 +808,54 @@ mod test {
         ));
     }
 
+    /// Validates the leading character rules differ between crates.io and cargo
+    #[test]
+    fn leading_underscore() {
+        assert!(matches!(
+            KrateName::crates_io("_underscore").unwrap_err(),
+            Error::InvalidKrateName(InvalidKrateName::InvalidCharacter {
+                index: 0,
+                invalid: '_',
+            })
+        ));
+        assert!(KrateName::cargo("_underscore").is_ok());
+        assert!(matches!(
+            KrateName::cargo("-dash").unwrap_err(),
+            Error::InvalidKrateName(InvalidKrateName::InvalidCharacter {
+                index: 0,
+                invalid: '-',
+            })
+        ));
+    }
+
+    /// Validates that names that could escape the index directory are rejected
+    /// by every constructor
+    #[test]
+    fn rejects_traversal() {
+        for (name, index, invalid) in [
+            ("foo/bar", 3, '/'),
+            ("foo\\bar", 3, '\\'),
+            ("..", 0, '.'),
+            ("a/../../etc", 1, '/'),
+        ] {
+            for res in [
+                KrateName::try_from(name),
+                KrateName::crates_io(name),
+                KrateName::cargo(name),
+            ] {
+                match res.unwrap_err() {
+                    Error::InvalidKrateName(InvalidKrateName::InvalidCharacter {
+                        index: i,
+                        invalid: c,
+                    }) => {
+                        assert_eq!((i, c), (index, invalid), "{name}");
+                    }
+                    err => panic!("unexpected error for '{name}': {err}"),
+                }
+            }
+        }
+    }
+
     /// Validates the crate name doesn't exceed the crates.io limit
     #[test]
     fn rejects_too_long() {
@@ -383,6 +920,27 @@ mod test {
         ));
     }
 
+    /// Validates case and separator insensitive comparisons
+    #[test]
+    fn case_folding() {
+        let name = KrateName("Serde-Json_Derive");
+
+        assert_eq!(name.normalized().as_str(), "serde-json_derive");
+        assert_eq!(name.canonical().as_str(), "serde_json_derive");
+
+        assert!(name.eq_ignore_case("serde-json_derive"));
+        assert!(!name.eq_ignore_case("serde_json_derive"));
+
+        assert!(name.eq_canonical("serde_json_derive"));
+        assert!(name.eq_canonical("SERDE-JSON-DERIVE"));
+        assert!(!name.eq_canonical("serde_json_derives"));
+        assert!(!name.eq_canonical("serde_json"));
+
+        let buf = super::KrateNameBuf::try_from("Serde-Json_Derive".to_owned()).unwrap();
+        assert_eq!(buf.as_name().canonical(), name.canonical());
+        assert!(super::KrateNameBuf::try_from("no.pe".to_owned()).is_err());
+    }
+
     #[inline]
     fn rp(n: &str) -> String {
         KrateName(n).relative_path(Some('/'))
@@ -399,4 +957,149 @@ mod test {
         assert_eq!(rp("_boop-"), "_b/oo/_boop-");
         assert_eq!(rp("Inflector"), "in/fl/inflector");
     }
+
+    /// Validates reserved names are reported without affecting validation
+    #[test]
+    fn reserved_reason() {
+        use crate::error::{ReservedNameKind as Kind, ReservedReason};
+
+        let rr = |n: &'static str| KrateName(n).reserved_reason();
+
+        assert_eq!(
+            rr("yield"),
+            Some(ReservedReason {
+                reserved: "yield",
+                kind: Kind::Keyword
+            })
+        );
+        assert_eq!(
+            rr("LPT1"),
+            Some(ReservedReason {
+                reserved: "lpt1",
+                kind: Kind::Windows
+            })
+        );
+        assert_eq!(
+            rr("proc-macro"),
+            Some(ReservedReason {
+                reserved: "proc-macro",
+                kind: Kind::Standard
+            })
+        );
+        assert_eq!(
+            rr("Compiler-Builtins"),
+            Some(ReservedReason {
+                reserved: "compiler_builtins",
+                kind: Kind::CratesIo
+            })
+        );
+        assert_eq!(rr("SELF"), None);
+        assert_eq!(rr("rustc-drivers"), None);
+
+        // Query only, these are still valid
+        KrateName::crates_io("rustdoc").unwrap();
+        KrateName::cargo("NUL").unwrap();
+    }
+
+    /// Validates non-ASCII names are only accepted by the relaxed constructor,
+    /// and get the same paths as cargo
+    #[test]
+    fn relaxed() {
+        use crate::error::{Error, InvalidKrateName};
+
+        for name in ["Über", "日本語", "_ñ-1", "a"] {
+            KrateName::try_relaxed(name).unwrap();
+            assert!(KrateName::cargo(name).is_err() || name.is_ascii());
+            assert!(KrateName::try_from(name).is_err() || name.is_ascii());
+        }
+
+        for (name, invalid, index) in [("-ü", '-', 0), ("ü/x", '/', 1), ("日.本", '.', 1)] {
+            let Err(Error::InvalidKrateName(InvalidKrateName::InvalidCharacter {
+                invalid: i,
+                index: x,
+            })) = KrateName::try_relaxed(name)
+            else {
+                panic!("expected '{name}' to be invalid");
+            };
+            assert_eq!((i, x), (invalid, index));
+        }
+        assert!(KrateName::try_relaxed("").is_err());
+
+        assert_eq!(rp("Über"), "ü/be/über");
+        assert_eq!(rp("ÀB"), "3/à/àb");
+        assert_eq!(rp("ÀBCD"), "à/bc/àbcd");
+        assert_eq!(rp("日本語"), "日/本/日本語");
+        // Cargo lowercases each character individually
+        assert_eq!(rp("ΑΣ"), "α/σ/ασ");
+        assert_eq!(rp("AbCd"), "ab/cd/abcd");
+
+        let mut prefix = String::new();
+        KrateName("ÜBER").push_lower_prefix(&mut prefix, '/');
+        assert_eq!(prefix, "ü/be");
+    }
+
+    /// Validates names can be compared, used as keys, and (de)serialized
+    #[test]
+    fn traits() {
+        let name = KrateName::cargo("tame-index").unwrap();
+        assert_eq!(name, "tame-index");
+        assert_eq!(name, *"tame-index");
+        assert_ne!(name, "Tame-index");
+        assert_eq!(name.to_string(), "tame-index");
+
+        let set: std::collections::BTreeSet<_> = [name, KrateName("cc"), name].into();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), ["cc", "tame-index"]);
+
+        assert_eq!(serde_json::to_string(&name).unwrap(), r#""tame-index""#);
+
+        let buf: super::KrateNameBuf = serde_json::from_str(r#""tame-index""#).unwrap();
+        assert_eq!(buf, name);
+        assert_eq!(buf, "tame-index");
+        assert_eq!(serde_json::to_string(&buf).unwrap(), r#""tame-index""#);
+        assert!(serde_json::from_str::<super::KrateNameBuf>(r#""../nope""#).is_err());
+    }
+
+    /// Validates the exact prefixes for each kind, registries depend on these
+    /// being verbatim
+    #[test]
+    fn prefixes() {
+        use super::PrefixKind as Kind;
+
+        for (name, kind, prefix, lower, sep) in [
+            ("A", Kind::One, "1", "1", '/'),
+            ("Ab", Kind::Two, "2", "2", '/'),
+            ("AbC", Kind::Three, "3/A", "3/a", '/'),
+            ("AbCd", Kind::Four, "Ab/Cd", "ab/cd", '/'),
+            ("AbCdE", Kind::Four, "Ab/Cd", "ab/cd", '/'),
+            ("AbC", Kind::Three, "3\\A", "3\\a", '\\'),
+            ("AbCdE", Kind::Four, "Ab\\Cd", "ab\\cd", '\\'),
+        ] {
+            let name = KrateName(name);
+            assert_eq!(name.prefix_kind(), kind);
+            assert_eq!(name.prefix(sep), prefix);
+            assert_eq!(name.lower_prefix(sep), lower);
+        }
+
+        let mut acc = String::from("https://dl/");
+        KrateName("AbCdE").push_prefix(&mut acc, '/');
+        assert_eq!(acc, "https://dl/Ab/Cd");
+    }
+
+    /// Validates the buffer is cleared and reused when writing relative paths
+    #[test]
+    fn relative_path_into() {
+        let mut buf = String::new();
+        KrateName("Inflector").relative_path_into(&mut buf, Some('/'));
+        assert_eq!(buf, "in/fl/inflector");
+        let cap = buf.capacity();
+
+        KrateName("ab").relative_path_into(&mut buf, Some('/'));
+        assert_eq!(buf, "2/ab");
+        assert_eq!(buf.capacity(), cap);
+
+        // Only the appended path is lowercased
+        let mut url = String::from("https://Example.com/");
+        KrateName("AbC").push_relative_path(&mut url, Some('/'));
+        assert_eq!(url, "https://Example.com/3/a/abc");
+    }
 }
@@ -15,7 +15,7 @@ pub use index::{
     IndexPath, IndexUrl, SparseIndex,
 };
 pub use krate::{IndexDependency, IndexKrate, IndexVersion};
-pub use krate_name::KrateName;
+pub use krate_name::{KrateName, KrateNameBuf, PrefixKind};
 
 /// Reexports of some crates for easier downstream usage without requiring adding
 /// your own dependencies
@@ -25,6 +25,6 @@ pub mod external {
     pub use http;
     #[cfg(any(feature = "sparse", feature = "local-builder"))]
     pub use reqwest;
-    #[cfg(any(feature = "sparse", feature = "local-builder"))]
+    #[cfg(feature = "tokio")]
     pub use tokio;
 }
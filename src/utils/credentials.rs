@@ -0,0 +1,152 @@
+//! Provides support for retrieving registry tokens the same as cargo, see
+//! <https://doc.rust-lang.org/cargo/reference/registry-authentication.html>
+
+use crate::{Error, Path};
+
+/// A registry token
+///
+/// The [`Debug`](std::fmt::Debug) implementation redacts the token so that it
+/// does not accidentally end up in logs or error messages
+#[derive(Clone, PartialEq, Eq)]
+pub struct Token(String);
+
+impl Token {
+    /// Creates a token
+    #[inline]
+    pub fn new(token: impl Into<String>) -> Self {
+        Self(token.into())
+    }
+
+    /// Gets the actual token value
+    #[inline]
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Debug for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Token(***)")
+    }
+}
+
+/// Retrieves the token for the specified registry, or crates.io if not
+/// specified
+///
+/// 1. Uses `CARGO_REGISTRIES_<name>_TOKEN`, or `CARGO_REGISTRY_TOKEN` for
+///    crates.io, if it is set
+/// 2. Uses `registries.<name>.token`, or `registry.token` for crates.io, in the
+///    `credentials.toml` in the cargo home otherwise
+///
+/// Note that only plain tokens are supported, registries that are configured
+/// with a `secret-key` for [asymmetric tokens](https://doc.rust-lang.org/cargo/reference/registry-authentication.html#cargoasymmetric-tokens)
+/// return `None`, as the token is generated per request by the credential provider
+pub fn registry_token(
+    registry_name: Option<&str>,
+    cargo_home: Option<&Path>,
+) -> Result<Option<Token>, Error> {
+    let registry_name = registry_name.filter(|rn| *rn != "crates-io");
+
+    let named;
+    let key: &[&str] = if let Some(name) = registry_name {
+        named = ["registries", name, "token"];
+        &named
+    } else {
+        &["registry", "token"]
+    };
+
+    let env = crate::index::location::config_env_var(key);
+    match std::env::var(&env) {
+        Ok(token) => return Ok(Some(Token(token))),
+        Err(std::env::VarError::NotUnicode(_)) => return Err(Error::NonUtf8EnvVar(env.into())),
+        Err(std::env::VarError::NotPresent) => {}
+    }
+
+    let cargo_home = match cargo_home {
+        Some(ch) => ch.to_owned(),
+        None => super::cargo_home()?,
+    };
+
+    file_token(&cargo_home, key)
+}
+
+/// Reads the token from the credentials file in the cargo home
+fn file_token(cargo_home: &Path, key: &[&str]) -> Result<Option<Token>, Error> {
+    // Same as config files, cargo uses the legacy extension-less file if both exist
+    let Some(path) = ["credentials", "credentials.toml"]
+        .into_iter()
+        .map(|name| cargo_home.join(name))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+
+    let contents = std::fs::read_to_string(&path).map_err(|err| Error::IoPath(err, path))?;
+    let credentials = toml_span::parse(&contents).map_err(Box::new)?;
+
+    let pointer = key.iter().fold(String::new(), |mut pointer, part| {
+        pointer.push('/');
+        pointer.push_str(part);
+        pointer
+    });
+
+    Ok(credentials
+        .pointer(&pointer)
+        .and_then(|token| token.as_str())
+        .map(Token::new))
+}
+
+#[cfg(test)]
+mod test {
+    use super::Token;
+
+    /// Validates tokens are read from the credentials file, and never printed
+    #[test]
+    fn reads_credentials() {
+        let td = tempfile::tempdir().unwrap();
+        let home = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+
+        let token = |name: Option<&str>| super::registry_token(name, Some(&home)).unwrap();
+
+        assert!(token(Some("tame-index-creds")).is_none());
+
+        std::fs::write(
+            home.join("credentials.toml"),
+            r#"
+[registry]
+token = "crates-io-token"
+
+[registries.tame-index-creds]
+token = "named-token"
+
+[registries.tame-index-asymmetric]
+secret-key = "k3.secret.fake"
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(token(None), Some(Token::new("crates-io-token")));
+        assert_eq!(
+            token(Some("crates-io")),
+            Some(Token::new("crates-io-token"))
+        );
+        assert_eq!(
+            token(Some("tame-index-creds")),
+            Some(Token::new("named-token"))
+        );
+        assert!(token(Some("tame-index-asymmetric")).is_none());
+
+        // The legacy file takes precedence
+        std::fs::write(
+            home.join("credentials"),
+            "[registries.tame-index-creds]\ntoken = \"legacy-token\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            token(Some("tame-index-creds")),
+            Some(Token::new("legacy-token"))
+        );
+
+        assert_eq!(format!("{:?}", Token::new("named-token")), "Token(***)");
+    }
+}
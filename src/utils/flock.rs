@@ -1,7 +1,10 @@
 //! Provides facilities for file locks on unix and windows
 
-use crate::{Error, Path, PathBuf};
-use std::{fs, time::Duration};
+use crate::{index::IndexCache, Error, KrateName, Path, PathBuf};
+use std::{
+    fs,
+    time::{Duration, Instant, SystemTime},
+};
 
 #[cfg_attr(unix, path = "flock/unix.rs")]
 #[cfg_attr(windows, path = "flock/windows.rs")]
@@ -47,6 +50,142 @@ pub enum LockError {
     /// The lock is currently held by another
     #[error("the lock is currently held by another")]
     Contested,
+    /// The wait for the lock was interrupted by the caller
+    #[error("the wait for the lock was interrupted")]
+    Interrupted,
+}
+
+/// Information about a lock that is currently held by another, passed to the
+/// callback given to [`LockOptions::lock_with`]
+#[derive(Debug)]
+pub struct LockContention<'pb> {
+    /// The path of the lock file
+    pub path: &'pb Path,
+    /// How long has been spent waiting for the lock so far
+    pub elapsed: Duration,
+    /// The number of times the callback has been called, starting at 1
+    pub attempt: u32,
+    /// The process that recorded itself as holding the lock, if any, see
+    /// [`LockOptions::current_holder`]
+    pub holder: Option<LockHolder>,
+}
+
+/// Diagnostic information recorded in a lock file by the process that holds
+/// an exclusive lock on it
+///
+/// This is purely advisory, the lock file is only written and read on a best
+/// effort basis, and the information may be stale if the process that wrote
+/// it crashed or was killed before it could clear it, or may not be present at
+/// all if the lock is held by a process that does not use this crate, eg. cargo
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LockHolder {
+    /// The process id of the holder
+    pub pid: u32,
+    /// The name of the holder's executable
+    pub name: String,
+    /// When the holder acquired the lock
+    pub since: SystemTime,
+}
+
+impl LockHolder {
+    /// Creates the holder information for the current process
+    fn current() -> Self {
+        let name = std::env::current_exe()
+            .ok()
+            .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().into_owned()))
+            .unwrap_or_else(|| "unknown".to_owned());
+
+        Self {
+            pid: std::process::id(),
+            name,
+            since: SystemTime::now(),
+        }
+    }
+
+    /// Serializes the holder information to the payload written to the lock file
+    fn serialize(&self) -> String {
+        let since = self
+            .since
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        format!("pid={}\nsince={since}\nname={}\n", self.pid, self.name)
+    }
+
+    /// Parses the payload written to a lock file, returning `None` if it is
+    /// empty or malformed, eg. because it was partially written
+    fn parse(payload: &str) -> Option<Self> {
+        let mut pid = None;
+        let mut since = None;
+        let mut name = None;
+
+        for line in payload.lines() {
+            let (key, value) = line.split_once('=')?;
+            match key {
+                "pid" => pid = Some(value.parse().ok()?),
+                "since" => since = Some(value.parse().ok()?),
+                "name" => name = Some(value.to_owned()),
+                _ => {}
+            }
+        }
+
+        Some(Self {
+            pid: pid?,
+            name: name?,
+            since: SystemTime::UNIX_EPOCH.checked_add(Duration::from_secs(since?))?,
+        })
+    }
+}
+
+/// What to do when a lock is currently held by another, see [`LockOptions::lock_with`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LockWaitDecision {
+    /// Wait for the lock to be released, for up to the specified duration
+    /// before the callback is called again, or infinitely in the case of `None`
+    KeepWaiting(Option<Duration>),
+    /// Stop waiting for the lock
+    GiveUp,
+}
+
+/// The lock file cargo holds while downloading, the only lock file used by
+/// older versions of cargo
+const CARGO_DOWNLOAD_LOCK: &str = ".package-cache";
+/// The lock file newer versions of cargo use to coordinate reads and
+/// modifications of the package cache
+const CARGO_MUTATE_LOCK: &str = ".package-cache-mutate";
+/// Files in `$CARGO_HOME` that are only created by versions of cargo that use
+/// the more granular lock layout, the global cache tracking database being
+/// created at the same time as the mutate lock
+const CARGO_GRANULAR_LAYOUT_PROBES: &[&str] = &[CARGO_MUTATE_LOCK, ".global-cache"];
+
+/// The result of [`LockOptions::clean_stale`]
+#[derive(Debug, Default)]
+pub struct CleanReport {
+    /// The lock files that were removed
+    pub removed: Vec<PathBuf>,
+    /// The lock files that were not removed, and why
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+}
+
+/// The reason a lock file was not removed by [`LockOptions::clean_stale`]
+#[derive(Debug)]
+pub enum SkipReason {
+    /// The lock file was modified more recently than the threshold
+    TooRecent,
+    /// The lock file is currently locked by another
+    Locked,
+    /// An I/O error occurred reading, locking, or removing the lock file
+    Io(std::io::Error),
+}
+
+/// Checks if the path has the name of a per-entry lock file this crate creates
+#[inline]
+fn is_lock_file_name(path: &Path) -> bool {
+    // cargo's global locks are permanent, removing them would only open a
+    // window where two processes can both think they hold the lock, they don't
+    // end in `.lock` so are naturally excluded
+    path.file_name().map_or(false, |name| {
+        name.len() > ".lock".len() && name.ends_with(".lock")
+    })
 }
 
 /// Provides options for creating a [`FileLock`]
@@ -54,6 +193,8 @@ pub struct LockOptions<'pb> {
     path: std::borrow::Cow<'pb, Path>,
     exclusive: bool,
     shared_fallback: bool,
+    unlink_on_drop: bool,
+    ofd: bool,
 }
 
 impl<'pb> LockOptions<'pb> {
@@ -64,6 +205,8 @@ impl<'pb> LockOptions<'pb> {
             path: path.into(),
             exclusive: false,
             shared_fallback: false,
+            unlink_on_drop: false,
+            ofd: false,
         }
     }
 
@@ -71,22 +214,118 @@ impl<'pb> LockOptions<'pb> {
     ///
     /// If specified, the path is used as the root, otherwise it is rooted at
     /// the path determined by `$CARGO_HOME`
+    ///
+    /// Note that newer versions of cargo use more granular locks, see
+    /// [`Self::cargo_download_lock`] and [`Self::cargo_global_cache_lock`]
     #[inline]
     pub fn cargo_package_lock(root: Option<PathBuf>) -> Result<Self, Error> {
+        Self::cargo_lock(root, CARGO_DOWNLOAD_LOCK)
+    }
+
+    /// Creates a new [`Self`] for locking cargo's download lock
+    ///
+    /// This is the lock that should be held while fetching index entries or
+    /// writing them to the local cache, eg. [`crate::index::SparseIndex`] or
+    /// `RemoteGitIndex` operations, as cargo holds it while updating the index
+    /// and downloading crates.
+    ///
+    /// Cargo has always used `$CARGO_HOME/.package-cache` for this, so this is
+    /// the same path as [`Self::cargo_package_lock`], and mutually excludes
+    /// both older versions of cargo, which only have a single lock, and newer
+    /// versions when they are downloading or modifying the package cache.
+    #[inline]
+    pub fn cargo_download_lock(root: Option<PathBuf>) -> Result<Self, Error> {
+        Self::cargo_lock(root, CARGO_DOWNLOAD_LOCK)
+    }
+
+    /// Creates a new [`Self`] for locking cargo's global cache mutation lock
+    ///
+    /// This is the lock that should be held while deleting or otherwise
+    /// modifying existing entries in the package cache, eg. removing stale
+    /// index cache entries, as opposed to adding new ones, which only requires
+    /// [`Self::cargo_download_lock`]. It can also be taken as a [`Self::shared`]
+    /// lock to prevent cargo from modifying the package cache while it is read.
+    ///
+    /// Newer versions of cargo take a shared lock on
+    /// `$CARGO_HOME/.package-cache-mutate` while reading the package cache,
+    /// and an exclusive lock on it, in addition to the download lock, when
+    /// modifying it, eg. during garbage collection. Older versions only use
+    /// the single `.package-cache` lock, so the layout is detected by probing
+    /// for the files newer versions of cargo create in the root, and the
+    /// download lock is used if they are not present.
+    pub fn cargo_global_cache_lock(root: Option<PathBuf>) -> Result<Self, Error> {
+        let root = if let Some(root) = root {
+            root
+        } else {
+            crate::utils::cargo_home()?
+        };
+
+        let file_name = if CARGO_GRANULAR_LAYOUT_PROBES
+            .iter()
+            .any(|probe| root.join(probe).exists())
+        {
+            CARGO_MUTATE_LOCK
+        } else {
+            CARGO_DOWNLOAD_LOCK
+        };
+
+        Self::cargo_lock(Some(root), file_name)
+    }
+
+    #[inline]
+    fn cargo_lock(root: Option<PathBuf>, file_name: &str) -> Result<Self, Error> {
         let mut path = if let Some(root) = root {
             root
         } else {
             crate::utils::cargo_home()?
         };
-        path.push(".package-cache");
+        path.push(file_name);
 
         Ok(Self {
             path: path.into(),
             exclusive: true,
             shared_fallback: false,
+            unlink_on_drop: false,
+            ofd: false,
         })
     }
 
+    /// Creates a new [`Self`] for exclusively locking an individual crate's
+    /// cache entry, allowing coordination between processes at a finer
+    /// granularity than [`Self::cargo_package_lock`]
+    ///
+    /// The lock file is placed next to the cache entry, `<entry>.lock`, and
+    /// its parent directories are created if they don't exist
+    ///
+    /// Note that these lock files are specific to this crate, cargo does not
+    /// know about them
+    #[inline]
+    pub fn crate_cache_lock(cache: &IndexCache, name: KrateName<'_>) -> Self {
+        let mut path = cache.cache_path(name).into_string();
+        path.push_str(".lock");
+
+        Self {
+            path: PathBuf::from(path).into(),
+            exclusive: true,
+            shared_fallback: false,
+            unlink_on_drop: false,
+            ofd: false,
+        }
+    }
+
+    /// If true, the lock file is deleted when the [`FileLock`] is dropped,
+    /// before the lock is released, so that lock files don't accumulate
+    ///
+    /// Note that this is racy, another process that opened the lock file
+    /// before it was deleted can still acquire a lock on it, at the same time
+    /// as a process that creates a new lock file, so this should only be used
+    /// when strict mutual exclusion is not required
+    #[inline]
+    pub fn unlink_on_drop(mut self, unlink: bool) -> Self {
+        self.unlink_on_drop = unlink;
+        self
+    }
+
     /// Will attempt to acquire a shared lock rather than an exclusive one
     #[inline]
     pub fn shared(mut self) -> Self {
@@ -103,11 +342,27 @@ impl<'pb> LockOptions<'pb> {
         self
     }
 
+    /// If true, [open file description](https://man7.org/linux/man-pages/man2/fcntl.2.html)
+    /// locks are used instead of `flock` on Linux, this has no effect on other
+    /// targets
+    ///
+    /// Open file description locks are always used on NFS mounts, where `flock`
+    /// does not work, so this is mainly useful for testing. Note that on Linux,
+    /// `flock` and open file description locks do not interact with each other,
+    /// so every process contending for the lock must use the same kind. Also
+    /// note that a shared lock can't be [upgraded](FileLock::upgrade) as the
+    /// file is opened read only, and these locks require write access.
+    #[inline]
+    pub fn ofd_locks(mut self, ofd: bool) -> Self {
+        self.ofd = ofd;
+        self
+    }
+
     /// Attempts to acquire a lock, but fails immediately if the lock is currently
     /// held
     #[inline]
     pub fn try_lock(&self) -> Result<FileLock, Error> {
-        self.open_and_lock(Option::<fn(&Path) -> Option<Duration>>::None)
+        self.open_and_lock(Option::<fn(&LockContention<'_>) -> LockWaitDecision>::None)
     }
 
     /// Attempts to acquire a lock, waiting if the lock is currently held.
@@ -118,7 +373,356 @@ impl<'pb> LockOptions<'pb> {
     /// of the callback, or infinitely in the case of `None`.
     #[inline]
     pub fn lock(&self, wait: impl Fn(&Path) -> Option<Duration>) -> Result<FileLock, Error> {
-        self.open_and_lock(Some(wait))
+        self.lock_with(|contention| {
+            if contention.attempt == 1 {
+                LockWaitDecision::KeepWaiting(wait(contention.path))
+            } else {
+                LockWaitDecision::GiveUp
+            }
+        })
+    }
+
+    /// Attempts to acquire a lock, waiting if the lock is currently held.
+    ///
+    /// Unlike [`Self::lock`], the callback is called repeatedly while the lock
+    /// is held by another, each time the previous wait has elapsed, allowing
+    /// the caller to eg. periodically log that they are still waiting, or give
+    /// up waiting at any point.
+    ///
+    /// If the callback gives up before any wait has been performed, this fails
+    /// with [`LockError::Contested`], otherwise [`LockError::TimedOut`].
+    #[inline]
+    pub fn lock_with(
+        &self,
+        on_contended: impl Fn(&LockContention<'_>) -> LockWaitDecision,
+    ) -> Result<FileLock, Error> {
+        self.open_and_lock(Some(on_contended))
+    }
+
+    /// The same as [`Self::lock`], but the wait can be interrupted, eg. on
+    /// `Ctrl-C`, by setting `should_interrupt`
+    ///
+    /// Rather than a single blocking wait, the lock is waited on in short
+    /// slices, checking `should_interrupt` between each, failing with
+    /// [`LockError::Interrupted`] as soon as it is observed to be set.
+    pub fn lock_interruptible(
+        &self,
+        wait: impl Fn(&Path) -> Option<Duration>,
+        should_interrupt: &std::sync::atomic::AtomicBool,
+    ) -> Result<FileLock, Error> {
+        const SLICE: Duration = Duration::from_millis(200);
+
+        let interrupted = std::cell::Cell::new(false);
+        let timeout = std::cell::Cell::new(None);
+
+        let res = self.lock_with(|contention| {
+            if should_interrupt.load(std::sync::atomic::Ordering::Relaxed) {
+                interrupted.set(true);
+                return LockWaitDecision::GiveUp;
+            }
+
+            if contention.attempt == 1 {
+                timeout.set(wait(contention.path));
+            }
+
+            match timeout.get() {
+                Some(timeout) => {
+                    let remaining = timeout.saturating_sub(contention.elapsed);
+                    if remaining.is_zero() && contention.attempt > 1 {
+                        LockWaitDecision::GiveUp
+                    } else {
+                        LockWaitDecision::KeepWaiting(Some(remaining.min(SLICE)))
+                    }
+                }
+                None => LockWaitDecision::KeepWaiting(Some(SLICE)),
+            }
+        });
+
+        match res {
+            Err(Error::Lock(mut err)) if interrupted.get() => {
+                err.source = LockError::Interrupted;
+                Err(err.into())
+            }
+            res => res,
+        }
+    }
+
+    /// Attempts to acquire a lock, waiting up to the specified duration if the
+    /// lock is currently held
+    ///
+    /// Fails with [`LockError::TimedOut`] if the lock is not acquired in time
+    #[inline]
+    pub fn try_lock_for(&self, timeout: Duration) -> Result<FileLock, Error> {
+        match Instant::now().checked_add(timeout) {
+            Some(deadline) => self.try_lock_until(deadline),
+            // The timeout is so large it is effectively infinite
+            None => self.lock(|_path| None),
+        }
+    }
+
+    /// Attempts to acquire a lock, waiting until the deadline if the lock is
+    /// currently held
+    ///
+    /// Fails with [`LockError::TimedOut`] if the lock is not acquired in time
+    #[inline]
+    pub fn try_lock_until(&self, deadline: Instant) -> Result<FileLock, Error> {
+        self.try_lock_until_with(deadline, |_path| {})
+    }
+
+    /// The same as [`Self::try_lock_until`], but the callback is called once
+    /// if the lock is currently held, before waiting on it
+    ///
+    /// This can be used to inform the user why they are waiting, the same as
+    /// cargo's `Blocking waiting for file lock on package cache`
+    #[inline]
+    pub fn try_lock_until_with(
+        &self,
+        deadline: Instant,
+        on_contended: impl Fn(&Path),
+    ) -> Result<FileLock, Error> {
+        self.lock(|path| {
+            on_contended(path);
+            Some(deadline.saturating_duration_since(Instant::now()))
+        })
+    }
+
+    /// Reads the diagnostic information recorded in the lock file by the
+    /// process that currently holds an exclusive lock on it, without taking
+    /// the lock
+    ///
+    /// Returns `None` if the lock file doesn't exist, or doesn't contain any
+    /// holder information. Note that the information is advisory only, and may
+    /// be stale if the holder crashed before clearing it. Holder information
+    /// is never recorded on Windows, as other processes are unable to read a
+    /// locked file.
+    pub fn current_holder(&self) -> Result<Option<LockHolder>, Error> {
+        if !sys::RECORDS_HOLDER {
+            return Ok(None);
+        }
+
+        match fs::read(self.path.as_std_path()) {
+            Ok(payload) => Ok(std::str::from_utf8(&payload)
+                .ok()
+                .and_then(LockHolder::parse)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(Error::IoPath(err, self.path.as_ref().to_owned())),
+        }
+    }
+
+    /// Async version of [`Self::lock`]
+    ///
+    /// Opening the lock file and attempting to acquire an uncontested lock is
+    /// done inline, but if the lock is currently held, the blocking wait for it
+    /// is moved to [`tokio::task::spawn_blocking`] so that the runtime is not
+    /// stalled.
+    ///
+    /// The blocking wait is done in short slices, the same as
+    /// [`Self::lock_interruptible`], so if this future is dropped before the
+    /// lock is acquired, the wait is abandoned at the end of the current slice
+    /// and the file is closed, rather than holding the blocking thread until
+    /// the lock is released by its current holder. If the lock happens to be
+    /// acquired during that last slice, it is immediately released.
+    #[cfg(feature = "tokio")]
+    pub async fn lock_async(
+        &self,
+        wait: impl Fn(&Path) -> Option<Duration>,
+    ) -> Result<FileLock, Error> {
+        let (state, file, imp) = self.open_lock_file()?;
+        let path = self.path.as_ref().to_owned();
+        let unlink = self.unlink_on_drop;
+
+        if self
+            .try_do_lock(state, &file, imp)
+            .map_err(|source| self.lock_error(source))?
+        {
+            return Ok(FileLock {
+                file: Some(file),
+                state,
+                path,
+                unlink,
+                imp,
+            }
+            .record_holder());
+        }
+
+        const SLICE: Duration = Duration::from_millis(200);
+
+        /// Signals the blocking wait to stop if the future is dropped
+        struct CancelOnDrop(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+        impl Drop for CancelOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+
+        let timeout = wait(&self.path);
+        let cancelled = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+        let _cancel = CancelOnDrop(cancelled.clone());
+
+        // The output of the task is dropped if the JoinHandle has been dropped,
+        // which in turn releases the lock if it was acquired in the last slice
+        let lock = tokio::task::spawn_blocking(move || -> Result<FileLock, LockError> {
+            let start = Instant::now();
+
+            loop {
+                if cancelled.load(std::sync::atomic::Ordering::Relaxed) {
+                    return Err(LockError::Interrupted);
+                }
+
+                let slice = match timeout {
+                    Some(timeout) => {
+                        let remaining = timeout.saturating_sub(start.elapsed());
+                        if remaining.is_zero() {
+                            return Err(LockError::TimedOut);
+                        }
+                        remaining.min(SLICE)
+                    }
+                    None => SLICE,
+                };
+
+                match blocking_lock(&file, state, Some(slice), imp) {
+                    Ok(()) => break,
+                    // The slice elapsed, check if we were cancelled and try again
+                    Err(LockError::TimedOut) => {}
+                    Err(err) => return Err(err),
+                }
+            }
+
+            Ok(FileLock {
+                file: Some(file),
+                state,
+                path,
+                unlink,
+                imp,
+            }
+            .record_holder())
+        })
+        .await
+        .map_err(|je| {
+            self.lock_error(LockError::Lock(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                je,
+            )))
+        })?;
+
+        lock.map_err(|source| self.lock_error(source).into())
+    }
+
+    /// Removes stale lock files in the specified directory and its
+    /// subdirectories
+    ///
+    /// Only the `<entry>.lock` files created by [`Self::crate_cache_lock`] are
+    /// considered, cargo's `.package-cache` and `.package-cache-mutate` files
+    /// are permanent and are never removed. To avoid deleting unrelated
+    /// `.lock` files, eg. `Cargo.lock`, only files that are empty or contain a
+    /// [`LockHolder`] are considered lock files.
+    ///
+    /// A lock file is only deleted if it was last modified at least
+    /// `older_than` ago, and an exclusive lock can be acquired on it without
+    /// waiting, it is never deleted if it is currently locked.
+    ///
+    /// Note that this is still racy, a process that opened the lock file
+    /// before it is unlinked, but only locks it after it has been removed,
+    /// will hold a lock on a file that no longer exists, while another process
+    /// can create and lock a new file at the same path. `older_than` should
+    /// be large enough that no process could still be about to use the entry.
+    pub fn clean_stale(root: &Path, older_than: Duration) -> Result<CleanReport, Error> {
+        let mut report = CleanReport::default();
+        let now = SystemTime::now();
+
+        let mut dirs = vec![root.to_owned()];
+        while let Some(dir) = dirs.pop() {
+            let entries = fs::read_dir(&dir).map_err(|err| Error::IoPath(err, dir.clone()))?;
+
+            for entry in entries {
+                let entry = entry.map_err(|err| Error::IoPath(err, dir.clone()))?;
+                let path = crate::utils::to_utf8_path_buf(entry.path())?;
+                let file_type = entry
+                    .file_type()
+                    .map_err(|err| Error::IoPath(err, path.clone()))?;
+
+                if file_type.is_dir() {
+                    dirs.push(path);
+                } else if file_type.is_file() && is_lock_file_name(&path) {
+                    Self::clean_lock_file(path, now, older_than, &mut report);
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn clean_lock_file(
+        path: PathBuf,
+        now: SystemTime,
+        older_than: Duration,
+        report: &mut CleanReport,
+    ) {
+        let metadata = match fs::metadata(&path) {
+            Ok(md) => md,
+            Err(err) => {
+                report.skipped.push((path, SkipReason::Io(err)));
+                return;
+            }
+        };
+
+        // Don't touch files that are not ours, eg. Cargo.lock
+        if metadata.len() > 0 {
+            match fs::read(&path) {
+                Ok(payload) => {
+                    if std::str::from_utf8(&payload)
+                        .ok()
+                        .and_then(LockHolder::parse)
+                        .is_none()
+                    {
+                        return;
+                    }
+                }
+                Err(err) => {
+                    report.skipped.push((path, SkipReason::Io(err)));
+                    return;
+                }
+            }
+        }
+
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+        if age < older_than {
+            report.skipped.push((path, SkipReason::TooRecent));
+            return;
+        }
+
+        let lock = match LockOptions::new(&path).exclusive(false).try_lock() {
+            Ok(lock) => lock,
+            Err(Error::Lock(FileLockError {
+                source: LockError::Contested,
+                ..
+            })) => {
+                report.skipped.push((path, SkipReason::Locked));
+                return;
+            }
+            Err(err) => {
+                report.skipped.push((
+                    path,
+                    SkipReason::Io(std::io::Error::new(std::io::ErrorKind::Other, err)),
+                ));
+                return;
+            }
+        };
+
+        // Remove the file while we still hold the lock, so that no other
+        // process can have acquired it in the meantime
+        let res = fs::remove_file(&path);
+        drop(lock);
+
+        match res {
+            Ok(()) => report.removed.push(path),
+            Err(err) => report.skipped.push((path, SkipReason::Io(err))),
+        }
     }
 
     fn open(&self, opts: &fs::OpenOptions) -> Result<fs::File, FileLockError> {
@@ -149,10 +753,17 @@ impl<'pb> LockOptions<'pb> {
         })
     }
 
-    fn open_and_lock(
-        &self,
-        wait: Option<impl Fn(&Path) -> Option<Duration>>,
-    ) -> Result<FileLock, Error> {
+    #[inline]
+    fn lock_error(&self, source: LockError) -> FileLockError {
+        FileLockError {
+            path: self.path.as_ref().to_owned(),
+            source,
+        }
+    }
+
+    /// Opens the lock file, determining the kind of lock to take, and the
+    /// mechanism used to take it
+    fn open_lock_file(&self) -> Result<(LockState, fs::File, LockImpl), Error> {
         let (state, file) = if self.exclusive {
             match self.open(&sys::open_opts(true)) {
                 Ok(file) => (LockState::Exclusive, file),
@@ -173,92 +784,201 @@ impl<'pb> LockOptions<'pb> {
             (LockState::Shared, self.open(&sys::open_opts(false))?)
         };
 
-        self.do_lock(state, &file, wait)
-            .map_err(|source| FileLockError {
-                path: self.path.as_ref().to_owned(),
-                source,
-            })?;
+        let imp = self.lock_impl().map_err(|source| self.lock_error(source))?;
+
+        // Unlike `flock`, an exclusive open file description lock requires the
+        // file to be open for writing, so if possible we reopen the file so
+        // that the shared lock can later be upgraded
+        let file = if imp == LockImpl::Ofd && state == LockState::Shared {
+            fs::OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(self.path.as_std_path())
+                .unwrap_or(file)
+        } else {
+            file
+        };
+
+        Ok((state, file, imp))
+    }
+
+    fn open_and_lock(
+        &self,
+        on_contended: Option<impl Fn(&LockContention<'_>) -> LockWaitDecision>,
+    ) -> Result<FileLock, Error> {
+        let (state, file, imp) = self.open_lock_file()?;
+
+        self.do_lock(state, &file, imp, on_contended)
+            .map_err(|source| self.lock_error(source))?;
 
         Ok(FileLock {
             file: Some(file),
             state,
-        })
+            path: self.path.as_ref().to_owned(),
+            unlink: self.unlink_on_drop,
+            imp,
+        }
+        .record_holder())
     }
 
     fn do_lock(
         &self,
         state: LockState,
         file: &fs::File,
-        wait: Option<impl Fn(&Path) -> Option<std::time::Duration>>,
+        imp: LockImpl,
+        on_contended: Option<impl Fn(&LockContention<'_>) -> LockWaitDecision>,
     ) -> Result<(), LockError> {
-        #[cfg(all(target_os = "linux", not(target_env = "musl")))]
-        fn is_on_nfs_mount(path: &crate::Path) -> bool {
-            use std::os::unix::prelude::*;
+        if self.try_do_lock(state, file, imp)? {
+            return Ok(());
+        }
+
+        let Some(on_contended) = on_contended else {
+            return Err(LockError::Contested);
+        };
 
-            let path = match std::ffi::CString::new(path.as_os_str().as_bytes()) {
-                Ok(path) => path,
-                Err(_) => return false,
+        let start = Instant::now();
+        let mut attempt = 1;
+
+        loop {
+            // Signal to the caller that we are about to enter a blocking operation
+            // and whether they want to assign a timeout to it
+            let contention = LockContention {
+                path: &self.path,
+                elapsed: start.elapsed(),
+                attempt,
+                holder: self.current_holder().ok().flatten(),
             };
 
-            #[allow(unsafe_code)]
-            unsafe {
-                let mut buf: libc::statfs = std::mem::zeroed();
-                let r = libc::statfs(path.as_ptr(), &mut buf);
+            let timeout = match on_contended(&contention) {
+                LockWaitDecision::KeepWaiting(timeout) => timeout,
+                LockWaitDecision::GiveUp => {
+                    return Err(if attempt == 1 {
+                        LockError::Contested
+                    } else {
+                        LockError::TimedOut
+                    });
+                }
+            };
 
-                r == 0 && buf.f_type as u32 == libc::NFS_SUPER_MAGIC as u32
+            match blocking_lock(file, state, timeout, imp) {
+                Err(LockError::TimedOut) if timeout.is_some() => attempt += 1,
+                res => return res,
             }
         }
+    }
 
-        #[cfg(any(not(target_os = "linux"), target_env = "musl"))]
-        fn is_on_nfs_mount(_path: &crate::Path) -> bool {
-            false
-        }
-
-        // File locking on Unix is currently implemented via `flock`, which is known
-        // to be broken on NFS. We could in theory just ignore errors that happen on
+    /// Determines how the lock file is locked
+    fn lock_impl(&self) -> Result<LockImpl, LockError> {
+        // File locking on Unix is implemented via `flock`, which is known to be
+        // broken on NFS. We could in theory just ignore errors that happen on
         // NFS, but apparently the failure mode [1] for `flock` on NFS is **blocking
         // forever**, even if the "non-blocking" flag is passed!
         //
-        // As a result, we just skip all file locks entirely on NFS mounts. That
-        // should avoid calling any `flock` functions at all, and it wouldn't work
-        // there anyway.
+        // As a result, we never call any `flock` functions on NFS mounts, and
+        // instead use open file description locks, which are implemented on
+        // top of NFSv4's locking. If those are not available, we skip all file
+        // locks entirely, as nothing else would work there anyway.
         //
         // [1]: https://github.com/rust-lang/cargo/issues/2615
         if is_on_nfs_mount(&self.path) {
-            return Err(LockError::Nfs);
+            return if sys::SUPPORTS_OFD {
+                Ok(LockImpl::Ofd)
+            } else {
+                Err(LockError::Nfs)
+            };
         }
 
-        match sys::try_lock(file, state) {
-            Ok(()) => return Ok(()),
+        Ok(if self.ofd && sys::SUPPORTS_OFD {
+            LockImpl::Ofd
+        } else {
+            LockImpl::Native
+        })
+    }
+
+    /// Attempts to acquire the lock without blocking, returning false if the
+    /// lock is currently held
+    fn try_do_lock(
+        &self,
+        state: LockState,
+        file: &fs::File,
+        imp: LockImpl,
+    ) -> Result<bool, LockError> {
+        match sys::try_lock(file, state, imp) {
+            Ok(()) => Ok(true),
 
             // In addition to ignoring NFS which is commonly not working we also
             // just ignore locking on filesystems that look like they don't
             // implement file locking.
-            Err(e) if sys::is_unsupported(&e) => return Err(LockError::NotSupported),
-
-            Err(e) => {
-                if !sys::is_contended(&e) {
-                    return Err(LockError::Lock(e));
+            Err(e) if sys::is_unsupported(&e) => {
+                if imp == LockImpl::Ofd && is_on_nfs_mount(&self.path) {
+                    Err(LockError::Nfs)
+                } else {
+                    Err(LockError::NotSupported)
                 }
             }
+
+            Err(e) if sys::is_contended(&e) => Ok(false),
+            Err(e) => Err(LockError::Lock(e)),
         }
+    }
+}
 
-        // Signal to the caller that we are about to enter a blocking operation
-        // and whether they want to assign a timeout to it
-        if let Some(wait) = wait {
-            let timeout = wait(&self.path);
+#[cfg(all(target_os = "linux", not(target_env = "musl")))]
+fn is_on_nfs_mount(path: &crate::Path) -> bool {
+    use std::os::unix::prelude::*;
 
-            sys::lock(file, state, timeout).map_err(|e| {
-                if sys::is_timed_out(&e) {
-                    LockError::TimedOut
-                } else {
-                    LockError::Lock(e)
-                }
-            })
+    let path = match std::ffi::CString::new(path.as_os_str().as_bytes()) {
+        Ok(path) => path,
+        Err(_) => return false,
+    };
+
+    #[allow(unsafe_code)]
+    unsafe {
+        let mut buf: libc::statfs = std::mem::zeroed();
+        let r = libc::statfs(path.as_ptr(), &mut buf);
+
+        r == 0 && buf.f_type as u32 == libc::NFS_SUPER_MAGIC as u32
+    }
+}
+
+#[cfg(any(not(target_os = "linux"), target_env = "musl"))]
+fn is_on_nfs_mount(_path: &crate::Path) -> bool {
+    false
+}
+
+/// Blocks until the lock is acquired, or the timeout, if any, elapses
+#[inline]
+fn blocking_lock(
+    file: &fs::File,
+    state: LockState,
+    timeout: Option<Duration>,
+    imp: LockImpl,
+) -> Result<(), LockError> {
+    sys::lock(file, state, timeout, imp).map_err(|e| {
+        if sys::is_timed_out(&e) {
+            LockError::TimedOut
         } else {
-            Err(LockError::Contested)
+            LockError::Lock(e)
         }
-    }
+    })
+}
+
+/// The kind of lock held by a [`FileLock`]
+#[derive(PartialEq, Eq, Copy, Clone, Debug)]
+pub enum LockStateKind {
+    /// A shared lock, other processes may also hold shared locks
+    Shared,
+    /// An exclusive lock, no other process may hold a lock
+    Exclusive,
+}
+
+/// The mechanism used to lock the file
+#[derive(PartialEq, Copy, Clone, Debug)]
+enum LockImpl {
+    /// `flock` on unix, `LockFileEx` on windows
+    Native,
+    /// Open file description locks, only supported on Linux
+    Ofd,
 }
 
 #[derive(PartialEq, Copy, Clone, Debug)]
@@ -272,9 +992,13 @@ enum LockState {
 ///
 /// The lock is released when this is dropped, or the program exits for any reason,
 /// including `SIGKILL` or power loss
+#[derive(Debug)]
 pub struct FileLock {
     file: Option<std::fs::File>,
     state: LockState,
+    path: PathBuf,
+    unlink: bool,
+    imp: LockImpl,
 }
 
 impl FileLock {
@@ -286,16 +1010,215 @@ impl FileLock {
         Self {
             file: None,
             state: LockState::Unlocked,
+            path: PathBuf::new(),
+            unlink: false,
+            imp: LockImpl::Native,
         }
     }
+
+    /// Returns true if this is holding a lock, ie. it was not created via
+    /// [`Self::unlocked`]
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.state().is_some()
+    }
+
+    /// The kind of lock being held, or `None` if this is unlocked
+    #[inline]
+    pub fn state(&self) -> Option<LockStateKind> {
+        match self.state {
+            LockState::Shared => Some(LockStateKind::Shared),
+            LockState::Exclusive => Some(LockStateKind::Exclusive),
+            LockState::Unlocked => None,
+        }
+    }
+
+    /// The path of the lock file, which is empty if this is unlocked
+    #[inline]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Converts this into a [`SharedFileLock`] so that it can be shared by
+    /// multiple threads or tasks
+    #[inline]
+    pub fn into_shared(self) -> SharedFileLock {
+        self.into()
+    }
+
+    /// Releases the lock, returning any error that occurs
+    ///
+    /// Dropping a [`FileLock`] also releases the lock, but ignores errors
+    #[inline]
+    pub fn unlock(mut self) -> Result<(), Error> {
+        self.release()
+    }
+
+    /// Releases the lock, leaving this in the same state as [`Self::unlocked`]
+    ///
+    /// This is useful when the lock is stored in a struct that outlives the
+    /// need for the lock. Calling this on an unlocked [`FileLock`] does nothing.
+    pub fn release(&mut self) -> Result<(), Error> {
+        let state = std::mem::replace(&mut self.state, LockState::Unlocked);
+        let path = std::mem::take(&mut self.path);
+        let Some(file) = self.file.take() else {
+            return Ok(());
+        };
+        if state == LockState::Unlocked {
+            return Ok(());
+        }
+
+        if state == LockState::Exclusive {
+            let _ = sys::clear_holder(&file);
+        }
+
+        if std::mem::take(&mut self.unlink) {
+            let _ = fs::remove_file(&path);
+        }
+
+        sys::unlock(&file, self.imp).map_err(|err| {
+            FileLockError {
+                path,
+                source: LockError::Lock(err),
+            }
+            .into()
+        })
+    }
+
+    /// Attempts to upgrade a shared lock to an exclusive lock, but fails
+    /// immediately if another shared lock is currently held
+    ///
+    /// See [`Self::upgrade`]
+    #[inline]
+    pub fn try_upgrade(self) -> Result<Self, Error> {
+        self.do_upgrade(Option::<fn(&Path) -> Option<Duration>>::None)
+    }
+
+    /// Upgrades a shared lock to an exclusive lock, waiting if another shared
+    /// lock is currently held, the same as [`LockOptions::lock`]
+    ///
+    /// Exclusive and unlocked locks are returned as is.
+    ///
+    /// Note that the upgrade is not atomic, the shared lock is released before
+    /// the exclusive lock is acquired, so another process may acquire the lock
+    /// in between. If the upgrade fails, the lock is released entirely. Also
+    /// note that if two holders of the shared lock both attempt to upgrade
+    /// without a timeout, they may wait on each other indefinitely.
+    #[inline]
+    pub fn upgrade(self, wait: impl Fn(&Path) -> Option<Duration>) -> Result<Self, Error> {
+        self.do_upgrade(Some(wait))
+    }
+
+    /// Records the current process as the holder of an exclusive lock
+    ///
+    /// Failing to write the diagnostics is ignored, as they are only advisory
+    fn record_holder(self) -> Self {
+        if let (LockState::Exclusive, Some(file)) = (self.state, &self.file) {
+            let _ = sys::write_holder(file, LockHolder::current().serialize().as_bytes());
+        }
+
+        self
+    }
+
+    fn do_upgrade(
+        mut self,
+        wait: Option<impl Fn(&Path) -> Option<Duration>>,
+    ) -> Result<Self, Error> {
+        let (LockState::Shared, Some(file)) = (self.state, &self.file) else {
+            return Ok(self);
+        };
+
+        let imp = self.imp;
+        let res = sys::prepare_upgrade(file, imp)
+            .map_err(LockError::Lock)
+            .and_then(|()| match sys::try_lock(file, LockState::Exclusive, imp) {
+                Ok(()) => Ok(()),
+                Err(e) if sys::is_contended(&e) => {
+                    if let Some(wait) = wait {
+                        blocking_lock(file, LockState::Exclusive, wait(&self.path), imp)
+                    } else {
+                        Err(LockError::Contested)
+                    }
+                }
+                Err(e) => Err(LockError::Lock(e)),
+            });
+
+        match res {
+            Ok(()) => {
+                self.state = LockState::Exclusive;
+                Ok(self.record_holder())
+            }
+            Err(source) => Err(FileLockError {
+                path: self.path.clone(),
+                source,
+            }
+            .into()),
+        }
+    }
+
+    /// Downgrades an exclusive lock to a shared lock, allowing other processes
+    /// to acquire shared locks
+    ///
+    /// Shared and unlocked locks are returned as is. Unlike [`Self::upgrade`],
+    /// this never waits, as no other process can hold a lock on the file.
+    pub fn downgrade(mut self) -> Result<Self, Error> {
+        let (LockState::Exclusive, Some(file)) = (self.state, &self.file) else {
+            return Ok(self);
+        };
+
+        // Clear the holder before downgrading, as once other processes can
+        // take shared locks we are no longer the sole holder
+        let _ = sys::clear_holder(file);
+
+        match sys::downgrade(file, self.imp) {
+            Ok(()) => {
+                self.state = LockState::Shared;
+                Ok(self)
+            }
+            Err(err) => Err(FileLockError {
+                path: self.path.clone(),
+                source: LockError::Lock(err),
+            }
+            .into()),
+        }
+    }
+}
+
+/// A [`FileLock`] that can be cheaply cloned and shared between threads or
+/// tasks
+///
+/// The lock is released once the last clone is dropped. Since this derefs to
+/// [`FileLock`], it can be passed to any API that takes a `&FileLock`.
+#[derive(Clone, Debug)]
+pub struct SharedFileLock(std::sync::Arc<FileLock>);
+
+impl SharedFileLock {
+    /// Retrieves the inner [`FileLock`] if this is the only clone, eg. to
+    /// release it via [`FileLock::unlock`] and observe any errors
+    #[inline]
+    pub fn try_unwrap(self) -> Result<FileLock, Self> {
+        std::sync::Arc::try_unwrap(self.0).map_err(Self)
+    }
+}
+
+impl From<FileLock> for SharedFileLock {
+    #[inline]
+    fn from(lock: FileLock) -> Self {
+        Self(std::sync::Arc::new(lock))
+    }
+}
+
+impl std::ops::Deref for SharedFileLock {
+    type Target = FileLock;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
 }
 
 impl Drop for FileLock {
     fn drop(&mut self) {
-        if self.state != LockState::Unlocked {
-            if let Some(f) = self.file.take() {
-                let _ = sys::unlock(&f);
-            }
-        }
+        let _ = self.release();
     }
 }
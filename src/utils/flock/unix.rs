@@ -1,6 +1,6 @@
 #![allow(unsafe_code)]
 
-use super::LockState;
+use super::{LockImpl, LockState};
 use std::{fs::File, io::Error, os::unix::io::AsRawFd, time::Duration};
 
 type Result = std::io::Result<()>;
@@ -15,6 +15,9 @@ macro_rules! flock_flag {
     };
 }
 
+/// Open file description locks are only available on Linux
+pub(super) const SUPPORTS_OFD: bool = cfg!(target_os = "linux");
+
 macro_rules! error {
     ($func:expr) => {
         if $func != 0 {
@@ -36,12 +39,25 @@ pub(super) fn open_opts(exclusive: bool) -> std::fs::OpenOptions {
 }
 
 #[inline]
-pub(super) fn try_lock(file: &File, state: LockState) -> Result {
-    flock(file, flock_flag!(state) | libc::LOCK_NB)
+pub(super) fn try_lock(file: &File, state: LockState, imp: LockImpl) -> Result {
+    match imp {
+        LockImpl::Native => flock(file, flock_flag!(state) | libc::LOCK_NB),
+        LockImpl::Ofd => ofd_try_lock(file, state),
+    }
 }
 
 #[inline]
-pub(super) fn lock(file: &File, state: LockState, timeout: Option<Duration>) -> Result {
+pub(super) fn lock(
+    file: &File,
+    state: LockState,
+    timeout: Option<Duration>,
+    imp: LockImpl,
+) -> Result {
+    let lock = move |file: &File| match imp {
+        LockImpl::Native => flock(file, flock_flag!(state)),
+        LockImpl::Ofd => ofd_lock(file, state),
+    };
+
     if let Some(timeout) = timeout {
         static SIG_HANDLER: std::sync::Once = std::sync::Once::new();
 
@@ -90,7 +106,7 @@ pub(super) fn lock(file: &File, state: LockState, timeout: Option<Duration>) ->
             .name("flock wait".into())
             .spawn(move || unsafe {
                 *(tid as *mut _) = libc::pthread_self();
-                let res = flock(&*(file_ptr as *const _), flock_flag!(state));
+                let res = lock(&*(file_ptr as *const _));
                 tx.send(res).unwrap();
             })?;
 
@@ -118,13 +134,28 @@ pub(super) fn lock(file: &File, state: LockState, timeout: Option<Duration>) ->
             Err(_) => unreachable!(),
         }
     } else {
-        flock(file, flock_flag!(state))
+        lock(file)
     }
 }
 
 #[inline]
-pub(super) fn unlock(file: &File) -> Result {
-    flock(file, libc::LOCK_UN)
+pub(super) fn unlock(file: &File, imp: LockImpl) -> Result {
+    match imp {
+        LockImpl::Native => flock(file, libc::LOCK_UN),
+        LockImpl::Ofd => ofd_unlock(file),
+    }
+}
+
+/// Both flock and fcntl convert the existing lock when locking again, so there
+/// is nothing to do before taking the exclusive lock
+#[inline]
+pub(super) fn prepare_upgrade(_file: &File, _imp: LockImpl) -> Result {
+    Ok(())
+}
+
+#[inline]
+pub(super) fn downgrade(file: &File, imp: LockImpl) -> Result {
+    try_lock(file, LockState::Shared, imp)
 }
 
 #[inline]
@@ -133,6 +164,85 @@ fn flock(file: &File, flag: libc::c_int) -> Result {
     Ok(())
 }
 
+/// Locks, or unlocks, the entire file with an open file description lock
+#[cfg(target_os = "linux")]
+fn fcntl(file: &File, cmd: libc::c_int, state: LockState) -> Result {
+    // A length of 0 covers the entire file, regardless of its size, and the
+    // pid must be 0 for open file description locks
+    let mut fl: libc::flock = unsafe { std::mem::zeroed() };
+    fl.l_type = match state {
+        LockState::Shared => libc::F_RDLCK,
+        LockState::Exclusive => libc::F_WRLCK,
+        LockState::Unlocked => libc::F_UNLCK,
+    } as _;
+    fl.l_whence = libc::SEEK_SET as _;
+
+    if unsafe { libc::fcntl(file.as_raw_fd(), cmd, &fl) } != 0 {
+        let err = Error::last_os_error();
+
+        // Kernels older than 3.15 don't support open file description locks,
+        // and NFS mounts without lock support fail with ENOLCK
+        return Err(match err.raw_os_error() {
+            Some(libc::EINVAL | libc::ENOLCK) => Error::from_raw_os_error(libc::ENOTSUP),
+            _ => err,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+#[inline]
+fn ofd_try_lock(file: &File, state: LockState) -> Result {
+    fcntl(file, libc::F_OFD_SETLK, state)
+}
+
+#[cfg(target_os = "linux")]
+#[inline]
+fn ofd_lock(file: &File, state: LockState) -> Result {
+    fcntl(file, libc::F_OFD_SETLKW, state)
+}
+
+#[cfg(target_os = "linux")]
+#[inline]
+fn ofd_unlock(file: &File) -> Result {
+    fcntl(file, libc::F_OFD_SETLK, LockState::Unlocked)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn ofd_try_lock(_file: &File, _state: LockState) -> Result {
+    unreachable!("open file description locks are only supported on linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn ofd_lock(_file: &File, _state: LockState) -> Result {
+    unreachable!("open file description locks are only supported on linux")
+}
+
+#[cfg(not(target_os = "linux"))]
+fn ofd_unlock(_file: &File) -> Result {
+    unreachable!("open file description locks are only supported on linux")
+}
+
+/// Lock holder diagnostics can be read by other processes while the lock is
+/// held, as locks on unix are only advisory
+pub(super) const RECORDS_HOLDER: bool = true;
+
+/// Replaces the contents of the lock file with the holder diagnostics
+#[inline]
+pub(super) fn write_holder(file: &File, payload: &[u8]) -> Result {
+    use std::os::unix::fs::FileExt;
+
+    file.write_all_at(payload, 0)?;
+    file.set_len(payload.len() as u64)
+}
+
+/// Removes any holder diagnostics from the lock file
+#[inline]
+pub(super) fn clear_holder(file: &File) -> Result {
+    file.set_len(0)
+}
+
 #[inline]
 pub(super) fn is_unsupported(err: &std::io::Error) -> bool {
     match err.raw_os_error() {
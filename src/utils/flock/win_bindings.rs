@@ -7,6 +7,8 @@
 )]
 #[link(name = "kernel32")]
 extern "system" {
+    #[link_name = "CancelIoEx"]
+    pub fn cancel_io_ex(file: Handle, overlapped: *const Overlapped) -> Bool;
     #[link_name = "CloseHandle"]
     pub fn close_handle(object: Handle) -> Bool;
     #[link_name = "CreateEventA"]
@@ -16,6 +18,13 @@ extern "system" {
         initial_state: Bool,
         name: Pcstr,
     ) -> Handle;
+    #[link_name = "GetOverlappedResult"]
+    pub fn get_overlapped_result(
+        file: Handle,
+        overlapped: *const Overlapped,
+        number_of_bytes_transferred: *mut u32,
+        wait: Bool,
+    ) -> Bool;
     #[link_name = "LockFileEx"]
     pub fn lock_file_ex(
         file: Handle,
@@ -73,5 +82,6 @@ pub mod Win32Error {
     pub const ErrorLockViolation: Enum = 33;
     pub const WaitIoCompletion: Enum = 192;
     pub const WaitTimeout: Enum = 258;
+    pub const ErrorOperationAborted: Enum = 995;
     pub const ErrorIoPending: Enum = 997;
 }
@@ -4,7 +4,7 @@
 //! <https://learn.microsoft.com/en-us/windows/win32/fileio/locking-and-unlocking-byte-ranges-in-files>
 //! in addition to cargo
 
-use super::LockState;
+use super::{LockImpl, LockState};
 use std::{fs::File, io::Error, os::windows::io::AsRawHandle, time::Duration};
 
 type Result = std::io::Result<()>;
@@ -41,8 +41,26 @@ pub(super) fn open_opts(exclusive: bool) -> std::fs::OpenOptions {
     o
 }
 
+/// Open file description locks are only available on Linux
+pub(super) const SUPPORTS_OFD: bool = false;
+
+/// Byte range locks on Windows are mandatory, so other processes can't read
+/// the lock file while it is held, and the lock file is opened for overlapped
+/// I/O, so holder diagnostics are never recorded
+pub(super) const RECORDS_HOLDER: bool = false;
+
+#[inline]
+pub(super) fn write_holder(_file: &File, _payload: &[u8]) -> Result {
+    Ok(())
+}
+
 #[inline]
-pub(super) fn try_lock(file: &File, state: LockState) -> Result {
+pub(super) fn clear_holder(_file: &File) -> Result {
+    Ok(())
+}
+
+#[inline]
+pub(super) fn try_lock(file: &File, state: LockState, _imp: LockImpl) -> Result {
     flock(
         file,
         flock_flag!(state) | LockFileFlags::LockfileFailImmediately,
@@ -51,7 +69,12 @@ pub(super) fn try_lock(file: &File, state: LockState) -> Result {
 }
 
 #[inline]
-pub(super) fn lock(file: &File, state: LockState, timeout: Option<Duration>) -> Result {
+pub(super) fn lock(
+    file: &File,
+    state: LockState,
+    timeout: Option<Duration>,
+    _imp: LockImpl,
+) -> Result {
     flock(file, flock_flag!(state), timeout)
 }
 
@@ -90,10 +113,18 @@ fn flock(file: &File, flags: u32, timeout: Option<Duration>) -> Result {
 
                 match wait_for_single_object(overlapped.event, timeout) {
                     Win32Error::WaitObject0 => Ok(()),
-                    Win32Error::WaitTimeout => {
-                        Err(Error::from_raw_os_error(Win32Error::WaitTimeout as _))
+                    Win32Error::WaitTimeout => cancel(file, &overlapped).map_err(|err| {
+                        if err.raw_os_error() == Some(Win32Error::ErrorOperationAborted as i32) {
+                            Error::from_raw_os_error(Win32Error::WaitTimeout as _)
+                        } else {
+                            err
+                        }
+                    }),
+                    _ => {
+                        let err = Error::last_os_error();
+                        let _ = cancel(file, &overlapped);
+                        Err(err)
                     }
-                    _ => Err(Error::last_os_error()),
                 }
             } else {
                 Err(err)
@@ -107,7 +138,43 @@ fn flock(file: &File, flags: u32, timeout: Option<Duration>) -> Result {
     }
 }
 
-pub(super) fn unlock(file: &File) -> Result {
+/// Cancels a pending lock request and waits for the cancellation to complete
+///
+/// The request refers to the [`Overlapped`], so it must not outlive it, and
+/// would otherwise still be granted at some later point, eg. in between the
+/// slices of a [`super::LockOptions::lock_with`] wait. `Ok` is returned if the
+/// lock was granted before it could be cancelled.
+unsafe fn cancel(file: &File, overlapped: &Overlapped) -> Result {
+    let handle = file.as_raw_handle() as Handle;
+
+    // This fails if the request has already completed, in which case we still
+    // want its result
+    cancel_io_ex(handle, overlapped);
+
+    let mut transferred = 0;
+    if get_overlapped_result(handle, overlapped, &mut transferred, 1) == 0 {
+        Err(Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+/// LockFileEx can't convert a shared lock into an exclusive one, and an
+/// exclusive lock can't overlap the shared lock, so it must be released first
+#[inline]
+pub(super) fn prepare_upgrade(file: &File, imp: LockImpl) -> Result {
+    unlock(file, imp)
+}
+
+/// A shared lock can overlap an exclusive lock taken with the same handle, and
+/// unlocking the range releases the exclusive lock first, so this is atomic
+#[inline]
+pub(super) fn downgrade(file: &File, imp: LockImpl) -> Result {
+    try_lock(file, LockState::Shared, imp)?;
+    unlock(file, imp)
+}
+
+pub(super) fn unlock(file: &File, _imp: LockImpl) -> Result {
     unsafe {
         let ret = unlock_file(file.as_raw_handle() as Handle, 0, 0, !0, !0);
         if ret == 0 {
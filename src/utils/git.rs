@@ -133,7 +133,7 @@ pub fn write_fetch_head(
         return Err(GitError::UnableToFindRemoteHead.into());
     }
 
-    let fetch_head_path = crate::PathBuf::from_path_buf(repo.path().join("FETCH_HEAD"))?;
+    let fetch_head_path = super::to_utf8_path_buf(repo.path().join("FETCH_HEAD"))?;
     std::fs::write(&fetch_head_path, fetch_head)
         .map_err(|io| Error::IoPath(io, fetch_head_path))?;
 
@@ -0,0 +1,320 @@
+//! Provides support for configuring HTTP clients the same as cargo, via its
+//! [`[http]`](https://doc.rust-lang.org/cargo/reference/config.html#http)
+//! configuration
+
+use crate::{Error, HttpError, Path, PathBuf};
+use std::time::Duration;
+
+/// A TLS version that can be specified via [`http.ssl-version`](https://doc.rust-lang.org/cargo/reference/config.html#httpssl-version)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TlsVersion {
+    /// TLS 1.0
+    Tls1_0,
+    /// TLS 1.1
+    Tls1_1,
+    /// TLS 1.2
+    Tls1_2,
+    /// TLS 1.3
+    Tls1_3,
+}
+
+impl TlsVersion {
+    /// Parses the version, `None` is returned for `default`, and an error for
+    /// unknown versions
+    fn parse(s: &str) -> Result<Option<Self>, ()> {
+        Ok(Some(match s {
+            "default" => return Ok(None),
+            "tlsv1" | "tlsv1.0" => Self::Tls1_0,
+            "tlsv1.1" => Self::Tls1_1,
+            "tlsv1.2" => Self::Tls1_2,
+            "tlsv1.3" => Self::Tls1_3,
+            _ => return Err(()),
+        }))
+    }
+
+    #[cfg(any(feature = "default", feature = "native-certs"))]
+    fn to_reqwest(self) -> reqwest::tls::Version {
+        use reqwest::tls::Version;
+
+        match self {
+            Self::Tls1_0 => Version::TLS_1_0,
+            Self::Tls1_1 => Version::TLS_1_1,
+            Self::Tls1_2 => Version::TLS_1_2,
+            Self::Tls1_3 => Version::TLS_1_3,
+        }
+    }
+}
+
+/// The subset of cargo's `[http]` configuration that can be applied to a
+/// [`reqwest`] client, see [`cargo_http_config`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HttpConfig {
+    /// [`http.proxy`](https://doc.rust-lang.org/cargo/reference/config.html#httpproxy),
+    /// in libcurl format, ie. the scheme is optional
+    pub proxy: Option<String>,
+    /// [`http.cainfo`](https://doc.rust-lang.org/cargo/reference/config.html#httpcainfo),
+    /// the path of a PEM bundle of additional root certificates
+    pub cainfo: Option<PathBuf>,
+    /// [`http.timeout`](https://doc.rust-lang.org/cargo/reference/config.html#httptimeout),
+    /// which cargo uses as the connection timeout
+    pub timeout: Option<Duration>,
+    /// The minimum TLS version from [`http.ssl-version`](https://doc.rust-lang.org/cargo/reference/config.html#httpssl-version)
+    pub min_tls_version: Option<TlsVersion>,
+    /// The maximum TLS version from [`http.ssl-version`](https://doc.rust-lang.org/cargo/reference/config.html#httpssl-version)
+    pub max_tls_version: Option<TlsVersion>,
+    /// [`http.multiplexing`](https://doc.rust-lang.org/cargo/reference/config.html#httpmultiplexing),
+    /// if false only HTTP/1 is used
+    pub multiplexing: Option<bool>,
+    /// [`http.user-agent`](https://doc.rust-lang.org/cargo/reference/config.html#httpuser-agent)
+    pub user_agent: Option<String>,
+    skipped: Vec<String>,
+}
+
+macro_rules! apply {
+    ($config:expr, $builder:expr) => {{
+        let config = $config;
+        let mut builder = $builder;
+
+        if let Some(proxy) = &config.proxy {
+            // libcurl defaults to http if the scheme is not specified
+            let proxy = if proxy.contains("://") {
+                reqwest::Proxy::all(proxy.as_str())
+            } else {
+                reqwest::Proxy::all(format!("http://{proxy}"))
+            };
+            builder = builder.proxy(proxy.map_err(HttpError::from)?);
+        }
+
+        if let Some(timeout) = config.timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+
+        if config.multiplexing == Some(false) {
+            builder = builder.http1_only();
+        }
+
+        if let Some(ua) = &config.user_agent {
+            builder = builder.user_agent(ua.as_str());
+        }
+
+        #[cfg(any(feature = "default", feature = "native-certs"))]
+        {
+            if let Some(cainfo) = &config.cainfo {
+                let pem =
+                    std::fs::read(cainfo).map_err(|err| Error::IoPath(err, cainfo.clone()))?;
+                let certs = reqwest::Certificate::from_pem_bundle(&pem).map_err(HttpError::from)?;
+                for cert in certs {
+                    builder = builder.add_root_certificate(cert);
+                }
+            }
+
+            if let Some(min) = config.min_tls_version {
+                builder = builder.min_tls_version(min.to_reqwest());
+            }
+
+            if let Some(max) = config.max_tls_version {
+                builder = builder.max_tls_version(max.to_reqwest());
+            }
+        }
+
+        Ok(builder)
+    }};
+}
+
+impl HttpConfig {
+    /// The keys in the configuration that were not applied, either because they
+    /// are not supported, or because their value was invalid
+    ///
+    /// Note that `cainfo` and `ssl-version` are only supported if TLS is
+    /// enabled via the `default` or `native-certs` features
+    #[inline]
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    /// Applies the configuration to an async client builder
+    pub fn apply(&self, builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder, Error> {
+        apply!(self, builder)
+    }
+
+    /// Applies the configuration to a blocking client builder
+    pub fn apply_blocking(
+        &self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder, Error> {
+        apply!(self, builder)
+    }
+
+    /// Sets the value for the key, returning false if the key is unsupported
+    /// or the value is invalid
+    fn set(&mut self, key: &str, value: &toml_span::value::Value<'_>) -> bool {
+        // These can only be applied if reqwest has TLS support
+        if cfg!(not(any(feature = "default", feature = "native-certs")))
+            && matches!(key, "cainfo" | "ssl-version")
+        {
+            return false;
+        }
+
+        match key {
+            "proxy" => value
+                .as_str()
+                .map(|p| self.proxy = Some(p.into()))
+                .is_some(),
+            "cainfo" => value
+                .as_str()
+                .map(|p| self.cainfo = Some(p.into()))
+                .is_some(),
+            "timeout" => value
+                .as_integer()
+                .and_then(|t| u64::try_from(t).ok())
+                .map(|t| self.timeout = Some(Duration::from_secs(t)))
+                .is_some(),
+            "ssl-version" => {
+                if let Some(version) = value.as_str() {
+                    let Ok(version) = TlsVersion::parse(version) else {
+                        return false;
+                    };
+                    self.min_tls_version = version;
+                    self.max_tls_version = version;
+                    true
+                } else if let Some(table) = value.as_table() {
+                    let parse = |key: &str| match table.get(key).and_then(|v| v.as_str()) {
+                        Some(v) => TlsVersion::parse(v),
+                        None => Ok(None),
+                    };
+
+                    let (Ok(min), Ok(max)) = (parse("min"), parse("max")) else {
+                        return false;
+                    };
+                    self.min_tls_version = min;
+                    self.max_tls_version = max;
+                    true
+                } else {
+                    false
+                }
+            }
+            "multiplexing" => value
+                .as_bool()
+                .map(|m| self.multiplexing = Some(m))
+                .is_some(),
+            "user-agent" => value
+                .as_str()
+                .map(|ua| self.user_agent = Some(ua.into()))
+                .is_some(),
+            _ => false,
+        }
+    }
+}
+
+/// Reads cargo's [`[http]`](https://doc.rust-lang.org/cargo/reference/config.html#http)
+/// configuration
+///
+/// The `CARGO_HTTP_<key>` environment variables take precedence over the
+/// values in the cargo config. Keys that can't be applied to a [`reqwest`]
+/// client, eg. `check-revoke` or `low-speed-limit`, are ignored, and can be
+/// retrieved via [`HttpConfig::skipped`]
+pub fn cargo_http_config(
+    config_root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+) -> Result<HttpConfig, Error> {
+    cargo_http_config_with(
+        &crate::index::location::config_env(),
+        config_root,
+        cargo_home,
+    )
+}
+
+fn cargo_http_config_with(
+    env: &[(String, String)],
+    config_root: Option<PathBuf>,
+    cargo_home: Option<&Path>,
+) -> Result<HttpConfig, Error> {
+    let hc = crate::index::location::read_cargo_config_with(
+        config_root,
+        cargo_home,
+        env,
+        &Default::default(),
+        |config| {
+            let http = config.pointer("/http")?.as_table()?;
+            let mut hc = HttpConfig::default();
+
+            for (key, value) in http {
+                if !hc.set(&key.name, value) {
+                    hc.skipped.push(key.name.to_string());
+                }
+            }
+
+            Some(hc)
+        },
+    )?;
+
+    Ok(hc.unwrap_or_default())
+}
+
+#[cfg(test)]
+mod test {
+    use super::TlsVersion;
+    use crate::PathBuf;
+    use std::time::Duration;
+
+    /// Validates the config is read from both cargo configs and the environment
+    #[test]
+    fn reads_http_config() {
+        let td = tempfile::tempdir().unwrap();
+        let root = crate::utils::to_utf8_path_buf(td.path().to_owned()).unwrap();
+        let home = root.join("cargo-home");
+        std::fs::create_dir_all(root.join(".cargo")).unwrap();
+        std::fs::create_dir(&home).unwrap();
+
+        std::fs::write(
+            root.join(".cargo/config.toml"),
+            r#"
+[http]
+proxy = "proxy.example.com:8080"
+cainfo = "certs/ca.pem"
+timeout = 30
+ssl-version.min = "tlsv1.2"
+check-revoke = false
+"#,
+        )
+        .unwrap();
+
+        let read = |env: &[(&str, &str)]| {
+            let env: Vec<_> = env
+                .iter()
+                .map(|(k, v)| ((*k).to_owned(), (*v).to_owned()))
+                .collect();
+            super::cargo_http_config_with(&env, Some(root.clone()), Some(&home)).unwrap()
+        };
+
+        let hc = read(&[]);
+        assert_eq!(hc.proxy.as_deref(), Some("proxy.example.com:8080"));
+        assert_eq!(hc.cainfo, Some(root.join("certs/ca.pem")));
+        assert_eq!(hc.timeout, Some(Duration::from_secs(30)));
+        assert_eq!(hc.min_tls_version, Some(TlsVersion::Tls1_2));
+        assert_eq!(hc.max_tls_version, None);
+        assert_eq!(hc.multiplexing, None);
+        assert_eq!(hc.skipped(), ["check-revoke"]);
+
+        let hc = read(&[
+            ("CARGO_HTTP_TIMEOUT", "5"),
+            ("CARGO_HTTP_CAINFO", "/etc/ca.pem"),
+            ("CARGO_HTTP_MULTIPLEXING", "false"),
+            ("CARGO_HTTP_SSL_VERSION", "tlsv1.4"),
+        ]);
+        assert_eq!(hc.timeout, Some(Duration::from_secs(5)));
+        assert_eq!(hc.cainfo, Some(PathBuf::from("/etc/ca.pem")));
+        assert_eq!(hc.multiplexing, Some(false));
+        assert_eq!(hc.min_tls_version, None);
+        assert_eq!(hc.skipped(), ["check-revoke", "ssl-version"]);
+
+        // The certificate doesn't exist
+        assert!(hc.apply(reqwest::Client::builder()).is_err());
+
+        let hc = super::HttpConfig { cainfo: None, ..hc };
+        let _client = hc.apply(reqwest::Client::builder()).unwrap();
+        let _client = hc
+            .apply_blocking(reqwest::blocking::Client::builder())
+            .unwrap();
+    }
+}
@@ -0,0 +1,109 @@
+//! A minimal implementation of the `StableSipHasher128` used by cargo 1.85+ to
+//! hash the directory names for registries and git repositories, see
+//! <https://github.com/rust-lang/rustc-stable-hash>
+//!
+//! Only the parts of the hasher needed to hash the inputs cargo uses are
+//! implemented, `isize` discriminants that fit in a single byte and strings
+
+/// SipHash-1-3 with a 128-bit output, the same as rustc's `SipHasher128`
+pub(crate) struct StableHasher {
+    v0: u64,
+    v1: u64,
+    v2: u64,
+    v3: u64,
+    tail: u64,
+    ntail: usize,
+    length: usize,
+}
+
+impl StableHasher {
+    #[inline]
+    pub(crate) fn new() -> Self {
+        Self {
+            v0: 0x736f6d6570736575,
+            v1: 0x646f72616e646f6d ^ 0xee,
+            v2: 0x6c7967656e657261,
+            v3: 0x7465646279746573,
+            tail: 0,
+            ntail: 0,
+            length: 0,
+        }
+    }
+
+    #[inline]
+    fn round(&mut self) {
+        self.v0 = self.v0.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(13);
+        self.v1 ^= self.v0;
+        self.v0 = self.v0.rotate_left(32);
+        self.v2 = self.v2.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(16);
+        self.v3 ^= self.v2;
+        self.v0 = self.v0.wrapping_add(self.v3);
+        self.v3 = self.v3.rotate_left(21);
+        self.v3 ^= self.v0;
+        self.v2 = self.v2.wrapping_add(self.v1);
+        self.v1 = self.v1.rotate_left(17);
+        self.v1 ^= self.v2;
+        self.v2 = self.v2.rotate_left(32);
+    }
+
+    #[inline]
+    fn compress(&mut self, m: u64) {
+        self.v3 ^= m;
+        self.round();
+        self.v0 ^= m;
+    }
+
+    /// Writes the bytes to the hasher
+    pub(crate) fn write(&mut self, bytes: &[u8]) {
+        self.length += bytes.len();
+
+        for &byte in bytes {
+            self.tail |= (byte as u64) << (8 * self.ntail);
+            self.ntail += 1;
+
+            if self.ntail == 8 {
+                self.compress(self.tail);
+                self.tail = 0;
+                self.ntail = 0;
+            }
+        }
+    }
+
+    /// Writes an enum discriminant, which `StableSipHasher128` hashes as a
+    /// single byte if it is less than `0xff`
+    #[inline]
+    pub(crate) fn write_discriminant(&mut self, discriminant: u8) {
+        debug_assert!(discriminant < 0xff);
+        self.write(&[discriminant]);
+    }
+
+    /// Writes a string the same as [`std::hash::Hash`] for `str`
+    #[inline]
+    pub(crate) fn write_str(&mut self, s: &str) {
+        self.write(s.as_bytes());
+        self.write(&[0xff]);
+    }
+
+    /// Finishes the hash, combining the 128-bit output into a `u64` the same
+    /// as `StableSipHasher128`'s [`std::hash::Hasher::finish`]
+    pub(crate) fn finish(mut self) -> u64 {
+        let b = ((self.length as u64 & 0xff) << 56) | self.tail;
+
+        self.compress(b);
+        self.v2 ^= 0xee;
+        for _ in 0..3 {
+            self.round();
+        }
+        let h0 = self.v0 ^ self.v1 ^ self.v2 ^ self.v3;
+
+        self.v1 ^= 0xdd;
+        for _ in 0..3 {
+            self.round();
+        }
+        let h1 = self.v0 ^ self.v1 ^ self.v2 ^ self.v3;
+
+        h0.wrapping_mul(3).wrapping_add(h1)
+    }
+}
@@ -80,3 +80,155 @@ fn serializes_current_cargo_cache() {
     assert_eq!(cargo_ce.revision, our_ce.revision);
     assert_eq!(cargo_ce.version_entries, our_ce.version_entries);
 }
+
+/// Validates revisions roundtrip through the exact format cargo uses
+#[test]
+fn revision_roundtrips() {
+    use tame_index::index::cache::Revision;
+
+    const ETAG: &str = "W/\"fa62f662c9aae1f21cab393950d4ae23\"";
+    const DATE: &str = "Thu, 22 Oct 2023 09:40:03 GMT";
+    const COMMIT: &str = "a7ffd7cabefac714a7ffd7cabefac714a7ffd7ca";
+
+    let etag = Revision::etag(ETAG).to_string();
+    assert_eq!(etag, format!("etag: {ETAG}"));
+    assert_eq!(Revision::parse(&etag), Revision::ETag(ETAG));
+
+    let lm = Revision::last_modified(DATE).to_string();
+    assert_eq!(lm, format!("last-modified: {DATE}"));
+    assert_eq!(Revision::parse(&lm), Revision::LastModified(DATE));
+
+    assert_eq!(Revision::parse(COMMIT), Revision::commit(COMMIT));
+    assert_eq!(Revision::commit(COMMIT).to_string(), COMMIT);
+
+    assert_eq!(Revision::parse("Unknown"), Revision::Unknown("Unknown"));
+}
+
+/// Validates the hashed layout has a fixed length path, and that reads fall
+/// back to the other layout
+#[test]
+fn hashed_layout() {
+    use tame_index::index::cache::CacheLayout;
+
+    let td = utils::tempdir();
+    let lock = &utils::unlocked();
+
+    let long_name = "a-crate-with-a-really-long-name-that-would-push-windows-over-max-path";
+    let krate = utils::fake_krate(long_name, 2);
+    let short = utils::fake_krate("short", 1);
+
+    let hashed = IndexCache::at_path(td.path().to_owned()).with_layout(CacheLayout::Hashed);
+    let long_path = hashed
+        .write_to_cache(&krate, "etag: \"hashed\"", lock)
+        .unwrap();
+    let short_path = hashed
+        .write_to_cache(&short, "etag: \"hashed\"", lock)
+        .unwrap();
+
+    assert_eq!(long_path.as_str().len(), short_path.as_str().len());
+    assert!(long_path.starts_with(td.path().join(".cache/hashed")));
+
+    let cargo = IndexCache::at_path(td.path().to_owned());
+    assert_eq!(
+        cargo
+            .cached_krate(long_name.try_into().unwrap(), None, lock)
+            .unwrap()
+            .unwrap(),
+        krate
+    );
+
+    let cargo_path = cargo
+        .write_to_cache(&krate, "etag: \"cargo\"", lock)
+        .unwrap();
+    assert!(cargo_path.ends_with(long_name));
+
+    // The configured layout is always preferred
+    let entry = hashed
+        .read_cache_file(long_name.try_into().unwrap(), lock)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        ValidCacheEntry::read(&entry).unwrap().revision,
+        "etag: \"hashed\""
+    );
+}
+
+/// Validates that an unusable entry in the configured layout, eg. due to a hash
+/// collision, an outdated revision, or corruption, falls back to the entry in
+/// the other layout
+#[test]
+fn hashed_layout_miss_falls_back() {
+    use tame_index::index::cache::CacheLayout;
+
+    let td = utils::tempdir();
+    let lock = &utils::unlocked();
+
+    let krate = utils::fake_krate("wanted", 2);
+    let other = utils::fake_krate("other", 1);
+
+    let cargo = IndexCache::at_path(td.path().to_owned());
+    cargo
+        .write_to_cache(&krate, "etag: \"cargo\"", lock)
+        .unwrap();
+
+    // Simulate a hash collision by placing the entry for another crate at the
+    // hashed path of the crate we want
+    let hashed = IndexCache::at_path(td.path().to_owned()).with_layout(CacheLayout::Hashed);
+    let other_path = hashed
+        .write_to_cache(&other, "etag: \"other\"", lock)
+        .unwrap();
+    let wanted_path = hashed.layout_path("wanted".try_into().unwrap(), CacheLayout::Hashed);
+    std::fs::create_dir_all(wanted_path.parent().unwrap()).unwrap();
+    std::fs::copy(&other_path, &wanted_path).unwrap();
+
+    assert_eq!(
+        hashed
+            .cached_krate("wanted".try_into().unwrap(), None, lock)
+            .unwrap()
+            .unwrap(),
+        krate
+    );
+    let entry = hashed
+        .read_cache_file("wanted".try_into().unwrap(), lock)
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        ValidCacheEntry::read(&entry).unwrap().revision,
+        "etag: \"cargo\""
+    );
+
+    // An outdated entry in the configured layout also falls back
+    hashed
+        .write_to_cache(&krate, "etag: \"stale\"", lock)
+        .unwrap();
+    assert_eq!(
+        hashed
+            .cached_krate("wanted".try_into().unwrap(), Some("etag: \"cargo\""), lock)
+            .unwrap()
+            .unwrap(),
+        krate
+    );
+    assert!(hashed
+        .cached_krate("wanted".try_into().unwrap(), Some("etag: \"nope\""), lock)
+        .unwrap()
+        .is_none());
+
+    // As does a corrupt or truncated entry
+    let contents = std::fs::read(&wanted_path).unwrap();
+    std::fs::write(&wanted_path, &contents[..contents.len() / 2]).unwrap();
+    assert_eq!(
+        hashed
+            .cached_krate("wanted".try_into().unwrap(), None, lock)
+            .unwrap()
+            .unwrap(),
+        krate
+    );
+    std::fs::write(&wanted_path, [0u8; 3]).unwrap();
+    assert_eq!(
+        hashed
+            .cached_krate("wanted".try_into().unwrap(), None, lock)
+            .unwrap()
+            .unwrap(),
+        krate
+    );
+}
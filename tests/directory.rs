@@ -0,0 +1,226 @@
+#![allow(missing_docs)]
+
+mod utils;
+
+use tame_index::{
+    index::{ComboIndexCache, DirectorySource},
+    IndexLocation, IndexUrl, Path,
+};
+
+const CHECKSUM: &str = "c530edf18f37068ac2d977409ed5cd50d53d73bc653c7647b48eb78976ac9ae2";
+
+fn vendor(root: &Path, dir: &str, manifest: &str, checksum: Option<&str>) {
+    let dir = root.join(dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("Cargo.toml"), manifest).unwrap();
+
+    let package = checksum.map_or("null".to_owned(), |c| format!("\"{c}\""));
+    std::fs::write(
+        dir.join(".cargo-checksum.json"),
+        format!(r#"{{"files":{{}},"package":{package}}}"#),
+    )
+    .unwrap();
+}
+
+/// Creates a directory source in the same layout as `cargo vendor`
+fn vendor_dir(root: &Path) {
+    vendor(
+        root,
+        "foo",
+        r#"
+[package]
+name = "foo"
+version = "1.0.0"
+rust-version = "1.70"
+links = "foo-sys"
+
+[dependencies.bar]
+version = "0.3"
+optional = true
+default-features = false
+features = ["std"]
+
+[dependencies.renamed]
+version = "2.0"
+package = "actual"
+
+[dev-dependencies]
+tester = "1.0"
+
+[target.'cfg(windows)'.build-dependencies.winstuff]
+version = "0.1"
+
+[features]
+default = ["bar"]
+serde = ["dep:bar", "bar?/serde"]
+"#,
+        Some(CHECKSUM),
+    );
+
+    vendor(
+        root,
+        "foo-0.9.0",
+        r#"
+[package]
+name = "foo"
+version = "0.9.0"
+"#,
+        None,
+    );
+
+    // A different crate that shares a prefix must not be picked up
+    vendor(
+        root,
+        "foo-bar",
+        r#"
+[package]
+name = "foo-bar"
+version = "0.1.0"
+"#,
+        None,
+    );
+}
+
+/// Validates index entries are synthesized from vendored crates
+#[test]
+fn reads_vendored_crates() {
+    let td = utils::tempdir();
+    vendor_dir(td.path());
+
+    let ds = DirectorySource::open(td.path().to_owned()).unwrap();
+    let lock = utils::unlocked();
+
+    let foo = ds
+        .cached_krate("foo".try_into().unwrap(), &lock)
+        .unwrap()
+        .expect("failed to find foo");
+
+    assert_eq!(foo.versions.len(), 2);
+    assert_eq!(foo.versions[0].version, "0.9.0");
+
+    let v1 = &foo.versions[1];
+    assert_eq!(v1.version, "1.0.0");
+    assert_eq!(v1.checksum.to_string(), CHECKSUM);
+    assert_eq!(v1.rust_version(), Some("1.70"));
+    assert_eq!(v1.links(), Some("foo-sys"));
+    assert_eq!(v1.features().count(), 2);
+
+    let deps = v1.dependencies();
+    assert_eq!(deps.len(), 4);
+
+    let bar = deps.iter().find(|d| d.name == "bar").unwrap();
+    assert_eq!(bar.req, "0.3");
+    assert!(bar.is_optional());
+    assert!(!bar.has_default_features());
+    assert_eq!(bar.features(), ["std"]);
+
+    let renamed = deps.iter().find(|d| d.name == "renamed").unwrap();
+    assert_eq!(renamed.crate_name(), "actual");
+
+    let tester = deps.iter().find(|d| d.name == "tester").unwrap();
+    assert_eq!(tester.kind(), tame_index::krate::DependencyKind::Dev);
+
+    let winstuff = deps.iter().find(|d| d.name == "winstuff").unwrap();
+    assert_eq!(winstuff.kind(), tame_index::krate::DependencyKind::Build);
+    assert_eq!(winstuff.target(), Some("cfg(windows)"));
+
+    let foo_bar = ds
+        .cached_krate("foo-bar".try_into().unwrap(), &lock)
+        .unwrap()
+        .expect("failed to find foo-bar");
+    assert_eq!(foo_bar.versions.len(), 1);
+
+    assert!(ds
+        .cached_krate("missing".try_into().unwrap(), &lock)
+        .unwrap()
+        .is_none());
+}
+
+/// Validates the `directory` source replacement written by `cargo vendor` is
+/// detected, with the path being relative to the config
+#[test]
+fn detects_vendor_replacement() {
+    let td = utils::tempdir();
+    let project = td.path().join("project");
+    let home = td.path().join("cargo-home");
+
+    std::fs::create_dir_all(project.join(".cargo")).unwrap();
+    std::fs::create_dir_all(&home).unwrap();
+    vendor_dir(&project.join("vendor"));
+
+    std::fs::write(
+        project.join(".cargo/config.toml"),
+        r#"[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "vendor"
+"#,
+    )
+    .unwrap();
+
+    let url = IndexUrl::crates_io(Some(project.clone()), Some(&home), None).unwrap();
+    let IndexUrl::Directory(path) = &url else {
+        panic!("expected a directory source, got {url:?}");
+    };
+    assert_eq!(path.as_str(), project.join("vendor").as_str());
+
+    let cache = ComboIndexCache::new(IndexLocation::new(url)).unwrap();
+    let foo = cache
+        .cached_krate("foo".try_into().unwrap(), &utils::unlocked())
+        .unwrap()
+        .expect("failed to find foo");
+    assert_eq!(foo.versions.len(), 2);
+}
+
+/// Validates the remote capable index for crates.io respects source replacements
+#[cfg(all(feature = "__git", feature = "sparse"))]
+#[test]
+fn combo_uses_vendor_replacement() {
+    use tame_index::index::{ClientKind, ComboIndex};
+
+    let td = utils::tempdir();
+    let project = td.path().join("project");
+    let home = td.path().join("cargo-home");
+
+    std::fs::create_dir_all(project.join(".cargo")).unwrap();
+    std::fs::create_dir_all(&home).unwrap();
+    vendor_dir(&project.join("vendor"));
+
+    std::fs::write(
+        project.join(".cargo/config.toml"),
+        r#"[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "vendor"
+"#,
+    )
+    .unwrap();
+
+    let lock = utils::unlocked();
+    let client = || ClientKind::Blocking(reqwest::blocking::Client::new());
+
+    let index = ComboIndex::crates_io(
+        Some(project.clone()),
+        Some(home.clone()),
+        Some("1.70.0"),
+        client(),
+        &lock,
+    )
+    .unwrap();
+    assert!(matches!(index, ComboIndex::Directory(_)));
+
+    let foo = index
+        .krate("foo".try_into().unwrap(), true, &lock)
+        .unwrap()
+        .expect("failed to find foo");
+    assert_eq!(foo.versions.len(), 2);
+
+    // Without the replacement, the version of cargo determines the protocol,
+    // which doesn't require any network I/O for sparse indices
+    std::fs::remove_file(project.join(".cargo/config.toml")).unwrap();
+    let index =
+        ComboIndex::crates_io(Some(project), Some(home), Some("1.70.0"), client(), &lock).unwrap();
+    assert!(matches!(index, ComboIndex::Sparse(_)));
+}
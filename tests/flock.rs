@@ -20,6 +20,10 @@ impl LockKind {
 }
 
 fn spawn(kind: LockKind, path: &tame_index::Path) -> std::process::Child {
+    spawn_with(kind, path, false)
+}
+
+fn spawn_with(kind: LockKind, path: &tame_index::Path, ofd: bool) -> std::process::Child {
     let mut cmd = std::process::Command::new("cargo");
     cmd.env("RUST_BACKTRACE", "1")
         .args([
@@ -33,6 +37,10 @@ fn spawn(kind: LockKind, path: &tame_index::Path) -> std::process::Child {
         .arg(kind.as_str())
         .arg(path);
 
+    if ofd {
+        cmd.arg("ofd");
+    }
+
     let mut child = cmd.spawn().expect("failed to spawn flock");
 
     // Wait for the child to actually take the lock
@@ -175,3 +183,783 @@ fn wait_lock_times_out() {
 
     kill(child);
 }
+
+/// Validates we can wait for a lock to be released without blocking the runtime
+#[cfg(feature = "tokio")]
+#[test]
+fn waits_lock_async() {
+    let td = utils::tempdir();
+    let ctl = td.path().join("waits-lock-async");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+
+    rt.block_on(async {
+        let killer = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(100));
+            kill(child);
+        });
+
+        LockOptions::new(&ctl)
+            .lock_async(|_p| Some(Duration::from_millis(500)))
+            .await
+            .expect("failed to acquire shared lock");
+        killer.join().unwrap();
+    });
+}
+
+/// Ensures that a cancelled async lock does not leak the lock once the
+/// blocking wait completes
+#[cfg(feature = "tokio")]
+#[test]
+fn cancelled_lock_async_is_released() {
+    let td = utils::tempdir();
+    let ctl = td.path().join("cancelled-lock-async");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+
+    {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+
+        rt.block_on(async {
+            let lo = LockOptions::new(&ctl).exclusive(false);
+            let res = tokio::time::timeout(
+                Duration::from_millis(100),
+                lo.lock_async(|_p| Some(Duration::from_millis(500))),
+            )
+            .await;
+            assert!(res.is_err(), "the lock should still be held");
+        });
+
+        // Dropping the runtime waits for the blocking wait to finish, which
+        // only takes until the end of the current slice now that the future
+        // has been cancelled, even though the lock is still held
+        let start = std::time::Instant::now();
+        drop(rt);
+        assert!(start.elapsed() < Duration::from_millis(400));
+    }
+
+    kill(child);
+
+    LockOptions::new(&ctl)
+        .exclusive(false)
+        .try_lock()
+        .expect("the cancelled lock was leaked");
+}
+
+/// Ensures the deadline based locking times out, and notifies the caller
+/// exactly once when the lock is contended
+#[test]
+fn try_lock_for_times_out() {
+    let td = utils::tempdir();
+    let ctl = td.path().join("try-lock-for-times-out");
+
+    let lo = LockOptions::new(&ctl).exclusive(false);
+    {
+        let _lf = lo
+            .try_lock_for(Duration::from_millis(100))
+            .expect("failed to acquire uncontested lock");
+    }
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+
+    let contended = std::sync::atomic::AtomicUsize::new(0);
+    let start = std::time::Instant::now();
+    let err = lo
+        .try_lock_until_with(start + Duration::from_millis(100), |_p| {
+            println!("Blocking waiting for file lock");
+            contended.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        })
+        .expect_err("we should not be able to take the lock");
+
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(
+        le.source,
+        tame_index::utils::flock::LockError::TimedOut
+    ));
+    assert_eq!(contended.load(std::sync::atomic::Ordering::Relaxed), 1);
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    let err = lo
+        .try_lock_for(Duration::from_millis(50))
+        .expect_err("we should not be able to take the lock");
+    assert!(matches!(
+        err,
+        tame_index::Error::Lock(tame_index::utils::flock::FileLockError {
+            source: tame_index::utils::flock::LockError::TimedOut,
+            ..
+        })
+    ));
+
+    kill(child);
+
+    lo.try_lock_for(Duration::from_millis(100))
+        .expect("failed to acquire lock after it was released");
+}
+
+/// Validates shared locks can be upgraded to exclusive locks and back
+#[test]
+fn upgrades_and_downgrades() {
+    use tame_index::utils::flock::{FileLockError, LockError};
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("upgrades-and-downgrades");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let shared = LockOptions::new(&ctl).shared();
+    let exclusive = LockOptions::new(&ctl).exclusive(false);
+
+    {
+        let child = spawn(LockKind::Shared, &ctl);
+
+        let lf = shared.try_lock().expect("failed to acquire shared lock");
+        let err = lf
+            .try_upgrade()
+            .expect_err("we should not be able to upgrade while another shared lock is held");
+        assert!(matches!(
+            err,
+            tame_index::Error::Lock(FileLockError {
+                source: LockError::Contested,
+                ..
+            })
+        ));
+
+        kill(child);
+    }
+
+    let lf = shared.try_lock().expect("failed to acquire shared lock");
+    let lf = lf
+        .upgrade(|_p| Some(Duration::from_millis(100)))
+        .expect("failed to upgrade lock");
+
+    assert!(shared.try_lock().is_err());
+
+    let lf = lf.downgrade().expect("failed to downgrade lock");
+    let other = shared
+        .try_lock()
+        .expect("failed to acquire shared lock after downgrade");
+    assert!(exclusive.try_lock().is_err());
+
+    drop(lf);
+    drop(other);
+    exclusive
+        .try_lock()
+        .expect("failed to acquire exclusive lock after unlocking");
+}
+
+/// Validates the state of a lock can be inspected
+#[test]
+fn inspects_lock() {
+    use tame_index::utils::flock::{FileLock, LockStateKind};
+
+    let unlocked = FileLock::unlocked();
+    assert!(!unlocked.is_locked());
+    assert_eq!(unlocked.state(), None);
+    assert_eq!(unlocked.path().as_str(), "");
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("inspects-lock");
+
+    let lf = LockOptions::new(&ctl)
+        .exclusive(false)
+        .try_lock()
+        .expect("failed to acquire lock");
+    assert!(lf.is_locked());
+    assert_eq!(lf.state(), Some(LockStateKind::Exclusive));
+    assert_eq!(lf.path(), ctl.as_path());
+
+    let lf = lf.downgrade().expect("failed to downgrade lock");
+    assert_eq!(lf.state(), Some(LockStateKind::Shared));
+}
+
+/// Validates per-crate locks are placed next to the crate's cache entry, and
+/// can optionally be cleaned up
+#[test]
+fn crate_cache_lock() {
+    let td = utils::tempdir();
+    let cache = tame_index::index::IndexCache::at_path(td.path().to_owned());
+    let name = "crate-cache-lock".try_into().unwrap();
+
+    let lock_path = format!("{}.lock", cache.cache_path(name));
+
+    {
+        let lf = LockOptions::crate_cache_lock(&cache, name)
+            .try_lock()
+            .expect("failed to acquire crate lock");
+        assert_eq!(lf.path().as_str(), lock_path);
+        assert!(LockOptions::crate_cache_lock(&cache, name)
+            .try_lock()
+            .is_err());
+    }
+
+    assert!(std::path::Path::new(&lock_path).exists());
+
+    {
+        let _lf = LockOptions::crate_cache_lock(&cache, name)
+            .unlink_on_drop(true)
+            .try_lock()
+            .expect("failed to acquire crate lock");
+    }
+
+    assert!(!std::path::Path::new(&lock_path).exists());
+}
+
+/// Validates locks can be explicitly released, allowing other processes to
+/// acquire them immediately
+#[test]
+fn unlocks() {
+    let td = utils::tempdir();
+    let ctl = td.path().join("unlocks");
+
+    let lo = LockOptions::new(&ctl).exclusive(false);
+
+    let lf = lo.try_lock().expect("failed to acquire lock");
+    lf.unlock().expect("failed to unlock");
+
+    // The child panics if it can't take the lock immediately
+    let child = spawn(LockKind::Exclusive, &ctl);
+    kill(child);
+
+    let mut lf = lo.try_lock().expect("failed to acquire lock");
+    lf.release().expect("failed to release lock");
+    assert!(!lf.is_locked());
+    assert_eq!(lf.state(), None);
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+    kill(child);
+
+    // Releasing again is a no-op
+    lf.release().expect("failed to release unlocked lock");
+}
+
+/// Validates open file description locks, which are used on NFS, work
+#[cfg(target_os = "linux")]
+#[test]
+fn ofd_locks() {
+    use tame_index::utils::flock::LockError;
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("ofd-locks");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let child = spawn_with(LockKind::Shared, &ctl, true);
+
+    let shared = LockOptions::new(&ctl).shared().ofd_locks(true);
+    let exclusive = LockOptions::new(&ctl).exclusive(false).ofd_locks(true);
+
+    let lf = shared.try_lock().expect("failed to acquire shared lock");
+    let err = exclusive
+        .try_lock_for(Duration::from_millis(100))
+        .expect_err("we should not be able to take an exclusive lock");
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(le.source, LockError::TimedOut));
+
+    kill(child);
+    lf.unlock().expect("failed to unlock");
+
+    let lf = exclusive
+        .try_lock()
+        .expect("failed to acquire exclusive lock");
+    assert!(shared.try_lock().is_err());
+    let lf = lf.downgrade().expect("failed to downgrade lock");
+    let _other = shared
+        .try_lock()
+        .expect("failed to acquire shared lock after downgrade");
+    drop(lf);
+}
+
+/// Validates shared open file description locks can be upgraded, as unlike
+/// `flock`, exclusive locks require the file to be writable
+#[cfg(target_os = "linux")]
+#[test]
+fn ofd_upgrades() {
+    use tame_index::utils::flock::{FileLockError, LockError};
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("ofd-upgrades");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let shared = LockOptions::new(&ctl).shared().ofd_locks(true);
+
+    {
+        let child = spawn_with(LockKind::Shared, &ctl, true);
+
+        let lf = shared.try_lock().expect("failed to acquire shared lock");
+        let err = lf
+            .try_upgrade()
+            .expect_err("we should not be able to upgrade while another shared lock is held");
+        assert!(matches!(
+            err,
+            tame_index::Error::Lock(FileLockError {
+                source: LockError::Contested,
+                ..
+            })
+        ));
+
+        kill(child);
+    }
+
+    let lf = shared.try_lock().expect("failed to acquire shared lock");
+    let lf = lf.try_upgrade().expect("failed to upgrade lock");
+    assert!(shared.try_lock().is_err());
+
+    let lf = lf.downgrade().expect("failed to downgrade lock");
+    let _other = shared
+        .try_lock()
+        .expect("failed to acquire shared lock after downgrade");
+    drop(lf);
+}
+
+/// Validates the contention callback is called repeatedly while waiting, and
+/// can give up at any point
+#[test]
+fn lock_with_polls() {
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use tame_index::utils::flock::{LockError, LockWaitDecision};
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("lock-with-polls");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+    let lo = LockOptions::new(&ctl).exclusive(false);
+
+    let attempts = AtomicU32::new(0);
+    let err = lo
+        .lock_with(|lc| {
+            assert_eq!(lc.path, ctl.as_path());
+            assert_eq!(attempts.fetch_add(1, Ordering::Relaxed) + 1, lc.attempt);
+
+            if lc.elapsed >= Duration::from_millis(150) {
+                LockWaitDecision::GiveUp
+            } else {
+                LockWaitDecision::KeepWaiting(Some(Duration::from_millis(50)))
+            }
+        })
+        .expect_err("we should not be able to take the lock");
+
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(le.source, LockError::TimedOut));
+    assert!(attempts.load(Ordering::Relaxed) >= 4);
+
+    let err = lo
+        .lock_with(|_lc| LockWaitDecision::GiveUp)
+        .expect_err("we should not be able to take the lock");
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(le.source, LockError::Contested));
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            lo.lock_with(|_lc| LockWaitDecision::KeepWaiting(Some(Duration::from_millis(20))))
+                .expect("failed to acquire lock");
+        });
+        s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(100));
+            kill(child);
+        });
+    });
+}
+
+/// Validates the holder of an exclusive lock records itself in the lock file,
+/// and that it is cleared once the lock is released
+#[cfg(unix)]
+#[test]
+fn records_lock_holder() {
+    use tame_index::utils::flock::LockWaitDecision;
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("records-lock-holder");
+
+    let lo = LockOptions::new(&ctl).exclusive(false);
+    assert!(lo.current_holder().unwrap().is_none());
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+    let child_pid = child.id();
+
+    let holder = lo
+        .current_holder()
+        .unwrap()
+        .expect("child should have recorded itself");
+    assert_eq!(holder.pid, child_pid);
+    assert_eq!(holder.name, "flock");
+
+    lo.lock_with(|lc| {
+        assert_eq!(lc.holder.as_ref(), Some(&holder));
+        LockWaitDecision::GiveUp
+    })
+    .expect_err("we should not be able to take the lock");
+
+    // The child is killed so is unable to clear the holder, which is why the
+    // holder information is only advisory
+    kill(child);
+    assert_eq!(lo.current_holder().unwrap().map(|h| h.pid), Some(child_pid));
+
+    let lf = lo.try_lock().expect("failed to acquire lock");
+    assert_eq!(
+        lo.current_holder().unwrap().map(|h| h.pid),
+        Some(std::process::id())
+    );
+
+    let lf = lf.downgrade().expect("failed to downgrade");
+    assert!(lo.current_holder().unwrap().is_none());
+
+    let lf = lf.upgrade(|_| None).expect("failed to upgrade");
+    assert_eq!(
+        lo.current_holder().unwrap().map(|h| h.pid),
+        Some(std::process::id())
+    );
+
+    lf.unlock().expect("failed to unlock");
+    assert!(lo.current_holder().unwrap().is_none());
+}
+
+/// Validates a wait for a contended lock can be interrupted
+#[test]
+fn lock_interruptible() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tame_index::utils::flock::LockError;
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("lock-interruptible");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let child = spawn(LockKind::Exclusive, &ctl);
+    let lo = LockOptions::new(&ctl).exclusive(false);
+
+    let should_interrupt = AtomicBool::new(false);
+    let start = std::time::Instant::now();
+
+    let err = std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(300));
+            should_interrupt.store(true, Ordering::Relaxed);
+        });
+
+        lo.lock_interruptible(|_p| None, &should_interrupt)
+            .expect_err("we should not be able to take the lock")
+    });
+
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(le.source, LockError::Interrupted));
+    assert!(start.elapsed() < Duration::from_secs(5));
+
+    // The timeout is still respected if we are never interrupted
+    should_interrupt.store(false, Ordering::Relaxed);
+    let err = lo
+        .lock_interruptible(|_p| Some(Duration::from_millis(300)), &should_interrupt)
+        .expect_err("we should not be able to take the lock");
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(le.source, LockError::TimedOut));
+
+    kill(child);
+
+    lo.lock_interruptible(|_p| None, &should_interrupt)
+        .expect("failed to acquire lock");
+}
+
+/// Validates that waiting in slices doesn't leave lock requests from the
+/// slices that timed out behind, which, on Windows, would otherwise still be
+/// pending and could be granted later without the caller knowing
+#[test]
+fn sliced_waits_are_cancelled() {
+    use std::sync::atomic::AtomicBool;
+    use tame_index::utils::flock::LockError;
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("sliced-waits");
+
+    let _ = std::fs::OpenOptions::new()
+        .create(true)
+        .truncate(true)
+        .write(true)
+        .open(&ctl)
+        .expect("failed to create lock file");
+
+    let lo = LockOptions::new(&ctl).exclusive(false);
+    let never = AtomicBool::new(false);
+
+    // Time out after several slices
+    let child = spawn(LockKind::Exclusive, &ctl);
+    let err = lo
+        .lock_interruptible(|_p| Some(Duration::from_millis(700)), &never)
+        .expect_err("we should not be able to take the lock");
+    let tame_index::Error::Lock(le) = err else {
+        panic!("unexpected error type {err:#?}");
+    };
+    assert!(matches!(le.source, LockError::TimedOut));
+    kill(child);
+
+    lo.try_lock()
+        .expect("failed to acquire lock after timing out")
+        .unlock()
+        .expect("failed to unlock");
+
+    // Acquire the lock after several slices have elapsed, once it is released
+    // the lock must be free, rather than held by an earlier request
+    let child = spawn(LockKind::Exclusive, &ctl);
+    let lf = std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(700));
+            kill(child);
+        });
+
+        lo.lock_interruptible(|_p| None, &never)
+            .expect("failed to acquire lock")
+    });
+
+    lf.unlock().expect("failed to unlock");
+    lo.try_lock()
+        .expect("failed to acquire lock after unlocking")
+        .unlock()
+        .expect("failed to unlock");
+}
+
+/// Validates the cargo global cache lock uses the granular layout if cargo
+/// has created it in the root
+#[test]
+fn cargo_lock_layout() {
+    let td = utils::tempdir();
+    let root = td.path().to_owned();
+
+    let lock = |lo: LockOptions<'_>| {
+        lo.try_lock()
+            .expect("failed to acquire lock")
+            .path()
+            .file_name()
+            .unwrap()
+            .to_owned()
+    };
+
+    assert_eq!(
+        lock(LockOptions::cargo_download_lock(Some(root.clone())).unwrap()),
+        ".package-cache"
+    );
+    assert_eq!(
+        lock(LockOptions::cargo_global_cache_lock(Some(root.clone())).unwrap()),
+        ".package-cache"
+    );
+
+    std::fs::write(root.join(".package-cache-mutate"), "").unwrap();
+
+    assert_eq!(
+        lock(LockOptions::cargo_download_lock(Some(root.clone())).unwrap()),
+        ".package-cache"
+    );
+    assert_eq!(
+        lock(LockOptions::cargo_global_cache_lock(Some(root)).unwrap()),
+        ".package-cache-mutate"
+    );
+}
+
+/// Validates the cargo download lock mutually excludes a real cargo
+/// operation that takes its package cache lock
+#[test]
+fn cargo_download_lock_excludes_cargo() {
+    use std::io::BufRead;
+
+    let cargo_available = std::process::Command::new("cargo")
+        .arg("--version")
+        .output()
+        .map_or(false, |out| out.status.success());
+    if !cargo_available {
+        return;
+    }
+
+    let td = utils::tempdir();
+    let cargo_home = td.path().join("cargo-home");
+    let project = td.path().join("project");
+
+    std::fs::create_dir_all(project.join("src")).unwrap();
+    std::fs::write(
+        project.join("Cargo.toml"),
+        "[package]\nname = \"lock-test\"\nversion = \"0.1.0\"\nedition = \"2021\"\n",
+    )
+    .unwrap();
+    std::fs::write(project.join("src/lib.rs"), "").unwrap();
+
+    let lock = LockOptions::cargo_download_lock(Some(cargo_home.clone()))
+        .unwrap()
+        .try_lock()
+        .expect("failed to acquire download lock");
+
+    let mut child = std::process::Command::new("cargo")
+        .env("CARGO_HOME", &cargo_home)
+        .args(["fetch", "--offline", "--manifest-path"])
+        .arg(project.join("Cargo.toml"))
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .expect("failed to spawn cargo");
+
+    // Cargo informs the user when it is blocked waiting on the lock
+    let mut stderr = std::io::BufReader::new(child.stderr.take().unwrap())
+        .lines()
+        .map_while(Result::ok);
+    let blocked = stderr
+        .by_ref()
+        .any(|line| line.contains("waiting for file lock"));
+    assert!(blocked, "cargo did not wait on the download lock");
+    assert!(child.try_wait().unwrap().is_none());
+
+    lock.unlock().expect("failed to unlock");
+
+    // Keep draining stderr so cargo doesn't fail writing to a closed pipe
+    stderr.for_each(drop);
+    assert!(child.wait().expect("failed to wait for cargo").success());
+}
+
+/// Validates a shared lock guard can be used from multiple threads, and is
+/// only released once the last clone is dropped
+#[test]
+fn shared_file_lock() {
+    use tame_index::utils::flock::{FileLock, SharedFileLock};
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<FileLock>();
+    assert_send_sync::<SharedFileLock>();
+
+    let td = utils::tempdir();
+    let ctl = td.path().join("shared-file-lock");
+    let lo = LockOptions::new(&ctl).exclusive(false);
+
+    let lock = lo.try_lock().expect("failed to acquire lock").into_shared();
+
+    std::thread::scope(|s| {
+        for _ in 0..4 {
+            let lock = lock.clone();
+            s.spawn(move || {
+                let lock: &FileLock = &lock;
+                assert!(lock.is_locked());
+            });
+        }
+    });
+
+    let clone = lock.clone();
+    let lock = lock
+        .try_unwrap()
+        .expect_err("a clone is still alive, so this should fail");
+    drop(clone);
+
+    lo.try_lock()
+        .expect_err("the lock should be held until the last clone is dropped");
+
+    lock.try_unwrap()
+        .expect("this is the last clone")
+        .unlock()
+        .expect("failed to unlock");
+
+    lo.try_lock().expect("failed to acquire lock");
+}
+
+/// Validates stale lock files are removed, but locked, recent, and unrelated
+/// files, as well as cargo's permanent lock files, are left alone
+#[test]
+fn cleans_stale_locks() {
+    use tame_index::utils::flock::SkipReason;
+
+    let td = utils::tempdir();
+    let root = td.path();
+
+    let package_cache = root.join(".package-cache");
+    let package_cache_mutate = root.join(".package-cache-mutate");
+    let crate_lock = root.join("cache/se/rd/serde.lock");
+    let held = root.join("cache/3/h/held.lock");
+    let cargo_lock = root.join("Cargo.lock");
+
+    for path in [&package_cache, &package_cache_mutate, &crate_lock, &held] {
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, "").unwrap();
+    }
+    std::fs::write(&cargo_lock, "version = 3\n").unwrap();
+
+    let child = spawn(LockKind::Exclusive, &held);
+
+    let report = LockOptions::clean_stale(root, Duration::from_secs(60 * 60)).unwrap();
+    assert!(report.removed.is_empty());
+    assert_eq!(report.skipped.len(), 2);
+    assert!(report
+        .skipped
+        .iter()
+        .all(|(_, reason)| matches!(reason, SkipReason::TooRecent)));
+
+    let report = LockOptions::clean_stale(root, Duration::ZERO).unwrap();
+    assert_eq!(report.removed, std::slice::from_ref(&crate_lock));
+    assert_eq!(report.skipped.len(), 1);
+    assert_eq!(report.skipped[0].0, held);
+    assert!(matches!(report.skipped[0].1, SkipReason::Locked));
+
+    assert!(package_cache.exists());
+    assert!(package_cache_mutate.exists());
+    assert!(!crate_lock.exists());
+    assert!(held.exists());
+    assert!(cargo_lock.exists());
+
+    kill(child);
+
+    let report = LockOptions::clean_stale(root, Duration::ZERO).unwrap();
+    assert_eq!(report.removed, std::slice::from_ref(&held));
+    assert!(report.skipped.is_empty());
+    assert!(!held.exists());
+}
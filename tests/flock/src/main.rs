@@ -15,6 +15,9 @@ fn main() {
         _ => panic!("unknown lock kind '{kind}'"),
     };
 
+    // Forces the use of fcntl locks so they can be tested off of NFS
+    let lo = lo.ofd_locks(args.next().as_deref() == Some("ofd"));
+
     let _fl = lo.try_lock().expect("failed to acquire lock");
     {
         use std::io::Write;
@@ -308,6 +308,49 @@ fn opens_existing() {
     );
 }
 
+/// Validates that opening without cloning, ie. in offline mode, uses an
+/// existing repo, but never clones a missing one
+#[test]
+fn opens_without_clone() {
+    let mut remote = FakeRemote::new();
+    let lock = &utils::unlocked();
+
+    let krate = utils::fake_krate("opens-without-clone", 2);
+    let expected_head = remote.commit(&krate);
+
+    let td = utils::tempdir();
+    let git_index = || {
+        GitIndex::new(IndexLocation {
+            url: IndexUrl::NonCratesIo(remote.td.path().as_str().into()),
+            root: IndexPath::Exact(td.path().join("sub/dir")),
+        })
+        .unwrap()
+    };
+
+    let err = RemoteGitIndex::open(git_index(), lock)
+        .err()
+        .expect("should not clone the index");
+    assert!(matches!(
+        err,
+        tame_index::Error::Git(tame_index::error::GitError::NotCloned(_))
+    ));
+    assert!(!td.path().join("sub/dir").exists());
+
+    drop(remote_index(&td, &remote.td));
+
+    let rgi = RemoteGitIndex::open(git_index(), lock).unwrap();
+    assert_eq!(
+        rgi.local().head_commit().unwrap(),
+        expected_head.to_hex().to_string()
+    );
+    assert_eq!(
+        rgi.krate("opens-without-clone".try_into().unwrap(), false, lock)
+            .unwrap()
+            .expect("expected krate"),
+        krate,
+    );
+}
+
 /// Validates that cache entries can be created and used
 #[test]
 fn updates_cache() {
@@ -456,6 +499,157 @@ fn fetch_invalidates_cache() {
     );
 }
 
+/// Validates updating a combo index fetches git indices and reports if the
+/// index changed
+#[cfg(feature = "sparse")]
+#[test]
+fn combo_update() {
+    use tame_index::index::{ComboIndex, UpdateOutcome};
+
+    let mut remote = FakeRemote::new();
+    let lock = &utils::unlocked();
+
+    remote.commit(&utils::fake_krate("combo-update", 1));
+    let (rgi, _td) = remote.local();
+    let mut combo = ComboIndex::from(rgi);
+
+    assert_eq!(combo.update(lock).unwrap(), UpdateOutcome::UpToDate);
+
+    let krate = utils::fake_krate("combo-update", 2);
+    remote.commit(&krate);
+
+    assert_eq!(combo.update(lock).unwrap(), UpdateOutcome::Updated);
+    assert_eq!(
+        combo
+            .krate("combo-update".try_into().unwrap(), true, lock)
+            .unwrap()
+            .expect("expected krate"),
+        krate
+    );
+    assert_eq!(combo.update(lock).unwrap(), UpdateOutcome::UpToDate);
+
+    let krates = combo.krates(
+        ["combo-update", "missing", "Invalid Name"]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+        true,
+        lock,
+    );
+    assert_eq!(krates.len(), 3);
+    assert_eq!(
+        krates["combo-update"].as_ref().unwrap().as_ref(),
+        Some(&krate)
+    );
+    assert!(krates["missing"].as_ref().unwrap().is_none());
+    assert!(krates["Invalid Name"].is_err());
+}
+
+/// Validates the async combo index works with both git and sparse indices
+#[cfg(feature = "sparse")]
+#[test]
+fn async_combo() {
+    use std::io::{Read, Write};
+    use tame_index::index::{AsyncComboIndex, UpdateOutcome};
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    let lock = &utils::unlocked();
+
+    let mut remote = FakeRemote::new();
+    remote.commit(&utils::fake_krate("async-git", 1));
+    let (rgi, _td) = remote.local();
+    let mut git = AsyncComboIndex::from(rgi);
+
+    let krate = utils::fake_krate("async-git", 2);
+    remote.commit(&krate);
+
+    rt.block_on(async {
+        assert_eq!(
+            git.update_async(lock).await.unwrap(),
+            UpdateOutcome::Updated
+        );
+        assert_eq!(
+            git.krate_async("async-git".try_into().unwrap(), true, lock)
+                .await
+                .unwrap()
+                .expect("expected krate"),
+            krate
+        );
+        assert_eq!(
+            git.cached_krate_async("async-git".try_into().unwrap(), lock)
+                .await
+                .unwrap()
+                .expect("expected cached krate"),
+            krate
+        );
+    });
+
+    // A minimal sparse registry that serves a single crate
+    let sparse_krate = utils::fake_krate("async-sparse", 3);
+    let mut body = Vec::new();
+    sparse_krate.write_json_lines(&mut body).unwrap();
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+
+        let mut request = Vec::new();
+        let mut buf = [0u8; 1024];
+        while !request.ends_with(b"\r\n\r\n") {
+            let read = stream.read(&mut buf).unwrap();
+            assert!(
+                read > 0,
+                "connection closed before the request was complete"
+            );
+            request.extend_from_slice(&buf[..read]);
+        }
+
+        let request = String::from_utf8(request).unwrap();
+        assert!(request.starts_with("GET /as/yn/async-sparse "));
+
+        write!(
+            stream,
+            "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+            body.len()
+        )
+        .unwrap();
+        stream.write_all(&body).unwrap();
+    });
+
+    let td = utils::tempdir();
+    let mut sparse = AsyncComboIndex::new(
+        IndexLocation {
+            url: IndexUrl::NonCratesIo(format!("sparse+http://{addr}/").into()),
+            root: IndexPath::Exact(td.path().to_owned()),
+        },
+        reqwest::Client::new(),
+        lock,
+    )
+    .unwrap();
+    assert!(matches!(sparse, AsyncComboIndex::Sparse(_)));
+
+    rt.block_on(async {
+        assert_eq!(
+            sparse.update_async(lock).await.unwrap(),
+            UpdateOutcome::UpToDate
+        );
+        assert_eq!(
+            sparse
+                .krate_async("async-sparse".try_into().unwrap(), true, lock)
+                .await
+                .unwrap()
+                .expect("expected krate"),
+            sparse_krate
+        );
+    });
+
+    server.join().unwrap();
+}
+
 /// gix uses a default branch name of `main`, but most cargo git indexes on users
 /// disks use the master branch, so just ensure that we support that as well
 #[test]
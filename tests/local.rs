@@ -128,6 +128,675 @@ local-registry = "{local_path}""#,
     mdc.exec().expect("failed to gather metadata");
 }
 
+/// Validates a relative `local-registry` path is resolved relative to the
+/// config that defines it rather than the current directory
+#[test]
+fn resolves_relative_local_registry() {
+    let td = utils::tempdir();
+    let project = td.path().join("project");
+    let home = td.path().join("cargo-home");
+
+    std::fs::create_dir_all(project.join(".cargo")).unwrap();
+    std::fs::create_dir_all(project.join("src/nested")).unwrap();
+    std::fs::create_dir_all(&home).unwrap();
+
+    std::fs::write(
+        project.join(".cargo/config.toml"),
+        r#"
+[source.crates-io]
+replace-with = "test-registry"
+
+[source.test-registry]
+local-registry = "registry""#,
+    )
+    .unwrap();
+
+    // The path is the same regardless of where in the project the config is
+    // discovered from
+    for root in [project.clone(), project.join("src/nested")] {
+        let url = tame_index::IndexUrl::crates_io(Some(root), Some(&home), None).unwrap();
+        let tame_index::IndexUrl::Local(path) = &url else {
+            panic!("expected a local registry, got {url:?}");
+        };
+        assert_eq!(path.as_str(), project.join("registry").as_str());
+    }
+}
+
+/// Validates a crates.io replacement with a local registry opens a `Local`
+/// cache that reads the registry's index entries
+#[test]
+fn opens_local_replacement() {
+    let td = utils::tempdir();
+    let project = td.path().join("project");
+    let home = td.path().join("cargo-home");
+    let registry = project.join("registry");
+
+    std::fs::create_dir_all(project.join(".cargo")).unwrap();
+    std::fs::create_dir_all(&home).unwrap();
+
+    let krate = utils::fake_krate("local-krate", 3);
+    let lrb = local::LocalRegistryBuilder::create(registry).unwrap();
+    lrb.insert(&krate, &[]).unwrap();
+    lrb.finalize(true).unwrap();
+
+    std::fs::write(
+        project.join(".cargo/config.toml"),
+        r#"
+[source.crates-io]
+replace-with = "test-registry"
+
+[source.test-registry]
+local-registry = "registry""#,
+    )
+    .unwrap();
+
+    let url = tame_index::IndexUrl::crates_io(Some(project), Some(&home), None).unwrap();
+    let cache =
+        tame_index::index::ComboIndexCache::new(tame_index::IndexLocation::new(url)).unwrap();
+    assert!(matches!(
+        cache,
+        tame_index::index::ComboIndexCache::Local(_)
+    ));
+
+    let lock = utils::unlocked();
+    assert_eq!(
+        cache
+            .cached_krate("local-krate".try_into().unwrap(), &lock)
+            .unwrap()
+            .expect("failed to find local-krate"),
+        krate
+    );
+    assert!(cache
+        .cached_krate("missing".try_into().unwrap(), &lock)
+        .unwrap()
+        .is_none());
+}
+
+/// Creates a fake version whose checksum matches the contents of its crate file
+fn versioned(name: &str, version: &str) -> (tame_index::IndexVersion, Vec<u8>) {
+    use sha2::{Digest, Sha256};
+
+    let contents = format!("{name}-{version}").into_bytes();
+    let mut iv = tame_index::IndexVersion::fake(name, version);
+    iv.checksum = tame_index::krate::Chksum(Sha256::digest(&contents).into());
+    (iv, contents)
+}
+
+/// Validates new versions can be appended to an existing local registry
+/// without rewriting the existing crate files
+#[test]
+fn appends_to_local_registry() {
+    let td = utils::tempdir();
+    let root = td.path().join("registry");
+
+    let (v1, v1_contents) = versioned("appended", "1.0.0");
+    let (v2, v2_contents) = versioned("appended", "1.1.0");
+
+    let initial = tame_index::IndexKrate {
+        versions: vec![v1.clone()],
+    };
+
+    {
+        let lrb = local::LocalRegistryBuilder::create(root.clone()).unwrap();
+        let v1_krate = local::ValidKrate::validate(v1_contents.clone(), &v1).unwrap();
+        lrb.insert(&initial, &[v1_krate]).unwrap();
+        lrb.finalize(true).unwrap();
+    }
+
+    // Only an empty directory can be created
+    assert!(local::LocalRegistryBuilder::create(root.clone()).is_err());
+    assert!(local::LocalRegistryBuilder::open(td.path().join("missing")).is_err());
+
+    let updated = tame_index::IndexKrate {
+        versions: vec![v1.clone(), v2.clone()],
+    };
+
+    let lrb = local::LocalRegistryBuilder::open(root.clone()).unwrap();
+    let krates = [
+        local::ValidKrate::validate(v1_contents, &v1).unwrap(),
+        local::ValidKrate::validate(v2_contents.clone(), &v2).unwrap(),
+    ];
+    let report = lrb.insert(&updated, &krates).unwrap();
+
+    // The existing crate file is not written again
+    let index_entry = std::fs::metadata(root.join("index/ap/pe/appended")).unwrap();
+    assert_eq!(
+        report.bytes_written,
+        index_entry.len() + v2_contents.len() as u64
+    );
+    assert_eq!(report.written, ["1.1.0"]);
+    assert_eq!(report.skipped, ["1.0.0"]);
+
+    // Inserting the same versions again doesn't write anything
+    let report = lrb.insert(&updated, &krates).unwrap();
+    assert_eq!(report.bytes_written, 0);
+    assert_eq!(report.skipped, ["1.0.0", "1.1.0"]);
+
+    let lr = lrb.finalize(true).unwrap();
+    let krate = lr
+        .cached_krate("appended".try_into().unwrap(), &utils::unlocked())
+        .unwrap()
+        .expect("failed to find appended");
+    assert_eq!(krate.versions.len(), 2);
+    assert_eq!(krate.versions[0].version, "1.0.0");
+    assert_eq!(krate.versions[1].version, "1.1.0");
+}
+
+/// Validates crate files that don't match the index entry are rejected
+/// before anything is written
+#[test]
+fn rejects_invalid_inserts() {
+    use tame_index::{index::local::LocalRegistryError, Error};
+
+    let td = utils::tempdir();
+    let root = td.path().join("registry");
+
+    let (v1, v1_contents) = versioned("rejected", "1.0.0");
+    let (v2, v2_contents) = versioned("rejected", "2.0.0");
+    let (other, other_contents) = versioned("other", "1.0.0");
+
+    let lrb = local::LocalRegistryBuilder::create(root.clone()).unwrap();
+    let entry = tame_index::IndexKrate {
+        versions: vec![v1.clone()],
+    };
+
+    let insert = |krates: &[local::ValidKrate<'_>]| {
+        let Err(Error::Local(err)) = lrb.insert(&entry, krates) else {
+            panic!("expected a local registry error");
+        };
+        err
+    };
+
+    assert!(matches!(
+        insert(&[local::ValidKrate::validate(v2_contents.clone(), &v2).unwrap()]),
+        LocalRegistryError::VersionMismatch { version, .. } if version == "2.0.0"
+    ));
+    assert!(matches!(
+        insert(&[local::ValidKrate::validate(other_contents, &other).unwrap()]),
+        LocalRegistryError::VersionMismatch { name, .. } if name == "other"
+    ));
+
+    // The same version, but with different contents
+    let (mut corrupt, corrupt_contents) = versioned("rejected", "0.0.0");
+    corrupt.version = v1.version.clone();
+    assert!(matches!(
+        insert(&[local::ValidKrate::validate(corrupt_contents, &corrupt).unwrap()]),
+        LocalRegistryError::ChecksumMismatch { version, .. } if version == "1.0.0"
+    ));
+
+    assert!(matches!(
+        insert(&[
+            local::ValidKrate::validate(v1_contents.clone(), &v1).unwrap(),
+            local::ValidKrate::validate(v1_contents.clone(), &v1).unwrap(),
+        ]),
+        LocalRegistryError::DuplicateVersion { version, .. } if version == "1.0.0"
+    ));
+
+    assert!(!root.join("index/re/je/rejected").exists());
+
+    lrb.insert(
+        &entry,
+        &[local::ValidKrate::validate(v1_contents, &v1).unwrap()],
+    )
+    .unwrap();
+
+    // A version can't change checksum once it is in the registry
+    let conflicting = tame_index::IndexKrate {
+        versions: vec![corrupt.clone()],
+    };
+    assert!(matches!(
+        lrb.insert(&conflicting, &[]),
+        Err(Error::Local(LocalRegistryError::ConflictingVersion { .. }))
+    ));
+}
+
+/// Validates the contents of a local registry can be queried
+#[test]
+fn queries_local_registry() {
+    let td = utils::tempdir();
+    let root = td.path().join("registry");
+
+    let (v1, v1_contents) = versioned("queried", "0.1.0");
+    let (v2, _) = versioned("queried", "0.2.0");
+
+    let lrb = local::LocalRegistryBuilder::create(root.clone()).unwrap();
+    lrb.insert(
+        &tame_index::IndexKrate {
+            versions: vec![v1.clone(), v2.clone()],
+        },
+        &[local::ValidKrate::validate(v1_contents, &v1).unwrap()],
+    )
+    .unwrap();
+    lrb.finalize(false).unwrap();
+
+    assert!(local::LocalRegistry::open(td.path().to_owned(), false).is_err());
+
+    let lr = local::LocalRegistry::open(root.clone(), true).unwrap();
+    assert_eq!(lr.path(), root);
+
+    let krate = lr
+        .krate("queried".try_into().unwrap())
+        .unwrap()
+        .expect("failed to find queried");
+    assert_eq!(krate.versions, [v1.clone(), v2.clone()]);
+    assert!(lr.krate("missing".try_into().unwrap()).unwrap().is_none());
+
+    assert_eq!(
+        lr.crate_file_path(&v1),
+        Some(root.join("queried-0.1.0.crate"))
+    );
+    assert_eq!(lr.crate_file_path(&v2), None);
+}
+
+/// Validates verification reports every problem in a local registry
+#[test]
+fn verifies_local_registry() {
+    use local::{Severity, VerifyOptions, VerifyProblem};
+
+    let td = utils::tempdir();
+    let root = td.path().join("registry");
+
+    let (good, good_contents) = versioned("verified", "1.0.0");
+    let (corrupt, corrupt_contents) = versioned("verified", "1.1.0");
+    let (missing, _) = versioned("verified", "1.2.0");
+    let (mut yanked, _) = versioned("verified", "0.9.0");
+    yanked.yanked = true;
+
+    let lrb = local::LocalRegistryBuilder::create(root.clone()).unwrap();
+    lrb.insert(
+        &tame_index::IndexKrate {
+            versions: vec![yanked, good.clone(), corrupt.clone(), missing],
+        },
+        &[
+            local::ValidKrate::validate(good_contents, &good).unwrap(),
+            local::ValidKrate::validate(corrupt_contents, &corrupt).unwrap(),
+        ],
+    )
+    .unwrap();
+    let lr = lrb.finalize(false).unwrap();
+
+    let report = lr.verify().unwrap();
+    assert!(!report.is_clean() && report.has_errors());
+    assert_eq!(report.checked, 2);
+
+    std::fs::write(root.join("verified-1.1.0.crate"), "bit rot").unwrap();
+    std::fs::write(root.join("orphan-0.1.0.crate"), "orphan").unwrap();
+
+    let report = lr.verify().unwrap();
+    assert_eq!(report.checked, 2);
+    assert!(!report.stopped_early);
+
+    let verified = &report.crates["verified"];
+    assert_eq!(verified.errors.len(), 2);
+    assert!(verified
+        .errors
+        .iter()
+        .any(|p| matches!(p, VerifyProblem::MissingCrateFile { version } if version == "1.2.0")));
+    assert!(verified
+        .errors
+        .iter()
+        .any(|p| matches!(p, VerifyProblem::ChecksumMismatch { version } if version == "1.1.0")));
+    assert!(verified.warnings.is_empty());
+
+    let warnings: Vec<_> = report.problems(Severity::Warning).collect();
+    assert_eq!(warnings.len(), 1);
+    assert_eq!(warnings[0].0, "orphan");
+    assert!(matches!(
+        warnings[0].1,
+        VerifyProblem::OrphanedCrateFile { .. }
+    ));
+
+    // The missing crate file is found before any crate files are hashed
+    let report = lr.verify_with(VerifyOptions { fail_fast: true }).unwrap();
+    assert_eq!(report.checked, 0);
+    assert!(report.stopped_early);
+    assert_eq!(report.problems(Severity::Error).count(), 1);
+}
+
+/// Serves the files in the directory over HTTP until the test exits
+fn serve_dir(root: utils::PathBuf) -> std::net::SocketAddr {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buf).unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..read]);
+            }
+
+            let request = String::from_utf8(request).unwrap();
+            let path = request.split(' ').nth(1).unwrap_or("/");
+
+            match std::fs::read(root.join(path.trim_start_matches('/'))) {
+                Ok(body) => {
+                    write!(
+                        stream,
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                        body.len()
+                    )
+                    .unwrap();
+                    stream.write_all(&body).unwrap();
+                }
+                Err(_) => {
+                    stream
+                        .write_all(
+                            b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n",
+                        )
+                        .unwrap();
+                }
+            }
+        }
+    });
+
+    addr
+}
+
+/// Validates a local registry can be exported and consumed as a sparse index
+#[test]
+fn exports_sparse_index() {
+    let td = utils::tempdir();
+    let root = td.path().join("registry");
+    let out = td.path().join("sparse");
+
+    let (v1, v1_contents) = versioned("exported", "0.1.0");
+    let (v2, v2_contents) = versioned("exported", "0.2.0");
+    let (short, short_contents) = versioned("ex", "1.0.0");
+
+    let lrb = local::LocalRegistryBuilder::create(root).unwrap();
+    lrb.insert(
+        &tame_index::IndexKrate {
+            versions: vec![v1.clone(), v2.clone()],
+        },
+        &[
+            local::ValidKrate::validate(v1_contents, &v1).unwrap(),
+            local::ValidKrate::validate(v2_contents, &v2).unwrap(),
+        ],
+    )
+    .unwrap();
+    lrb.insert(
+        &tame_index::IndexKrate {
+            versions: vec![short.clone()],
+        },
+        &[local::ValidKrate::validate(short_contents, &short).unwrap()],
+    )
+    .unwrap();
+    let lr = lrb.finalize(true).unwrap();
+
+    // A leading / in the template is relative to the output directory, not
+    // the root of the filesystem
+    let rooted = td.path().join("rooted");
+    lr.export_sparse_with(
+        rooted.clone(),
+        "http://localhost/",
+        &local::SparseExportOptions {
+            dl_template: "/rooted-crates/{crate}/{version}/download".into(),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    assert!(rooted
+        .join("rooted-crates/exported/0.1.0/download")
+        .is_file());
+    assert!(!std::path::Path::new("/rooted-crates").exists());
+
+    let addr = serve_dir(out.clone());
+    let base_url = format!("http://{addr}");
+    lr.export_sparse(out.clone(), &base_url).unwrap();
+
+    assert!(out.join("ex/po/exported").is_file());
+    assert!(out.join("2/ex").is_file());
+    assert!(out.join("crates/exported/0.2.0/download").is_file());
+
+    let index_td = utils::tempdir();
+    let sparse = tame_index::index::RemoteSparseIndex::new(
+        tame_index::SparseIndex::new(tame_index::IndexLocation {
+            url: tame_index::IndexUrl::NonCratesIo(format!("sparse+{base_url}/").into()),
+            root: tame_index::IndexPath::Exact(index_td.path().to_owned()),
+        })
+        .unwrap(),
+        reqwest::blocking::Client::new(),
+    );
+
+    let config = sparse.index_config().unwrap();
+    assert_eq!(
+        config.dl,
+        format!("{base_url}/crates/{{crate}}/{{version}}/download")
+    );
+
+    let lock = utils::unlocked();
+    let krate = sparse
+        .krate("exported".try_into().unwrap(), false, &lock)
+        .unwrap()
+        .expect("failed to find exported");
+    assert_eq!(krate.versions, [v1, v2.clone()]);
+    assert!(sparse
+        .krate("ex".try_into().unwrap(), false, &lock)
+        .unwrap()
+        .is_some());
+
+    let client = local::builder::Client::build(reqwest::blocking::ClientBuilder::new()).unwrap();
+    // The download is validated against the checksum in the index
+    local::ValidKrate::download(&client, &config, &v2).unwrap();
+}
+
+/// Validates a local registry can be built from a lockfile, and that failures
+/// for individual packages are reported without stopping the others
+#[test]
+fn builds_from_lockfile() {
+    let td = utils::tempdir();
+    let upstream = td.path().join("upstream");
+    let served = td.path().join("served");
+
+    let (a, a_contents) = versioned("locked-a", "1.0.0");
+    let (b, b_contents) = versioned("locked-b", "0.2.0");
+
+    let lrb = local::LocalRegistryBuilder::create(upstream).unwrap();
+    for (iv, contents) in [(&a, a_contents), (&b, b_contents)] {
+        lrb.insert(
+            &tame_index::IndexKrate {
+                versions: vec![iv.clone()],
+            },
+            &[local::ValidKrate::validate(contents, iv).unwrap()],
+        )
+        .unwrap();
+    }
+
+    let addr = serve_dir(served.clone());
+    let base_url = format!("http://{addr}");
+    lrb.finalize(true)
+        .unwrap()
+        .export_sparse(served, &base_url)
+        .unwrap();
+
+    let source = format!("sparse+{base_url}/");
+    let lockfile = td.path().join("Cargo.lock");
+    let write_lockfile = |extra: &str| {
+        std::fs::write(
+            &lockfile,
+            format!(
+                r#"
+version = 3
+
+[[package]]
+name = "locked-a"
+version = "1.0.0"
+source = "{source}"
+checksum = "{}"
+
+[[package]]
+name = "locked-b"
+version = "0.2.0"
+source = "{source}"
+checksum = "{}"
+
+[[package]]
+name = "not-from-the-index"
+version = "1.0.0"
+source = "registry+https://github.com/rust-lang/crates.io-index"
+{extra}
+"#,
+                a.checksum, b.checksum
+            ),
+        )
+        .unwrap();
+    };
+
+    let index_td = utils::tempdir();
+    let sparse = tame_index::index::RemoteSparseIndex::new(
+        tame_index::SparseIndex::new(tame_index::IndexLocation {
+            url: tame_index::IndexUrl::NonCratesIo(source.clone().into()),
+            root: tame_index::IndexPath::Exact(index_td.path().to_owned()),
+        })
+        .unwrap(),
+        reqwest::blocking::Client::new(),
+    );
+    let client = local::builder::Client::build(reqwest::blocking::ClientBuilder::new()).unwrap();
+    let lock = utils::unlocked();
+
+    write_lockfile("");
+
+    let completed = std::sync::atomic::AtomicUsize::new(0);
+    let progress = |p: local::builder::LockfileProgress<'_>| {
+        assert!(p.ok, "{}-{} failed", p.name, p.version);
+        assert_eq!(p.total, 2);
+        completed.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    };
+
+    let out = td.path().join("out");
+    let lr = local::builder::from_lockfile(
+        &lockfile,
+        &sparse,
+        &client,
+        out.clone(),
+        local::builder::LockfileOptions {
+            progress: Some(&progress),
+            ..Default::default()
+        },
+        &lock,
+    )
+    .unwrap();
+    assert_eq!(completed.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+    assert!(lr.crate_file_path(&a).is_some());
+    assert!(lr.crate_file_path(&b).is_some());
+    assert!(lr.verify().unwrap().is_clean());
+
+    // A version that isn't in the index fails, but the others are still added
+    write_lockfile(&format!(
+        r#"
+[[package]]
+name = "locked-a"
+version = "9.9.9"
+source = "{source}"
+"#
+    ));
+
+    let Err(tame_index::Error::Local(local::LocalRegistryError::Packages(failed))) =
+        local::builder::from_lockfile(
+            &lockfile,
+            &sparse,
+            &client,
+            out,
+            local::builder::LockfileOptions {
+                reuse_existing: true,
+                ..Default::default()
+            },
+            &lock,
+        )
+    else {
+        panic!("expected the missing version to fail");
+    };
+
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0].name, "locked-a");
+    assert_eq!(failed[0].version, "9.9.9");
+}
+
+/// Validates downloads are retried after server errors, and that the
+/// `{sha256-checksum}` marker is expanded in the download url
+#[test]
+fn retries_checksum_download() {
+    use std::io::{Read, Write};
+
+    let (iv, contents) = versioned("retried", "1.0.0");
+    let expected_path = format!("/retried/{}.crate", iv.checksum);
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        for i in 0..2 {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = Vec::new();
+            let mut buf = [0u8; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                let read = stream.read(&mut buf).unwrap();
+                assert!(read > 0);
+                request.extend_from_slice(&buf[..read]);
+            }
+
+            let request = String::from_utf8(request).unwrap();
+            assert!(request.starts_with(&format!("GET {expected_path} ")));
+
+            if i == 0 {
+                stream
+                    .write_all(b"HTTP/1.1 503 Service Unavailable\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                    .unwrap();
+            } else {
+                write!(
+                    stream,
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\nconnection: close\r\n\r\n",
+                    contents.len()
+                )
+                .unwrap();
+                stream.write_all(&contents).unwrap();
+            }
+        }
+    });
+
+    let config = tame_index::index::IndexConfig {
+        dl: format!("http://{addr}/{{crate}}/{{sha256-checksum}}.crate"),
+        api: None,
+    };
+
+    let client = local::builder::Client::build(reqwest::blocking::ClientBuilder::new()).unwrap();
+    local::ValidKrate::download(&client, &config, &iv).unwrap();
+    server.join().unwrap();
+
+    // Client errors are not retried
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut buf = [0u8; 1024];
+        let _ = stream.read(&mut buf).unwrap();
+        stream
+            .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+            .unwrap();
+        listener
+    });
+
+    let config = tame_index::index::IndexConfig {
+        dl: format!("http://{addr}/{{crate}}/{{sha256-checksum}}.crate"),
+        api: None,
+    };
+    assert!(local::ValidKrate::download(&client, &config, &iv).is_err());
+
+    let listener = server.join().unwrap();
+    listener.set_nonblocking(true).unwrap();
+    assert!(listener.accept().is_err());
+}
+
 /// Validates we get the correct checksum for a crate
 #[test]
 fn downloads_and_verifies() {
@@ -145,12 +814,25 @@ fn downloads_and_verifies() {
 
     let body = res.bytes().unwrap();
 
+    let chksum = "7706a72ab36d8cb1f80ffbf0e071533974a60d0a308d01a5d0375bf60499a342"
+        .parse()
+        .unwrap();
+
     use bytes::Buf;
-    assert!(local::validate_checksum::<{ 16 * 1024 }>(
-        body.reader(),
-        &("7706a72ab36d8cb1f80ffbf0e071533974a60d0a308d01a5d0375bf60499a342"
-            .parse()
-            .unwrap()),
+    assert!(local::validate_checksum::<{ 16 * 1024 }>(body.clone().reader(), &chksum).unwrap());
+
+    // The tee variant writes the exact bytes while hashing them
+    let td = utils::tempdir();
+    let path = td.path().join("wasm-bindgen-0.2.87.crate");
+    let copied = local::copy_and_validate(
+        body.clone().reader(),
+        std::fs::File::create(&path).unwrap(),
+        &chksum,
     )
-    .unwrap());
+    .unwrap();
+    assert_eq!(copied, body.len() as u64);
+    assert_eq!(std::fs::read(&path).unwrap(), body.as_ref());
+
+    let mut copy = Vec::new();
+    assert!(local::copy_and_validate(body.slice(1..).reader(), &mut copy, &chksum,).is_err());
 }
@@ -46,6 +46,62 @@ fn make_request_without_cache() {
     assert_eq!(hdrs.get(header::ACCEPT_ENCODING).unwrap(), "gzip");
 }
 
+/// Validates the index config is read from disk for cache only access, and
+/// that indices without one report an error
+#[test]
+fn reads_index_config() {
+    use tame_index::index::{ComboIndexCache, DirectorySource, IndexPath};
+
+    let td = utils::tempdir();
+    let index = || {
+        ComboIndexCache::Sparse(
+            SparseIndex::new(IndexLocation {
+                url: IndexUrl::CratesIoSparse,
+                root: IndexPath::Exact(td.path().to_owned()),
+            })
+            .unwrap(),
+        )
+    };
+
+    assert!(index().index_config().is_err());
+
+    std::fs::write(
+        td.path().join("config.json"),
+        r#"{"dl":"https://static.crates.io/crates","api":"https://crates.io"}"#,
+    )
+    .unwrap();
+
+    let config = index().index_config().unwrap();
+    assert_eq!(config.dl, "https://static.crates.io/crates");
+    assert_eq!(config.api.as_deref(), Some("https://crates.io"));
+
+    let directory =
+        ComboIndexCache::Directory(DirectorySource::open(td.path().to_owned()).unwrap());
+    assert!(directory.index_config().is_err());
+}
+
+/// Validates the cache is accessible regardless of the kind of index
+#[test]
+fn combo_cache() {
+    use tame_index::index::{ComboIndexCache, DirectorySource};
+
+    let td = utils::tempdir();
+    let index = ComboIndexCache::Sparse(crates_io(&td));
+    let lock = &utils::unlocked();
+
+    let krate = utils::fake_krate("combo-cache", 2);
+    let cache = index.cache().expect("sparse indices have a cache");
+    cache.write_to_cache(&krate, "etag: \"1\"", lock).unwrap();
+
+    let name = "combo-cache".try_into().unwrap();
+    assert_eq!(cache.cache_path(name), index.cache_path(name));
+    assert_eq!(index.cached_krate(name, lock).unwrap(), Some(krate));
+
+    let directory =
+        ComboIndexCache::Directory(DirectorySource::open(td.path().to_owned()).unwrap());
+    assert!(directory.cache().is_none());
+}
+
 const ETAG: &str = "W/\"fa62f662c9aae1f21cab393950d4ae23\"";
 const DATE: &str = "Thu, 22 Oct 2023 09:40:03 GMT";
 
@@ -97,6 +153,36 @@ fn make_request_with_cache() {
     }
 }
 
+/// Validates the async request builder reads the same cache entry headers
+#[test]
+fn make_request_with_cache_async() {
+    let td = utils::tempdir();
+    let index = crates_io(&td);
+    let lock = &utils::unlocked();
+
+    let rt = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+
+    let etag_krate = utils::fake_krate("etag-krate", 2);
+    index
+        .cache()
+        .write_to_cache(&etag_krate, &format!("{}: {ETAG}", header::ETAG), lock)
+        .unwrap();
+
+    let req = rt
+        .block_on(index.make_remote_request_async("etag-krate".try_into().unwrap(), None, lock))
+        .unwrap();
+    assert_eq!(req.headers().get(header::IF_NONE_MATCH).unwrap(), ETAG);
+
+    let req = rt
+        .block_on(index.make_remote_request_async("no-cache-krate".try_into().unwrap(), None, lock))
+        .unwrap();
+    assert!(req.headers().get(header::IF_NONE_MATCH).is_none());
+    assert!(req.headers().get(header::IF_MODIFIED_SINCE).is_none());
+}
+
 /// Validates we can parse a response where the local cache version is up to date
 #[test]
 fn parse_unmodified_response() {
@@ -10,7 +10,7 @@ pub struct TempDir {
 impl TempDir {
     #[inline]
     pub fn path(&self) -> &Path {
-        Path::from_path(self.td.path()).unwrap()
+        tame_index::utils::to_utf8_path(self.td.path()).unwrap()
     }
 }
 